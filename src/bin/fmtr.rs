@@ -1,25 +1,30 @@
+use audiotools::utils::logging::{self, Verbosity};
+use audiotools::utils::{format_size, is_stdio};
 use clap::Parser;
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::Write;
 use std::path::PathBuf;
 
 // Command line interface configuration
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    /// Input file (output file from audiotools)
+    /// Input file (output file from audiotools), or - to read stdin
     #[arg(short, long)]
     input: PathBuf,
 
-    /// Output JSON file path
+    /// Output JSON file path, or - to write to stdout
     #[arg(short, long)]
     output: PathBuf,
 
     /// Analysis type (info or loudness)
     #[arg(short, long)]
     type_: String,
+
+    #[command(flatten)]
+    verbosity: Verbosity,
 }
 
 // Structure for storing audio file information
@@ -27,9 +32,31 @@ struct Cli {
 struct AudioInfo {
     file_path: String,
     format: String,
-    size: AudioSize,
+    size: Option<AudioSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    wav_header: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    broadcast: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    dc_offset: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    spectral: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    stereo: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    channel_levels: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    silence: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    level_histogram: HashMap<String, String>,
     format_info: HashMap<String, String>,
     stream_info: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<String>,
+    #[serde(default)]
+    parse_warnings: Vec<String>,
 }
 
 // Structure for file size information
@@ -44,140 +71,513 @@ struct AudioSize {
 struct LoudnessInfo {
     file_path: String,
     format: String,
-    size: AudioSize,
+    size: Option<AudioSize>,
     loudness: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<String>,
+    #[serde(default)]
+    parse_warnings: Vec<String>,
+}
+
+// One entry of `audiotools loudness --output-format json`
+#[derive(Deserialize)]
+struct NativeLoudness {
+    path: String,
+    integrated_lufs: Option<f32>,
+    loudness_range_lu: Option<f32>,
+    true_peak_dbfs: Option<f32>,
+    threshold_lufs: Option<f32>,
+    active_region: Option<NativeActiveRegion>,
+    dynamics: Option<NativeDynamics>,
+}
+
+// The `active_region` object written with `loudness --gate-silence`
+#[derive(Deserialize)]
+struct NativeActiveRegion {
+    start_s: f32,
+    end_s: f32,
+    integrated_lufs: Option<f32>,
+}
+
+// The `dynamics` object written with `loudness --dynamics`
+#[derive(Deserialize)]
+struct NativeDynamics {
+    sample_peak_dbfs: Option<f32>,
+    rms_dbfs: Option<f32>,
+    crest_factor_db: Option<f32>,
+    dr: Option<f32>,
+}
+
+// Section of the info text output that the current line belongs to
+#[derive(PartialEq)]
+enum InfoSection {
+    Summary,
+    WavHeader,
+    Broadcast,
+    DcOffset,
+    Spectral,
+    Stereo,
+    ChannelLevels,
+    Silence,
+    LevelHistogram,
+    HeaderSummary,
+    Format,
+    Stream,
 }
 
 // Main function - handles command line arguments and dispatches processing
 fn main() {
     let cli = Cli::parse();
-    let file = File::open(&cli.input).expect("Failed to open input file");
-    let reader = BufReader::new(file);
+    logging::init_logger(&cli.verbosity);
 
-    match cli.type_.as_str() {
-        "info" => process_info(reader, &cli.output),
-        "loudness" => process_loudness(reader, &cli.output),
-        _ => panic!("Invalid type. Use 'info' or 'loudness'"),
+    let content = if is_stdio(&cli.input) {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(&cli.input)
+    };
+    let content = match content {
+        Ok(content) => content,
+        Err(e) => {
+            error!("Failed to open {}: {}", cli.input.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let is_json = content.trim_start().starts_with('[');
+
+    match (cli.type_.as_str(), is_json) {
+        ("info", false) => process_info(&content, &cli.output),
+        ("loudness", false) => process_loudness(&content, &cli.output),
+        ("loudness", true) => process_loudness_json(&content, &cli.output),
+        ("info", true) => {
+            error!("JSON input is only supported for loudness");
+            std::process::exit(1);
+        }
+        _ => {
+            error!("Invalid type. Use 'info' or 'loudness'");
+            std::process::exit(1);
+        }
     }
 }
 
+// Parse "86.83 KB (88910 bytes)" into its formatted and byte parts
+fn parse_size(size_str: &str) -> Option<AudioSize> {
+    let (formatted, bytes) = size_str.rsplit_once(" (")?;
+    Some(AudioSize {
+        formatted: formatted.to_string(),
+        bytes: bytes.strip_suffix(" bytes)")?.parse().ok()?,
+    })
+}
+
+// Record a line that could not be parsed. Lines before the first "File:" have
+// no entry to attach to and are only logged
+fn unparsed(
+    warnings: Option<&mut Vec<String>>,
+    line_number: usize,
+    reason: &str,
+    line: &str,
+    count: &mut usize,
+) {
+    *count += 1;
+    let message = format!("line {}: {}: {}", line_number, reason, line.trim());
+    match warnings {
+        Some(warnings) => warnings.push(message),
+        None => warn!("{}", message),
+    }
+}
+
+// Write the JSON output and report how many lines were skipped
+fn write_json<T: Serialize>(entries: &[T], output_path: &PathBuf, unparsed_lines: usize) {
+    let json = serde_json::to_string_pretty(entries).expect("Failed to serialize to JSON");
+    // On stdout only the JSON is printed; warnings still go to stderr. A closed
+    // pipe (e.g. `| head`) is not an error
+    if is_stdio(output_path) {
+        let _ = writeln!(std::io::stdout(), "{}", json);
+        if unparsed_lines > 0 {
+            warn!(
+                "{} lines could not be parsed (see parse_warnings)",
+                unparsed_lines
+            );
+        }
+        return;
+    }
+
+    std::fs::write(output_path, json).expect("Failed to write JSON file");
+    if unparsed_lines > 0 {
+        warn!(
+            "{} lines could not be parsed (see parse_warnings in {})",
+            unparsed_lines,
+            output_path.display()
+        );
+    }
+    info!(
+        "Wrote {} entries to {}",
+        entries.len(),
+        output_path.display()
+    );
+}
+
 // Process audio information and convert to JSON
-fn process_info(reader: BufReader<File>, output_path: &PathBuf) {
+fn process_info(content: &str, output_path: &PathBuf) {
     let mut audio_files: Vec<AudioInfo> = Vec::new();
     let mut current_file: Option<AudioInfo> = None;
-    let mut in_format_section = false;
-    let mut in_stream_section = false;
+    let mut section = InfoSection::Summary;
+    let mut has_size_line = false;
+    let mut unparsed_lines = 0;
 
-    for line in reader.lines() {
-        let line = line.expect("Failed to read line");
-        if line.starts_with("File: ") {
-            if let Some(file_info) = current_file {
-                audio_files.push(file_info);
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        if let Some(path) = line.strip_prefix("File: ") {
+            if let Some(file_info) = current_file.take() {
+                audio_files.push(finish_info(file_info, has_size_line));
             }
+            debug!("Parsing {}", path);
             current_file = Some(AudioInfo {
-                file_path: line.trim_start_matches("File: ").to_string(),
+                file_path: path.to_string(),
                 format: String::new(),
-                size: AudioSize {
-                    formatted: String::new(),
-                    bytes: 0,
-                },
+                size: None,
+                hash: None,
+                wav_header: HashMap::new(),
+                broadcast: HashMap::new(),
+                dc_offset: HashMap::new(),
+                spectral: HashMap::new(),
+                stereo: HashMap::new(),
+                channel_levels: HashMap::new(),
+                silence: HashMap::new(),
+                level_histogram: HashMap::new(),
                 format_info: HashMap::new(),
                 stream_info: HashMap::new(),
+                errors: Vec::new(),
+                parse_warnings: Vec::new(),
             });
-        } else if line.starts_with("Format: ") && current_file.is_some() {
-            if let Some(ref mut file) = current_file {
-                file.format = line.trim_start_matches("Format: ").to_string();
+            section = InfoSection::Summary;
+            has_size_line = false;
+            continue;
+        }
+
+        let Some(file) = current_file.as_mut() else {
+            if !line.trim().is_empty() {
+                unparsed(
+                    None,
+                    line_number,
+                    "outside of a file entry",
+                    line,
+                    &mut unparsed_lines,
+                );
             }
-        } else if line.starts_with("Size: ") && current_file.is_some() {
-            if let Some(ref mut file) = current_file {
-                let size_str = line.trim_start_matches("Size: ");
-                let parts: Vec<&str> = size_str.split(" (").collect();
-                if parts.len() == 2 {
-                    file.size.formatted = parts[0].to_string();
-                    file.size.bytes = parts[1].trim_end_matches(" bytes)").parse().unwrap_or(0);
+            continue;
+        };
+
+        match line.trim() {
+            "[FORMAT]" => section = InfoSection::Format,
+            "[STREAM]" => section = InfoSection::Stream,
+            "[/FORMAT]" | "[/STREAM]" => section = InfoSection::Summary,
+            "WAV Header Information:" => section = InfoSection::WavHeader,
+            "Broadcast Info:" => section = InfoSection::Broadcast,
+            "DC Offset:" => section = InfoSection::DcOffset,
+            "Spectral:" => section = InfoSection::Spectral,
+            "Stereo:" => section = InfoSection::Stereo,
+            "Channel Levels:" => section = InfoSection::ChannelLevels,
+            "Silence:" => section = InfoSection::Silence,
+            "Level Histogram:" => section = InfoSection::LevelHistogram,
+            "Header Information (without ffprobe):" => section = InfoSection::HeaderSummary,
+            // Blank lines end the header, bext and analysis blocks
+            "" => {
+                if matches!(
+                    section,
+                    InfoSection::WavHeader
+                        | InfoSection::Broadcast
+                        | InfoSection::DcOffset
+                        | InfoSection::Spectral
+                        | InfoSection::Stereo
+                        | InfoSection::ChannelLevels
+                        | InfoSection::Silence
+                        | InfoSection::LevelHistogram
+                        | InfoSection::HeaderSummary
+                ) {
+                    section = InfoSection::Summary;
                 }
             }
-        } else if line.starts_with("[FORMAT]") {
-            in_format_section = true;
-            in_stream_section = false;
-        } else if line.starts_with("[/FORMAT]") {
-            in_format_section = false;
-        } else if line.starts_with("[STREAM]") {
-            in_format_section = false;
-            in_stream_section = true;
-        } else if line.starts_with("[/STREAM]") {
-            in_stream_section = false;
-        } else if !line.is_empty() && !line.starts_with("[") && current_file.is_some() {
-            let parts: Vec<&str> = line.split('=').collect();
-            if parts.len() == 2 {
-                let key = parts[0].trim();
-                let value = parts[1].trim();
-                if let Some(ref mut file) = current_file {
-                    if in_format_section {
-                        file.format_info.insert(key.to_string(), value.to_string());
-                    } else if in_stream_section {
-                        file.stream_info.insert(key.to_string(), value.to_string());
+            "Error reading WAV header" | "Format information unavailable" => {
+                file.errors.push(line.trim().to_string());
+            }
+            trimmed => match section {
+                InfoSection::Format | InfoSection::Stream => match trimmed.split_once('=') {
+                    Some((key, value)) => {
+                        let map = if section == InfoSection::Format {
+                            &mut file.format_info
+                        } else {
+                            &mut file.stream_info
+                        };
+                        map.insert(key.trim().to_string(), value.trim().to_string());
+                    }
+                    None => unparsed(
+                        Some(&mut file.parse_warnings),
+                        line_number,
+                        "expected key=value",
+                        line,
+                        &mut unparsed_lines,
+                    ),
+                },
+                // Files without any sound have no leading/trailing split, and
+                // files that could not be decoded report "n/a"
+                InfoSection::Silence if trimmed == "n/a" => {}
+                InfoSection::Silence if trimmed.starts_with("Entire file is silent") => {
+                    let duration = trimmed
+                        .strip_prefix("Entire file is silent (")
+                        .and_then(|rest| rest.strip_suffix(") [silent]"));
+                    match duration {
+                        Some(duration) => {
+                            file.silence
+                                .insert("Entire file silent".to_string(), duration.to_string());
+                        }
+                        None => unparsed(
+                            Some(&mut file.parse_warnings),
+                            line_number,
+                            "invalid silence line",
+                            line,
+                            &mut unparsed_lines,
+                        ),
                     }
                 }
-            }
+                // "<low> to <high> dBFS <pct>% ###": the range is the key and the
+                // share the value (the bar only repeats the share)
+                InfoSection::LevelHistogram if trimmed == "n/a" => {}
+                InfoSection::LevelHistogram => {
+                    let bin = trimmed.split_once(" dBFS ").and_then(|(range, rest)| {
+                        let percent = rest.split_whitespace().next()?;
+                        percent.strip_suffix('%')?.parse::<f32>().ok()?;
+                        let range: Vec<&str> = range.split_whitespace().collect();
+                        Some((format!("{} dBFS", range.join(" ")), percent.to_string()))
+                    });
+                    match bin {
+                        Some((range, percent)) => {
+                            file.level_histogram.insert(range, percent);
+                        }
+                        None => unparsed(
+                            Some(&mut file.parse_warnings),
+                            line_number,
+                            "expected \"<low> to <high> dBFS <pct>%\"",
+                            line,
+                            &mut unparsed_lines,
+                        ),
+                    }
+                }
+                InfoSection::HeaderSummary => match header_summary_field(trimmed) {
+                    Some((is_format, key, value)) => {
+                        let map = if is_format {
+                            &mut file.format_info
+                        } else {
+                            &mut file.stream_info
+                        };
+                        map.insert(key.to_string(), value.to_string());
+                    }
+                    None => unparsed(
+                        Some(&mut file.parse_warnings),
+                        line_number,
+                        "unrecognized header field",
+                        line,
+                        &mut unparsed_lines,
+                    ),
+                },
+                InfoSection::WavHeader
+                | InfoSection::Broadcast
+                | InfoSection::DcOffset
+                | InfoSection::Spectral
+                | InfoSection::Stereo
+                | InfoSection::ChannelLevels
+                | InfoSection::Silence => match trimmed.split_once(": ") {
+                    Some((key, value)) => {
+                        let map = match section {
+                            InfoSection::WavHeader => &mut file.wav_header,
+                            InfoSection::Broadcast => &mut file.broadcast,
+                            InfoSection::DcOffset => &mut file.dc_offset,
+                            InfoSection::Spectral => &mut file.spectral,
+                            InfoSection::Stereo => &mut file.stereo,
+                            InfoSection::ChannelLevels => &mut file.channel_levels,
+                            _ => &mut file.silence,
+                        };
+                        map.insert(key.to_string(), value.to_string());
+                    }
+                    None => unparsed(
+                        Some(&mut file.parse_warnings),
+                        line_number,
+                        "expected \"key: value\"",
+                        line,
+                        &mut unparsed_lines,
+                    ),
+                },
+                InfoSection::Summary => {
+                    if let Some(format) = trimmed.strip_prefix("Format: ") {
+                        file.format = format.to_string();
+                    } else if let Some(size_str) = trimmed.strip_prefix("Size: ") {
+                        has_size_line = true;
+                        file.size = parse_size(size_str);
+                        if file.size.is_none() {
+                            unparsed(
+                                Some(&mut file.parse_warnings),
+                                line_number,
+                                "invalid size",
+                                line,
+                                &mut unparsed_lines,
+                            );
+                        }
+                    } else if let Some(hash) = trimmed.strip_prefix("Hash: ") {
+                        file.hash = Some(hash.to_string());
+                    } else if let Some(message) = trimmed.strip_prefix("Error: ") {
+                        file.errors.push(message.to_string());
+                    } else {
+                        unparsed(
+                            Some(&mut file.parse_warnings),
+                            line_number,
+                            "unrecognized line",
+                            line,
+                            &mut unparsed_lines,
+                        );
+                    }
+                }
+            },
         }
     }
 
     if let Some(file_info) = current_file {
-        audio_files.push(file_info);
+        audio_files.push(finish_info(file_info, has_size_line));
     }
 
-    let json = serde_json::to_string_pretty(&audio_files).expect("Failed to serialize to JSON");
-    std::fs::write(output_path, json).expect("Failed to write JSON file");
+    write_json(&audio_files, output_path, unparsed_lines);
+}
+
+// Map a line of the `info --no-ffprobe` header block to the ffprobe key it
+// stands for, as (goes in format_info, key, value without the unit)
+fn header_summary_field(line: &str) -> Option<(bool, &'static str, &str)> {
+    let (label, value) = line.split_once(": ")?;
+    match label {
+        "Codec" => Some((false, "codec_name", value)),
+        "Sample Rate" => Some((false, "sample_rate", value.strip_suffix(" Hz")?)),
+        "Channels" => Some((false, "channels", value)),
+        "Bit Depth" => Some((false, "bits_per_sample", value.strip_suffix(" bits")?)),
+        "Duration" => Some((true, "duration", value.strip_suffix(" s")?)),
+        _ => None,
+    }
+}
+
+// Note summary lines that never appeared (entries for failed files only carry the error)
+fn finish_info(mut file: AudioInfo, has_size_line: bool) -> AudioInfo {
+    if file.errors.is_empty() || !file.format.is_empty() {
+        if file.format.is_empty() {
+            file.parse_warnings.push("missing Format line".to_string());
+        }
+        if !has_size_line {
+            file.parse_warnings.push("missing Size line".to_string());
+        }
+    }
+    file
 }
 
 // Process loudness information and convert to JSON
-fn process_loudness(reader: BufReader<File>, output_path: &PathBuf) {
+fn process_loudness(content: &str, output_path: &PathBuf) {
     let mut audio_files: Vec<LoudnessInfo> = Vec::new();
     let mut current_file: Option<LoudnessInfo> = None;
+    let mut in_analysis = false;
+    let mut section = String::new();
+    let mut unparsed_lines = 0;
 
     // Process input file line by line
-    for line in reader.lines() {
-        let line = line.expect("Failed to read line");
-        if line.starts_with("File: ") {
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        if let Some(path) = line.strip_prefix("File: ") {
             // Start processing new file
-            if let Some(file_info) = current_file {
+            if let Some(file_info) = current_file.take() {
                 audio_files.push(file_info);
             }
+            debug!("Parsing {}", path);
             current_file = Some(LoudnessInfo {
-                file_path: line.trim_start_matches("File: ").to_string(),
+                file_path: path.to_string(),
                 format: String::new(),
-                size: AudioSize {
-                    formatted: String::new(),
-                    bytes: 0,
-                },
+                size: None,
                 loudness: HashMap::new(),
+                errors: Vec::new(),
+                parse_warnings: Vec::new(),
             });
-        } else if line.starts_with("Format: ") && current_file.is_some() {
-            // Extract format information
-            if let Some(ref mut file) = current_file {
-                file.format = line.trim_start_matches("Format: ").to_string();
-            }
-        } else if line.starts_with("Size: ") && current_file.is_some() {
-            // Parse file size information
-            if let Some(ref mut file) = current_file {
-                let size_str = line.trim_start_matches("Size: ");
-                let parts: Vec<&str> = size_str.split(" (").collect();
-                if parts.len() == 2 {
-                    file.size.formatted = parts[0].to_string();
-                    file.size.bytes = parts[1].trim_end_matches(" bytes)").parse().unwrap_or(0);
-                }
+            in_analysis = false;
+            continue;
+        }
+
+        let Some(file) = current_file.as_mut() else {
+            if !line.trim().is_empty() {
+                unparsed(
+                    None,
+                    line_number,
+                    "outside of a file entry",
+                    line,
+                    &mut unparsed_lines,
+                );
             }
-        } else if line.contains("LUFS") || line.contains("LU") || line.contains("True Peak") {
-            // Extract loudness measurements
-            if let Some(ref mut file) = current_file {
-                let parts: Vec<&str> = line.trim().split(':').collect();
-                if parts.len() == 2 {
-                    let key = parts[0].trim();
-                    let value = parts[1].trim();
-                    file.loudness.insert(key.to_string(), value.to_string());
+            continue;
+        };
+
+        // ffmpeg prefixes its lines with "[Parsed_ebur128_0 @ 0x...] "
+        let trimmed = match line.trim().split_once("] ") {
+            Some((prefix, rest)) if prefix.starts_with('[') => rest.trim(),
+            _ => line.trim(),
+        };
+
+        if trimmed.is_empty() {
+            continue;
+        } else if !in_analysis {
+            if let Some(format) = trimmed.strip_prefix("Format: ") {
+                // Extract format information
+                file.format = format.to_string();
+            } else if let Some(size_str) = trimmed.strip_prefix("Size: ") {
+                // Parse file size information
+                file.size = parse_size(size_str);
+                if file.size.is_none() {
+                    unparsed(
+                        Some(&mut file.parse_warnings),
+                        line_number,
+                        "invalid size",
+                        line,
+                        &mut unparsed_lines,
+                    );
                 }
+            } else if trimmed == "Loudness Analysis:" {
+                in_analysis = true;
+                section.clear();
+            } else if let Some(message) = trimmed.strip_prefix("Error: ") {
+                file.errors.push(message.to_string());
+            } else {
+                unparsed(
+                    Some(&mut file.parse_warnings),
+                    line_number,
+                    "unrecognized line",
+                    line,
+                    &mut unparsed_lines,
+                );
             }
+        } else if trimmed.starts_with("t:") {
+            // Running measurements printed while ffmpeg processes the file
+        } else if let Some(header) = trimmed.strip_suffix(':') {
+            // Section headers such as "Integrated loudness:" and "Loudness range:"
+            section = header.to_string();
+        } else if let Some((key, value)) = trimmed.split_once(':') {
+            // Extract loudness measurements; both the integrated and the
+            // loudness range sections report a "Threshold", the
+            // --gate-silence section repeats "I" and the --dynamics section
+            // repeats "Peak"
+            let key = match key.trim() {
+                "Threshold" if section == "Loudness range" => "LRA Threshold".to_string(),
+                key if section == "Active region" => format!("Active {}", key),
+                key if section == "Dynamics" => format!("Dynamics {}", key),
+                key => key.to_string(),
+            };
+            file.loudness.insert(key, value.trim().to_string());
+        } else {
+            unparsed(
+                Some(&mut file.parse_warnings),
+                line_number,
+                "unrecognized measurement",
+                line,
+                &mut unparsed_lines,
+            );
         }
     }
 
@@ -186,7 +586,75 @@ fn process_loudness(reader: BufReader<File>, output_path: &PathBuf) {
         audio_files.push(file_info);
     }
 
-    // Write JSON output
-    let json = serde_json::to_string_pretty(&audio_files).expect("Failed to serialize to JSON");
-    std::fs::write(output_path, json).expect("Failed to write JSON file");
+    write_json(&audio_files, output_path, unparsed_lines);
+}
+
+// Convert `audiotools loudness --output-format json` output to the same layout
+// as the text parser
+fn process_loudness_json(content: &str, output_path: &PathBuf) {
+    let entries: Vec<NativeLoudness> = match serde_json::from_str(content) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Invalid loudness JSON: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let audio_files: Vec<LoudnessInfo> = entries
+        .into_iter()
+        .map(|entry| {
+            let path = PathBuf::from(&entry.path);
+            let mut loudness = HashMap::new();
+            for (key, value, unit) in [
+                ("I", entry.integrated_lufs, "LUFS"),
+                ("Threshold", entry.threshold_lufs, "LUFS"),
+                ("LRA", entry.loudness_range_lu, "LU"),
+                ("Peak", entry.true_peak_dbfs, "dBFS"),
+            ] {
+                if let Some(value) = value {
+                    loudness.insert(key.to_string(), format!("{:.1} {}", value, unit));
+                }
+            }
+            if let Some(active) = &entry.active_region {
+                loudness.insert(
+                    "Active Start".to_string(),
+                    format!("{:.3} s", active.start_s),
+                );
+                loudness.insert("Active End".to_string(), format!("{:.3} s", active.end_s));
+                if let Some(lufs) = active.integrated_lufs {
+                    loudness.insert("Active I".to_string(), format!("{:.1} LUFS", lufs));
+                }
+            }
+            if let Some(dynamics) = &entry.dynamics {
+                for (key, value, unit) in [
+                    ("Dynamics Peak", dynamics.sample_peak_dbfs, "dBFS"),
+                    ("Dynamics RMS", dynamics.rms_dbfs, "dBFS"),
+                    ("Dynamics Crest factor", dynamics.crest_factor_db, "dB"),
+                ] {
+                    if let Some(value) = value {
+                        loudness.insert(key.to_string(), format!("{:.1} {}", value, unit));
+                    }
+                }
+                if let Some(dr) = dynamics.dr {
+                    loudness.insert("Dynamics DR".to_string(), format!("DR{:.0}", dr));
+                }
+            }
+            LoudnessInfo {
+                format: path
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().to_uppercase())
+                    .unwrap_or_default(),
+                // The JSON output has no size; use the file on disk when it is still there
+                size: std::fs::metadata(&path)
+                    .ok()
+                    .and_then(|m| parse_size(&format_size(m.len()))),
+                file_path: entry.path,
+                loudness,
+                errors: Vec::new(),
+                parse_warnings: Vec::new(),
+            }
+        })
+        .collect();
+
+    write_json(&audio_files, output_path, 0);
 }
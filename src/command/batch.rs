@@ -0,0 +1,278 @@
+use super::convert::{convert_file, ConvertOptions, OverwritePolicy};
+use crate::error::AudioToolsError;
+use crate::utils::parse_csv_row;
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Value};
+
+/// マニフェストで指定できる列（TOMLのキー）
+const FIELDS: &[&str] = &[
+    "input",
+    "output",
+    "format",
+    "bit_depth",
+    "sample_rate",
+    "normalize",
+];
+
+/// マニフェストの1行（1ジョブ）分の変換設定
+#[derive(Clone, Debug)]
+pub struct BatchJob {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// 出力形式（省略時は出力ファイルの拡張子）
+    pub format: Option<String>,
+    pub bit_depth: Option<u8>,
+    pub sample_rate: Option<u32>,
+    /// ノーマライズの目標ピークレベル（dBFS）
+    pub normalize: Option<f32>,
+}
+
+/// TOML（[[job]] テーブルの配列）またはヘッダ付きCSVのマニフェストを読み込む。
+/// 相対パスはマニフェストのあるディレクトリを基準にする。
+/// 個々のジョブの設定エラーはジョブごとの Err として返す
+pub fn read_manifest(
+    manifest: &Path,
+) -> Result<Vec<Result<BatchJob, AudioToolsError>>, AudioToolsError> {
+    let parse_rows = match manifest
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("toml") => toml_rows,
+        Some("csv") => csv_rows,
+        _ => {
+            return Err(AudioToolsError::UnsupportedFormat(format!(
+                "{} (manifest must be .toml or .csv)",
+                manifest.display()
+            )))
+        }
+    };
+    let rows = parse_rows(&fs::read_to_string(manifest)?)?;
+    let base = manifest.parent().unwrap_or_else(|| Path::new(""));
+
+    Ok(rows
+        .into_iter()
+        .map(|row| row.and_then(|fields| job_from_fields(&fields, base)))
+        .collect())
+}
+
+/// マニフェストの全ジョブを変換し、失敗したジョブは最後にまとめて報告する。
+/// 失敗したジョブがあれば false を返す
+pub fn run_batch(manifest: &Path, force: bool) -> Result<bool, AudioToolsError> {
+    let jobs = read_manifest(manifest)?;
+
+    let mut converted = 0;
+    let mut skipped = 0;
+    let mut failed: Vec<(usize, String)> = Vec::new();
+
+    for (index, job) in jobs.iter().enumerate() {
+        let number = index + 1;
+        let job = match job {
+            Ok(job) => job,
+            Err(e) => {
+                error!("Job {}: {}", number, e);
+                failed.push((number, e.to_string()));
+                continue;
+            }
+        };
+
+        if job.output.exists() && !force {
+            info!(
+                "Skipped: {} (output file already exists. Use --force to overwrite)",
+                job.output.display()
+            );
+            skipped += 1;
+            continue;
+        }
+
+        match run_job(job, force) {
+            Ok(()) => {
+                converted += 1;
+                info!(
+                    "Converted: {} -> {}",
+                    job.input.display(),
+                    job.output.display()
+                );
+            }
+            Err(e) => {
+                error!("Job {}: {} ({})", number, job.input.display(), e);
+                failed.push((number, format!("{}: {}", job.input.display(), e)));
+            }
+        }
+    }
+
+    // 結果の集計
+    info!(
+        "Summary: {} converted, {} skipped, {} failed",
+        converted,
+        skipped,
+        failed.len()
+    );
+    // -q でも失敗したジョブは表示する
+    for (number, message) in &failed {
+        error!("  Failed job {}: {}", number, message);
+    }
+
+    Ok(failed.is_empty())
+}
+
+fn run_job(job: &BatchJob, force: bool) -> Result<(), AudioToolsError> {
+    if !job.input.exists() {
+        return Err(AudioToolsError::InvalidInput(
+            "input file does not exist".to_string(),
+        ));
+    }
+    let output_format = match &job.format {
+        Some(format) => format.to_lowercase(),
+        None => job
+            .output
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .ok_or_else(|| {
+                AudioToolsError::InvalidInput(
+                    "set format or give the output file an extension".to_string(),
+                )
+            })?,
+    };
+    if let Some(dir) = job.output.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let defaults = ConvertOptions::default();
+    convert_file(
+        &job.input,
+        &job.output,
+        &ConvertOptions {
+            output_format,
+            bit_depth: job.bit_depth.unwrap_or(defaults.bit_depth),
+            sample_rate: job.sample_rate,
+            normalize_level: job.normalize,
+            overwrite: if force {
+                OverwritePolicy::Overwrite
+            } else {
+                OverwritePolicy::Skip
+            },
+            ..defaults
+        },
+    )
+}
+
+/// 1ジョブ分の列名と値（値は文字列のまま）
+type Fields = HashMap<String, String>;
+
+fn toml_rows(content: &str) -> Result<Vec<Result<Fields, AudioToolsError>>, AudioToolsError> {
+    let doc: DocumentMut = content
+        .parse()
+        .map_err(|e| AudioToolsError::InvalidInput(format!("Invalid TOML manifest: {}", e)))?;
+    let jobs = doc
+        .get("job")
+        .and_then(|item| item.as_array_of_tables())
+        .ok_or_else(|| {
+            AudioToolsError::InvalidInput("TOML manifest has no [[job]] tables".to_string())
+        })?;
+
+    Ok(jobs
+        .iter()
+        .map(|table| {
+            table
+                .iter()
+                .map(|(key, item)| {
+                    let value = match item.as_value() {
+                        Some(Value::String(s)) => s.value().clone(),
+                        Some(Value::Integer(i)) => i.value().to_string(),
+                        Some(Value::Float(f)) => f.value().to_string(),
+                        _ => {
+                            return Err(AudioToolsError::InvalidInput(format!(
+                                "{} must be a string or number",
+                                key
+                            )))
+                        }
+                    };
+                    Ok((key.to_string(), value))
+                })
+                .collect()
+        })
+        .collect())
+}
+
+fn csv_rows(content: &str) -> Result<Vec<Result<Fields, AudioToolsError>>, AudioToolsError> {
+    // 空行と # で始まるコメント行は無視する
+    let mut lines = content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    let header: Vec<String> = lines
+        .next()
+        .map(parse_csv_row)
+        .ok_or_else(|| AudioToolsError::InvalidInput("CSV manifest is empty".to_string()))?
+        .into_iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    if let Some(unknown) = header.iter().find(|name| !FIELDS.contains(&name.as_str())) {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Unknown CSV column '{}' (expected {})",
+            unknown,
+            FIELDS.join(", ")
+        )));
+    }
+
+    Ok(lines
+        .map(|line| {
+            let values = parse_csv_row(line);
+            if values.len() > header.len() {
+                return Err(AudioToolsError::InvalidInput(format!(
+                    "{} values for {} columns",
+                    values.len(),
+                    header.len()
+                )));
+            }
+            // 空欄は未指定として扱う
+            Ok(header
+                .iter()
+                .cloned()
+                .zip(values)
+                .filter(|(_, value)| !value.trim().is_empty())
+                .collect())
+        })
+        .collect())
+}
+
+fn job_from_fields(fields: &Fields, base: &Path) -> Result<BatchJob, AudioToolsError> {
+    if let Some(unknown) = fields.keys().find(|key| !FIELDS.contains(&key.as_str())) {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Unknown field '{}'",
+            unknown
+        )));
+    }
+
+    let path = |name: &str| -> Result<PathBuf, AudioToolsError> {
+        let value = fields
+            .get(name)
+            .ok_or_else(|| AudioToolsError::InvalidInput(format!("Missing {}", name)))?;
+        Ok(base.join(value.trim()))
+    };
+    fn number<T: std::str::FromStr>(
+        fields: &Fields,
+        name: &str,
+    ) -> Result<Option<T>, AudioToolsError> {
+        fields
+            .get(name)
+            .map(|value| {
+                value.trim().parse().map_err(|_| {
+                    AudioToolsError::InvalidInput(format!("Invalid {}: {}", name, value))
+                })
+            })
+            .transpose()
+    }
+
+    Ok(BatchJob {
+        input: path("input")?,
+        output: path("output")?,
+        format: fields.get("format").map(|format| format.trim().to_string()),
+        bit_depth: number(fields, "bit_depth")?,
+        sample_rate: number(fields, "sample_rate")?,
+        normalize: number(fields, "normalize")?,
+    })
+}
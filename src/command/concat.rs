@@ -0,0 +1,216 @@
+use crate::command::convert::{codec_for_format, encoder_args};
+use crate::error::AudioToolsError;
+use crate::utils::ffmpeg::ffmpeg_command;
+use crate::utils::ffprobe::run_ffprobe;
+use crate::utils::wave_header::WavHeader;
+use crate::utils::{get_walker, WalkOptions};
+use log::{debug, info};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// 連結処理のパラメータ
+#[derive(Clone, Debug)]
+pub struct ConcatOptions {
+    /// ディレクトリ入力時に対象とする拡張子
+    pub input_format: Vec<String>,
+    /// 区間の間に入れるクロスフェードの長さ（ミリ秒）
+    pub crossfade_ms: Option<f32>,
+    /// 指定時は全入力をこのサンプリングレートに揃える
+    pub sample_rate: Option<u32>,
+    pub bit_depth: u8,
+    pub force: bool,
+}
+
+pub fn concat_files(
+    inputs: &[PathBuf],
+    output: &Path,
+    options: &ConcatOptions,
+    walk: &WalkOptions,
+) -> Result<(), AudioToolsError> {
+    let output_format = output
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .ok_or_else(|| {
+            AudioToolsError::InvalidInput(
+                "Output file needs an extension (wav, flac, or mp3)".to_string(),
+            )
+        })?;
+    let (codec, out_ext) = codec_for_format(&output_format, options.bit_depth)?;
+
+    if output.exists() && !options.force {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "{} already exists. Use --force to overwrite",
+            output.display()
+        )));
+    }
+    if let Some(ms) = options.crossfade_ms {
+        if ms <= 0.0 {
+            return Err(AudioToolsError::InvalidInput(
+                "Crossfade must be longer than 0 ms".to_string(),
+            ));
+        }
+    }
+
+    let files = collect_inputs(inputs, &options.input_format, walk);
+    if files.len() < 2 {
+        return Err(AudioToolsError::InvalidInput(
+            "At least two input files are required".to_string(),
+        ));
+    }
+
+    // サンプリングレートとチャンネル数の確認
+    let streams = files
+        .iter()
+        .map(|file| {
+            probe_stream(file)
+                .map_err(|e| AudioToolsError::InvalidInput(format!("{}: {}", file.display(), e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let (first_rate, first_channels) = streams[0];
+    for (file, &(rate, channels)) in files.iter().zip(streams.iter()) {
+        if channels != first_channels {
+            return Err(AudioToolsError::InvalidInput(format!(
+                "Channel count mismatch: {} has {} channels, expected {}",
+                file.display(),
+                channels,
+                first_channels
+            )));
+        }
+        if rate != first_rate && options.sample_rate.is_none() {
+            return Err(AudioToolsError::InvalidInput(format!(
+                "Sample rate mismatch: {} is {} Hz, expected {} Hz. Use --sample-rate to resample",
+                file.display(),
+                rate,
+                first_rate
+            )));
+        }
+    }
+
+    info!("Joining {} files:", files.len());
+    for file in &files {
+        info!("  {}", file.display());
+    }
+
+    let mut cmd = ffmpeg_command();
+    cmd.arg(if options.force { "-y" } else { "-n" });
+
+    // concat デミュクサは全入力のコーデックとビット深度が同じ必要があるため、
+    // 常に入力ごとにデコードする concat フィルタ（またはクロスフェード）でつなぐ
+    for file in &files {
+        cmd.arg("-i").arg(file);
+    }
+    cmd.args(["-filter_complex", &build_filter(files.len(), options)]);
+    cmd.args(["-map", "[out]"]);
+
+    if let Some(rate) = options.sample_rate {
+        cmd.arg("-ar").arg(rate.to_string());
+    }
+    cmd.args(encoder_args(out_ext));
+    cmd.args(["-acodec", codec]).arg(output);
+
+    debug!("Running {:?}", cmd);
+    let result = cmd
+        .output()
+        .map_err(|e| AudioToolsError::Ffmpeg(format!("could not run ffmpeg: {}", e)))?;
+    if !result.status.success() {
+        return Err(AudioToolsError::Ffmpeg(
+            String::from_utf8_lossy(&result.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// ファイルはそのまま、ディレクトリは対象拡張子のファイルを --sort の順に展開する
+fn collect_inputs(inputs: &[PathBuf], input_format: &[String], walk: &WalkOptions) -> Vec<PathBuf> {
+    let extensions: Vec<String> = input_format.iter().map(|f| f.to_lowercase()).collect();
+    let mut files = Vec::new();
+
+    for input in inputs {
+        if input.is_dir() {
+            let entries: Vec<PathBuf> = get_walker(input, walk)
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .filter(|path| {
+                    path.extension().is_some_and(|ext| {
+                        extensions.contains(&ext.to_string_lossy().to_lowercase())
+                    })
+                })
+                .collect();
+            files.extend(entries);
+        } else {
+            files.push(input.clone());
+        }
+    }
+
+    files
+}
+
+/// (サンプリングレート, チャンネル数) を取得する
+fn probe_stream(path: &Path) -> Result<(u32, u16), AudioToolsError> {
+    let is_wav = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    if is_wav {
+        let header = WavHeader::read_from_file(&mut File::open(path)?)?;
+        return Ok((header.sample_rate(), header.num_channels()));
+    }
+
+    let output = run_ffprobe(
+        path,
+        &[
+            "-select_streams",
+            "a:0",
+            "-show_entries",
+            "stream=sample_rate,channels",
+            "-of",
+            "csv=p=0",
+        ],
+    )?;
+    let mut values = output.trim().split(',');
+    match (
+        values.next().and_then(|v| v.parse().ok()),
+        values.next().and_then(|v| v.parse().ok()),
+    ) {
+        (Some(rate), Some(channels)) => Ok((rate, channels)),
+        _ => Err(AudioToolsError::Ffmpeg(
+            "Could not read sample rate and channel count".to_string(),
+        )),
+    }
+}
+
+/// リサンプリングとクロスフェード（または単純連結）の filtergraph を組み立てる
+fn build_filter(count: usize, options: &ConcatOptions) -> String {
+    let mut filters: Vec<String> = (0..count)
+        .map(|i| match options.sample_rate {
+            Some(rate) => format!("[{}:a]aresample={}[a{}]", i, rate, i),
+            None => format!("[{}:a]anull[a{}]", i, i),
+        })
+        .collect();
+
+    match options.crossfade_ms {
+        Some(ms) => {
+            // 前の結果と次の区間を順にクロスフェードでつなぐ
+            let duration = ms / 1000.0;
+            let mut previous = "a0".to_string();
+            for i in 1..count {
+                let label = if i == count - 1 {
+                    "out".to_string()
+                } else {
+                    format!("x{}", i)
+                };
+                filters.push(format!(
+                    "[{}][a{}]acrossfade=d={}[{}]",
+                    previous, i, duration, label
+                ));
+                previous = label;
+            }
+        }
+        None => {
+            let inputs: String = (0..count).map(|i| format!("[a{}]", i)).collect();
+            filters.push(format!("{}concat=n={}:v=0:a=1[out]", inputs, count));
+        }
+    }
+
+    filters.join(";")
+}
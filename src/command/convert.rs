@@ -1,7 +1,18 @@
-use crate::utils::detection::detect_peak_level;
-use crate::utils::get_walker;
-use std::fs;
-use std::path::PathBuf;
+use super::info::mono_fold;
+use crate::error::AudioToolsError;
+use crate::utils::decode::{decode_audio, load_samples};
+use crate::utils::detection::{detect_channel_peak_levels, detect_peak_level, AutoStartDetection};
+use crate::utils::ffmpeg::ffmpeg_command;
+use crate::utils::ffprobe::run_ffprobe;
+use crate::utils::progress::ProgressCallback;
+use crate::utils::summary::BatchSummary;
+use crate::utils::{get_walker, WalkOptions};
+use clap::ValueEnum;
+use hound::WavReader;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use std::fs::{self, File, FileTimes};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 // 定数の定義
@@ -9,62 +20,738 @@ const SUPPORTED_FORMATS: &[&str] = &["wav", "flac", "mp3"];
 const SUPPORTED_BIT_DEPTHS: &[u8] = &[16, 24];
 const DEFAULT_MP3_BITRATE: &str = "320k";
 const DEFAULT_FLAC_COMPRESSION: &str = "8";
-const CHANNEL_CONVERSION_FACTOR: f32 = 0.7071; // -3dB
 
-pub fn convert_files(
-    input: &PathBuf,
-    output_dir: Option<&PathBuf>,
-    flatten: bool,
-    input_format: &[String],
+/// 出力形式からffmpegのコーデック名と拡張子を決定する
+pub fn codec_for_format(
     output_format: &str,
     bit_depth: u8,
-    sample_rate: Option<u32>,
-    prefix: Option<&str>,
-    postfix: Option<&str>,
-    recursive: bool,
-    force: bool,
-    channels: Option<u8>,
-    normalize_level: Option<f32>,
-) {
-    // Determine codec and extension based on output format
-    let (codec, out_ext) = match output_format.to_lowercase().as_str() {
-        "wav" => {
-            if !SUPPORTED_BIT_DEPTHS.contains(&bit_depth) {
-                panic!(
-                    "Unsupported bit depth for WAV. Supported depths are: {:?}",
-                    SUPPORTED_BIT_DEPTHS
+) -> Result<(&'static str, &'static str), AudioToolsError> {
+    match output_format.to_lowercase().as_str() {
+        "wav" => match bit_depth {
+            16 => Ok(("pcm_s16le", "wav")),
+            24 => Ok(("pcm_s24le", "wav")),
+            _ => Err(AudioToolsError::UnsupportedFormat(format!(
+                "{}-bit WAV. Supported depths are: {:?}",
+                bit_depth, SUPPORTED_BIT_DEPTHS
+            ))),
+        },
+        "flac" => Ok(("flac", "flac")),
+        "mp3" => Ok(("libmp3lame", "mp3")),
+        format => Err(AudioToolsError::UnsupportedFormat(format!(
+            "{}. Supported formats are: {:?}",
+            format, SUPPORTED_FORMATS
+        ))),
+    }
+}
+
+/// 出力形式ごとのエンコーダ設定
+pub fn encoder_args(out_ext: &str) -> &'static [&'static str] {
+    match out_ext {
+        "mp3" => &["-b:a", DEFAULT_MP3_BITRATE],
+        "flac" => &["-compression_level", DEFAULT_FLAC_COMPRESSION],
+        _ => &[],
+    }
+}
+
+/// 先頭・末尾の無音除去の設定
+#[derive(Clone, Debug)]
+pub struct SilenceTrim {
+    /// 無音とみなすレベル（dBFS）
+    pub threshold_db: f32,
+    /// 無音とみなす最短の長さ（秒）
+    pub duration: f32,
+}
+
+/// フェードのカーブ（ffmpeg afade の curve）
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FadeCurve {
+    /// 直線
+    Tri,
+    /// 1/4 サイン波
+    Qsin,
+    /// 1/2 サイン波
+    Hsin,
+    /// 指数サイン波
+    Esin,
+    Log,
+    Exp,
+    /// 逆放物線
+    Par,
+    /// 二次
+    Qua,
+    /// 三次
+    Cub,
+}
+
+impl FadeCurve {
+    fn as_ffmpeg(&self) -> &'static str {
+        match self {
+            FadeCurve::Tri => "tri",
+            FadeCurve::Qsin => "qsin",
+            FadeCurve::Hsin => "hsin",
+            FadeCurve::Esin => "esin",
+            FadeCurve::Log => "log",
+            FadeCurve::Exp => "exp",
+            FadeCurve::Par => "par",
+            FadeCurve::Qua => "qua",
+            FadeCurve::Cub => "cub",
+        }
+    }
+}
+
+/// 出力チャンネルレイアウト（名前はffmpegのレイアウト名）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelLayout {
+    pub name: &'static str,
+    pub channels: u16,
+}
+
+const CHANNEL_LAYOUTS: &[ChannelLayout] = &[
+    ChannelLayout {
+        name: "mono",
+        channels: 1,
+    },
+    ChannelLayout {
+        name: "stereo",
+        channels: 2,
+    },
+    ChannelLayout {
+        name: "2.1",
+        channels: 3,
+    },
+    ChannelLayout {
+        name: "quad",
+        channels: 4,
+    },
+    ChannelLayout {
+        name: "5.0",
+        channels: 5,
+    },
+    ChannelLayout {
+        name: "5.1",
+        channels: 6,
+    },
+    ChannelLayout {
+        name: "6.1",
+        channels: 7,
+    },
+    ChannelLayout {
+        name: "7.1",
+        channels: 8,
+    },
+];
+
+/// チャンネル数（1〜8）またはレイアウト名（mono, stereo, 2.1, quad, 5.0, 5.1, 6.1, 7.1）を解析する
+pub fn parse_channel_layout(s: &str) -> Result<ChannelLayout, String> {
+    let layout = match s.parse::<u16>() {
+        Ok(count) => CHANNEL_LAYOUTS.iter().find(|l| l.channels == count),
+        Err(_) => CHANNEL_LAYOUTS
+            .iter()
+            .find(|l| l.name.eq_ignore_ascii_case(s)),
+    };
+    layout.copied().ok_or_else(|| {
+        let names: Vec<&str> = CHANNEL_LAYOUTS.iter().map(|l| l.name).collect();
+        format!(
+            "Unsupported channel layout: {}. Use a count from 1 to 8 or one of {:?}",
+            s, names
+        )
+    })
+}
+
+/// ffmpeg の loudnorm フィルタによる2パスのラウドネスノーマライズの目標値
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoudnormTarget {
+    /// 目標の統合ラウドネス（LUFS）
+    pub integrated: f32,
+    /// トゥルーピークの上限（dBTP）
+    pub true_peak: f32,
+    /// 目標のラウドネスレンジ（LU）
+    pub range: f32,
+}
+
+impl LoudnormTarget {
+    fn filter(&self) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}",
+            self.integrated, self.true_peak, self.range
+        )
+    }
+}
+
+/// "I:TP:LRA" 形式（例: -16:-1.5:11）の目標値を解析する
+pub fn parse_loudnorm_target(s: &str) -> Result<LoudnormTarget, String> {
+    let values: Option<Vec<f32>> = s.split(':').map(|v| v.trim().parse().ok()).collect();
+    let Some([integrated, true_peak, range]) = values.as_deref() else {
+        return Err(format!(
+            "Invalid loudnorm target: {}. Use I:TP:LRA (e.g., -16:-1.5:11)",
+            s
+        ));
+    };
+    let (integrated, true_peak, range) = (*integrated, *true_peak, *range);
+    // loudnorm フィルタが受け付ける範囲
+    if !(-70.0..=-5.0).contains(&integrated) {
+        return Err(format!(
+            "Integrated loudness must be between -70 and -5 LUFS (got {})",
+            integrated
+        ));
+    }
+    if !(-9.0..=0.0).contains(&true_peak) {
+        return Err(format!(
+            "True peak must be between -9 and 0 dBTP (got {})",
+            true_peak
+        ));
+    }
+    if !(1.0..=50.0).contains(&range) {
+        return Err(format!(
+            "Loudness range must be between 1 and 50 LU (got {})",
+            range
+        ));
+    }
+    Ok(LoudnormTarget {
+        integrated,
+        true_peak,
+        range,
+    })
+}
+
+/// チャンネルごとのピーク（dBFS）をそれぞれ目標レベルに合わせる pan フィルタ
+fn channel_gain_filter(peaks: &[f32], target_level: f32) -> String {
+    let mapping: Vec<String> = peaks
+        .iter()
+        .enumerate()
+        .map(|(c, peak)| {
+            let gain = target_level - peak;
+            info!(
+                "Channel {}: peak {:.1} dBFS, Target: {:.1} dBFS, Applying gain: {:.1} dB",
+                c + 1,
+                peak,
+                target_level,
+                gain
+            );
+            format!("c{}={}*c{}", c, 10_f32.powf(gain / 20.0), c)
+        })
+        .collect();
+    let layout = CHANNEL_LAYOUTS
+        .iter()
+        .find(|l| l.channels as usize == peaks.len())
+        .map_or_else(|| format!("{}c", peaks.len()), |l| l.name.to_string());
+    format!("pan={}|{}", layout, mapping.join("|"))
+}
+
+/// loudnorm の1パス目の測定結果（ffmpeg が JSON で出力する値を文字列のまま保持する）
+#[derive(Debug, Deserialize)]
+struct LoudnormMeasurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+/// 出力先に同名のファイルがある場合の扱い
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OverwritePolicy {
+    /// 変換せずに飛ばす
+    Skip,
+    /// 上書きする
+    Overwrite,
+    /// 空いている連番の名前（name_1.wav, name_2.wav, ...）で書き出す
+    Rename,
+    /// 入力の更新日時が出力より新しい場合だけ上書きする
+    Newer,
+}
+
+impl OverwritePolicy {
+    /// 出力先 output に書き出すかを決め、書き出すパスを返す（飛ばす場合は None）
+    pub fn resolve(self, input: &Path, output: &Path) -> Option<PathBuf> {
+        if !output.exists() {
+            return Some(output.to_path_buf());
+        }
+        match self {
+            OverwritePolicy::Skip => None,
+            OverwritePolicy::Overwrite => Some(output.to_path_buf()),
+            OverwritePolicy::Rename => {
+                let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+                let ext = output.extension().map(|ext| ext.to_string_lossy());
+                (1..)
+                    .map(|n| {
+                        let name = match &ext {
+                            Some(ext) => format!("{}_{}.{}", stem, n, ext),
+                            None => format!("{}_{}", stem, n),
+                        };
+                        output.with_file_name(name)
+                    })
+                    .find(|path| !path.exists())
+            }
+            OverwritePolicy::Newer => {
+                let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+                // 日時が読めない場合は上書きする
+                match (modified(input), modified(output)) {
+                    (Some(source), Some(existing)) if source <= existing => None,
+                    _ => Some(output.to_path_buf()),
+                }
+            }
+        }
+    }
+}
+
+/// チャンネル数変換の方法
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DownmixMode {
+    /// pan フィルタで指定ゲインを掛けて合成・複製する
+    Pan,
+    /// ゲイン調整せず ffmpeg の標準のチャンネル変換に任せる
+    None,
+}
+
+/// サンプリングレート変換の品質（soxr の precision に対応）
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ResamplerQuality {
+    Low,
+    Medium,
+    High,
+    /// Very high quality
+    Vhq,
+}
+
+impl ResamplerQuality {
+    /// soxr の計算精度（ビット）
+    fn precision(&self) -> u8 {
+        match self {
+            ResamplerQuality::Low => 16,
+            ResamplerQuality::Medium => 20,
+            ResamplerQuality::High => 28,
+            ResamplerQuality::Vhq => 33,
+        }
+    }
+}
+
+/// 変換処理のパラメータ
+#[derive(Clone, Debug)]
+pub struct ConvertOptions {
+    pub output_dir: Option<PathBuf>,
+    /// 入力のディレクトリ構造を無視して出力ディレクトリ直下に書き出す
+    pub flatten: bool,
+    pub input_format: Vec<String>,
+    pub output_format: String,
+    pub bit_depth: u8,
+    /// MP3のビットレート（例: "192k"）。未指定時は 320k
+    pub bitrate: Option<String>,
+    pub sample_rate: Option<u32>,
+    /// 指定時は soxr でサンプリングレート変換する（未指定時はffmpeg標準の swr）
+    pub resampler_quality: Option<ResamplerQuality>,
+    pub prefix: Option<String>,
+    pub postfix: Option<String>,
+    /// 出力先に同名のファイルがある場合の扱い
+    pub overwrite: OverwritePolicy,
+    pub channels: Option<ChannelLayout>,
+    pub downmix: DownmixMode,
+    /// チャンネル変換時に各チャンネルに掛けるゲイン（dB）
+    pub downmix_gain_db: f32,
+    /// 固定で掛けるゲイン（dB）。ノーマライズの代わりに既知の量だけレベルを変える
+    pub gain_db: Option<f32>,
+    /// ノーマライズの目標ピークレベル（dBFS）
+    pub normalize_level: Option<f32>,
+    /// チャンネルごとにピークを検出し、それぞれが目標レベルになるよう別々のゲインを掛ける
+    pub normalize_per_channel: bool,
+    /// 指定時は loudnorm の2パス（測定してから適用）でラウドネスを揃える
+    pub loudnorm: Option<LoudnormTarget>,
+    /// ノーマライズ後にリミッターを掛ける場合の上限レベル（dBFS）
+    pub limiter_ceiling: Option<f32>,
+    /// 5Hzのハイパスフィルタで直流成分を除去する
+    pub remove_dc: bool,
+    pub trim_silence: Option<SilenceTrim>,
+    /// 指定時は検出した音の開始位置より前を切り捨てて変換する（WAVのみ）
+    pub auto_trim: Option<AutoStartDetection>,
+    /// フェードインの長さ（ミリ秒）
+    pub fade_in_ms: Option<f32>,
+    /// フェードアウトの長さ（ミリ秒）
+    pub fade_out_ms: Option<f32>,
+    pub fade_curve: FadeCurve,
+    /// 指定時はステレオ入力をモノラルに畳み込んだときのレベル変化を報告する
+    pub mono_check: Option<MonoCheck>,
+    /// 変換に成功した出力の更新日時・アクセス日時を入力に合わせる
+    pub preserve_timestamps: bool,
+}
+
+/// --mono-check の警告の基準
+#[derive(Clone, Copy, Debug)]
+pub struct MonoCheck {
+    /// モノラルにしたときのRMSの低下がこれ（dB）を超えると警告する
+    pub max_level_drop_db: f32,
+    /// モノラルにしたときのピークの上昇がこれ（dB）を超えると警告する
+    pub max_peak_rise_db: f32,
+    /// 報告に加えてモノラルに変換したファイルを書き出す
+    pub write: bool,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: None,
+            flatten: false,
+            input_format: vec!["wav".to_string()],
+            output_format: "wav".to_string(),
+            bit_depth: 16,
+            bitrate: None,
+            sample_rate: None,
+            resampler_quality: None,
+            prefix: None,
+            postfix: None,
+            overwrite: OverwritePolicy::Skip,
+            channels: None,
+            downmix: DownmixMode::Pan,
+            downmix_gain_db: -3.0,
+            gain_db: None,
+            normalize_level: None,
+            normalize_per_channel: false,
+            loudnorm: None,
+            limiter_ceiling: None,
+            remove_dc: false,
+            trim_silence: None,
+            auto_trim: None,
+            fade_in_ms: None,
+            fade_out_ms: None,
+            fade_curve: FadeCurve::Tri,
+            mono_check: None,
+            preserve_timestamps: false,
+        }
+    }
+}
+
+/// 1ファイルを変換する（出力先の存在確認は呼び出し側で行う）
+pub fn convert_file(
+    input: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+) -> Result<(), AudioToolsError> {
+    // アクセス日時は変換で入力を読むと更新されるため、読む前に取得しておく
+    let source_times = options.preserve_timestamps.then(|| file_times(input));
+
+    let mut cmd = build_convert_command(input, output, options)?;
+
+    // 変換実行（ffmpegが起動しても異常終了した場合は失敗として扱う）
+    debug!("Running {:?}", cmd);
+    let result = cmd
+        .output()
+        .map_err(|e| AudioToolsError::Ffmpeg(format!("could not run ffmpeg: {}", e)))?;
+    if !result.status.success() {
+        return Err(AudioToolsError::Ffmpeg(format!(
+            "{}\n{}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+
+    // 変換自体は成功しているため、日時を設定できなくても警告に留める
+    if let Some(times) = source_times {
+        if let Err(e) =
+            times.and_then(|times| File::options().write(true).open(output)?.set_times(times))
+        {
+            warn!(
+                "Could not copy timestamps from {} to {}: {}",
+                input.display(),
+                output.display(),
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// 変換する ffmpeg のコマンドを組み立てる。ノーマライズ・無音検出・loudnorm の測定など
+/// 入力を調べる必要がある処理はここで行う
+pub fn build_convert_command(
+    input: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+) -> Result<Command, AudioToolsError> {
+    let (codec, out_ext) = codec_for_format(&options.output_format, options.bit_depth)?;
+    if options.bitrate.is_some() && out_ext != "mp3" {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Bitrate only applies to MP3 output, not {}",
+            out_ext.to_uppercase()
+        )));
+    }
+    if let Some(ceiling) = options.limiter_ceiling {
+        // alimiter の limit は 0.0625（約 -24 dBFS）から 1.0 まで
+        if !(-24.0..=0.0).contains(&ceiling) {
+            return Err(AudioToolsError::InvalidInput(format!(
+                "Limiter ceiling must be between -24 and 0 dBFS (got {})",
+                ceiling
+            )));
+        }
+    }
+
+    let mut cmd = ffmpeg_command();
+
+    // 検出した開始位置から読み込む（-ss は -i より前に置いて入力をシークする）
+    let trim_start = options
+        .auto_trim
+        .as_ref()
+        .map_or(0.0, |detection| detect_trim_start(input, detection));
+    if trim_start > 0.0 {
+        cmd.args(["-ss", &format!("{:.6}", trim_start)]);
+    }
+    cmd.arg("-i").arg(input);
+
+    if options.overwrite != OverwritePolicy::Skip {
+        cmd.arg("-y");
+    } else {
+        cmd.arg("-n");
+    }
+
+    // フィルタは1つの -af にまとめる（-af を複数指定すると最後のものしか効かない）
+    let mut filters: Vec<String> = Vec::new();
+
+    // 直流成分の除去（バイアスが残ると無音判定がずれるため最初に行う）
+    if options.remove_dc {
+        filters.push("highpass=f=5".to_string());
+    }
+
+    // 先頭・末尾の無音除去（末尾は反転して先頭として処理する）
+    if let Some(trim) = &options.trim_silence {
+        let remove = format!(
+            "silenceremove=start_periods=1:start_duration={}:start_threshold={}dB",
+            trim.duration, trim.threshold_db
+        );
+        filters.extend([remove.clone(), "areverse".into(), remove, "areverse".into()]);
+    }
+
+    // フェードイン・フェードアウト
+    let curve = options.fade_curve.as_ffmpeg();
+    if let Some(ms) = options.fade_in_ms {
+        filters.push(format!("afade=t=in:st=0:d={}:curve={}", ms / 1000.0, curve));
+    }
+    if let Some(ms) = options.fade_out_ms {
+        let length = ms / 1000.0;
+        // 無音除去後は長さが変わるため、長さが分かる場合のみ開始位置を指定する。
+        // それ以外は反転してフェードインを掛けることで末尾にフェードを置く
+        match probe_duration(input).filter(|_| options.trim_silence.is_none()) {
+            Some(duration) => filters.push(format!(
+                "afade=t=out:st={}:d={}:curve={}",
+                (duration - trim_start - length).max(0.0),
+                length,
+                curve
+            )),
+            None => filters.extend([
+                "areverse".to_string(),
+                format!("afade=t=in:st=0:d={}:curve={}", length, curve),
+                "areverse".to_string(),
+            ]),
+        }
+    }
+
+    // 固定のゲイン（ノーマライズと同じ位置に掛ける）
+    if let Some(gain) = options.gain_db {
+        filters.push(format!("volume={}dB", gain));
+    }
+
+    // ノーマライズ処理の改善
+    if let Some(target_level) = options.normalize_level {
+        let gain_filter = if options.normalize_per_channel {
+            detect_channel_peak_levels(&input.to_path_buf())
+                .map(|peaks| channel_gain_filter(&peaks, target_level))
+        } else {
+            detect_peak_level(&input.to_path_buf()).map(|current_peak| {
+                let gain = target_level - current_peak;
+                info!(
+                    "Current peak: {:.1} dBFS, Target: {:.1} dBFS, Applying gain: {:.1} dB",
+                    current_peak, target_level, gain
+                );
+                format!("volume={}dB", gain)
+            })
+        };
+        match gain_filter {
+            Ok(filter) => {
+                filters.push(filter);
+
+                // ゲイン後のピークを上限以下に抑える（先読み分の遅延は補正する）
+                if let Some(ceiling) = options.limiter_ceiling {
+                    if target_level > ceiling {
+                        info!(
+                            "Limiter engaged: target {:.1} dBFS exceeds ceiling {:.1} dBFS",
+                            target_level, ceiling
+                        );
+                    }
+                    filters.push(format!(
+                        "alimiter=limit={}:level=0:latency=1",
+                        10_f32.powf(ceiling / 20.0)
+                    ));
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Could not detect peak level for {}: {}. Skipping normalization.",
+                    input.display(),
+                    e
                 );
             }
-            (
-                match bit_depth {
-                    16 => "pcm_s16le",
-                    24 => "pcm_s24le",
-                    _ => unreachable!(),
-                },
-                "wav",
-            )
-        }
-        "flac" => ("flac", "flac"),
-        "mp3" => ("libmp3lame", "mp3"),
-        format => panic!(
-            "Unsupported output format: {}. Supported formats are: {:?}",
-            format, SUPPORTED_FORMATS
-        ),
+        }
+    }
+
+    // 2パスのラウドネスノーマライズ：ここまでのフィルタを掛けた音を測定し、
+    // 測定値を渡して線形（一定のゲイン）で補正する
+    let mut output_rate = options.sample_rate;
+    if let Some(target) = &options.loudnorm {
+        let measured = measure_loudnorm(input, trim_start, &filters, target)?;
+        info!(
+            "Measured {} LUFS, {} dBTP, LRA {} LU; target {} LUFS, {} dBTP, LRA {} LU",
+            measured.input_i,
+            measured.input_tp,
+            measured.input_lra,
+            target.integrated,
+            target.true_peak,
+            target.range
+        );
+        filters.push(format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            target.filter(),
+            measured.input_i,
+            measured.input_tp,
+            measured.input_lra,
+            measured.input_thresh,
+            measured.target_offset
+        ));
+        // loudnorm は 192kHz で出力するため、指定がなければ入力のレートに戻す
+        output_rate = output_rate.or_else(|| probe_sample_rate(input));
+    }
+
+    // チャンネル数変換（入力と同じチャンネル数なら何もしない）
+    if let Some(layout) = options.channels {
+        let source_channels = probe_channels(input);
+        if source_channels != Some(layout.channels) {
+            let gain = 10_f32.powf(options.downmix_gain_db / 20.0);
+            match (options.downmix, source_channels) {
+                (DownmixMode::None, _) | (_, None) => {
+                    cmd.args(["-ac", &layout.channels.to_string()]);
+                }
+                // ステレオからモノラルへの合成
+                (DownmixMode::Pan, Some(2)) if layout.channels == 1 => {
+                    filters.push(format!("pan=mono|c0={}*c0+{}*c1", gain, gain))
+                }
+                // モノラルからステレオへの複製
+                (DownmixMode::Pan, Some(1)) if layout.channels == 2 => {
+                    filters.push(format!("pan=stereo|c0={}*c0|c1={}*c0", gain, gain))
+                }
+                // それ以外のダウンミックスはffmpegの標準マトリクスを使う
+                (DownmixMode::Pan, Some(source)) if source > layout.channels => {
+                    filters.push(format!("aformat=channel_layouts={}", layout.name))
+                }
+                // アップミックス：元のチャンネルをそのまま配置し、残りは無音にする
+                // （モノラルはフロント左右に複製する）
+                (DownmixMode::Pan, Some(source)) => {
+                    let mapping: Vec<String> = if source == 1 {
+                        vec![format!("c0={}*c0", gain), format!("c1={}*c0", gain)]
+                    } else {
+                        (0..source).map(|c| format!("c{}=c{}", c, c)).collect()
+                    };
+                    filters.push(format!("pan={}|{}", layout.name, mapping.join("|")));
+                }
+            }
+        }
+    }
+
+    // 高品質サンプリングレート変換（チャンネル変換の後に行う）
+    if let (Some(rate), Some(quality)) = (output_rate, options.resampler_quality) {
+        filters.push(format!(
+            "aresample={}:resampler=soxr:precision={}",
+            rate,
+            quality.precision()
+        ));
+    }
+
+    if !filters.is_empty() {
+        cmd.args(["-af", &filters.join(",")]);
+    }
+
+    // サンプリングレート
+    if let Some(rate) = output_rate {
+        cmd.arg("-ar").arg(rate.to_string());
+    }
+
+    // ファイル形式とコーデック
+    match &options.bitrate {
+        Some(bitrate) => cmd.args(["-b:a", bitrate]),
+        None => cmd.args(encoder_args(out_ext)),
     };
+    cmd.args(["-acodec", codec]).arg(output);
+
+    Ok(cmd)
+}
+
+/// ファイルの更新日時とアクセス日時
+fn file_times(path: &Path) -> std::io::Result<FileTimes> {
+    let metadata = fs::metadata(path)?;
+    Ok(FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?))
+}
+
+/// 入力をすべて変換する。失敗したファイルがあれば Ok(false) を返す。
+/// progress を渡すと各ファイルの開始・結果と全体の集計をイベントとして受け取れる
+pub fn convert_files(
+    inputs: &[PathBuf],
+    options: &ConvertOptions,
+    walk: &WalkOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<bool, AudioToolsError> {
+    let output_dir = options.output_dir.as_ref();
+    let flatten = options.flatten;
+    let output_format = options.output_format.as_str();
+
+    // Determine codec and extension based on output format
+    let (_, out_ext) = codec_for_format(output_format, options.bit_depth)?;
 
     // Convert input formats to lowercase for comparison
-    let input_extensions: Vec<String> = input_format.iter().map(|f| f.to_lowercase()).collect();
+    let input_extensions: Vec<String> = options
+        .input_format
+        .iter()
+        .map(|f| f.to_lowercase())
+        .collect();
 
-    for entry in get_walker(input, recursive) {
+    let mut summary = BatchSummary::for_walk(walk).with_progress(progress);
+
+    // 出力先のフォルダ構成は各入力からの相対パスで決める
+    let entries = inputs
+        .iter()
+        .flat_map(|input| get_walker(input, walk).map(move |entry| (input, entry)));
+    for (input, entry) in entries {
+        if summary.should_stop() {
+            break;
+        }
         if let Some(ext) = entry.path().extension() {
             let ext_str = ext.to_string_lossy().to_lowercase();
             if input_extensions.contains(&ext_str) {
+                summary.started(entry.path());
+
+                // --mono-check だけなら報告して次のファイルへ進む
+                if let Some(check) = &options.mono_check {
+                    match check_mono_fold(entry.path(), options.downmix_gain_db, check) {
+                        Ok(_) if check.write => {}
+                        Ok(true) => {
+                            summary.processed(entry.path());
+                            continue;
+                        }
+                        Ok(false) => {
+                            summary.skipped(entry.path());
+                            continue;
+                        }
+                        Err(e) => {
+                            error!("Failed: {} ({})", entry.path().display(), e);
+                            summary.failed(entry.path());
+                            continue;
+                        }
+                    }
+                }
+
                 let stem = entry.path().file_stem().unwrap().to_string_lossy();
                 let filename = format!(
                     "{}{}{}.{}",
-                    prefix.unwrap_or(""),
+                    options.prefix.as_deref().unwrap_or(""),
                     stem,
-                    postfix.unwrap_or(""),
+                    options.postfix.as_deref().unwrap_or(""),
                     out_ext
                 );
 
@@ -79,104 +766,413 @@ pub fn convert_files(
                             .parent()
                             .unwrap_or_else(|| std::path::Path::new(""));
                         let full_output_dir = out_dir.join(relative_path);
-                        fs::create_dir_all(&full_output_dir)
-                            .expect("Failed to create output directory");
+                        if let Err(e) = fs::create_dir_all(&full_output_dir) {
+                            error!("Failed: {} ({})", entry.path().display(), e);
+                            summary.failed(entry.path());
+                            continue;
+                        }
                         full_output_dir.join(&filename)
                     }
                 } else {
                     entry.path().with_file_name(filename)
                 };
 
-                if output.exists() && !force {
-                    println!(
-                        "Skipped: {} (output file already exists. Use --force to overwrite)",
-                        output.display()
-                    );
+                let Some(output) = options.overwrite.resolve(entry.path(), &output) else {
+                    if options.overwrite == OverwritePolicy::Newer {
+                        info!("Skipped: {} (output file is up to date)", output.display());
+                    } else {
+                        info!(
+                            "Skipped: {} (output file already exists. Use --force or --overwrite-policy to replace it)",
+                            output.display()
+                        );
+                    }
+                    summary.skipped(entry.path());
                     continue;
+                };
+
+                match convert_file(entry.path(), &output, options) {
+                    Ok(()) => {
+                        summary.processed(entry.path());
+                        info!(
+                            "Converted: {} -> {}",
+                            entry.path().display(),
+                            output.display()
+                        );
+                    }
+                    Err(e) => {
+                        error!("Failed: {} ({})", entry.path().display(), e);
+                        summary.failed(entry.path());
+                    }
                 }
+            }
+        }
+    }
 
-                let mut cmd = Command::new("ffmpeg");
-                cmd.arg("-i").arg(entry.path());
+    match &options.mono_check {
+        Some(check) if !check.write => summary.report("checked"),
+        _ => summary.report("converted"),
+    }
 
-                if force {
-                    cmd.arg("-y");
-                } else {
-                    cmd.arg("-n");
-                }
+    Ok(!summary.has_failures())
+}
 
-                // ノーマライズ処理の改善
-                if let Some(target_level) = normalize_level {
-                    match detect_peak_level(&entry.path().to_path_buf()) {
-                        Ok(current_peak) => {
-                            let gain = target_level - current_peak;
-                            println!(
-                                                "Current peak: {:.1} dBFS, Target: {:.1} dBFS, Applying gain: {:.1} dB",
-                                                current_peak, target_level, gain
-                                            );
-                            cmd.args(&["-af", &format!("volume={}dB", gain)]);
-                        }
-                        Err(e) => {
-                            println!(
-                                                "Warning: Could not detect peak level for {}: {}. Skipping normalization.",
-                                                entry.path().display(),
-                                                e
-                                            );
-                        }
-                    }
-                }
+/// ステレオ入力を (L + R) * downmix gain でモノラルにしたときのピークとRMSの変化を報告し、
+/// 基準を超えたら警告する。ステレオでない入力は報告せず false を返す
+fn check_mono_fold(
+    input: &Path,
+    downmix_gain_db: f32,
+    check: &MonoCheck,
+) -> Result<bool, AudioToolsError> {
+    let audio = decode_audio(input)?;
+    if audio.channels != 2 {
+        info!(
+            "Mono check: {} has {} channel(s); only stereo is checked",
+            input.display(),
+            audio.channels
+        );
+        return Ok(false);
+    }
 
-                // モノラルステレオ変換
-                if let Some(ch) = channels {
-                    match ch {
-                        1 => {
-                            cmd.args(&[
-                                "-af",
-                                &format!(
-                                    "pan=mono|c0={}*c0+{}*c1",
-                                    CHANNEL_CONVERSION_FACTOR, CHANNEL_CONVERSION_FACTOR
-                                ),
-                            ]);
-                        }
-                        2 => {
-                            cmd.args(&[
-                                "-af",
-                                &format!(
-                                    "pan=stereo|c0={}*c0|c1={}*c0",
-                                    CHANNEL_CONVERSION_FACTOR, CHANNEL_CONVERSION_FACTOR
-                                ),
-                            ]);
-                        }
-                        _ => {
-                            panic!("Unsupported number of channels. Use 1 for mono or 2 for stereo")
-                        }
-                    }
-                }
+    let fold = mono_fold(&audio.samples, 10_f32.powf(downmix_gain_db / 20.0));
+    if fold.stereo_peak == 0.0 {
+        info!("Mono check: {} is silent", input.display());
+        return Ok(true);
+    }
+    let db = |amplitude: f32| 20.0 * amplitude.log10();
+    let peak_rise = db(fold.mono_peak) - db(fold.stereo_peak);
+    let level_change = db(fold.mono_rms) - db(fold.stereo_rms);
+    info!(
+        "Mono check: {}: peak {:.1} -> {:.1} dBFS ({:+.1} dB), RMS {:.1} -> {:.1} dBFS ({:+.1} dB)",
+        input.display(),
+        db(fold.stereo_peak),
+        db(fold.mono_peak),
+        peak_rise,
+        db(fold.stereo_rms),
+        db(fold.mono_rms),
+        level_change
+    );
 
-                // サンプリングレート
-                if let Some(rate) = sample_rate {
-                    cmd.arg("-ar").arg(rate.to_string());
-                }
+    // 逆相成分が多いほど合成で打ち消し合ってレベルが下がる
+    if fold.mono_peak == 0.0 {
+        warn!(
+            "{}: the channels cancel out completely when folded to mono (out of phase)",
+            input.display()
+        );
+    } else if -level_change > check.max_level_drop_db {
+        warn!(
+            "{}: folding to mono drops the level by {:.1} dB (more than {:.1} dB); check for out-of-phase content",
+            input.display(),
+            -level_change,
+            check.max_level_drop_db
+        );
+    }
+    if peak_rise > check.max_peak_rise_db {
+        warn!(
+            "{}: folding to mono raises the peak by {:.1} dB (more than {:.1} dB)",
+            input.display(),
+            peak_rise,
+            check.max_peak_rise_db
+        );
+    }
+    if fold.mono_peak > 1.0 {
+        warn!(
+            "{}: the mono fold clips ({:+.1} dBFS peak)",
+            input.display(),
+            db(fold.mono_peak)
+        );
+    }
+    Ok(true)
+}
 
-                // ファイル形式とコーデック
-                match output_format {
-                    "mp3" => {
-                        cmd.args(&["-b:a", DEFAULT_MP3_BITRATE]);
-                    }
-                    "flac" => {
-                        cmd.args(&["-compression_level", DEFAULT_FLAC_COMPRESSION]);
-                    }
-                    _ => {}
-                }
-                cmd.args(&["-acodec", codec]).arg(&output);
-
-                // 変換実行
-                cmd.output().expect("Failed to execute ffmpeg");
-                println!(
-                    "Converted: {} -> {}",
-                    entry.path().display(),
-                    output.display()
-                );
+/// 先頭の無音の長さ（秒）を検出する。WAV以外や音が見つからない場合は警告して 0 を返す
+fn detect_trim_start(input: &Path, detection: &AutoStartDetection) -> f32 {
+    let is_wav = input
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    if !is_wav {
+        warn!(
+            "Auto trim only applies to WAV input; converting {} untrimmed",
+            input.display()
+        );
+        return 0.0;
+    }
+
+    let start =
+        load_samples(input)
+            .map_err(|e| e.to_string())
+            .and_then(|(samples, sample_rate)| {
+                detection
+                    .detect_start_time(&samples, sample_rate as f32)
+                    .ok_or_else(|| "no sound above the threshold".to_string())
+            });
+    match start {
+        Ok(start) => {
+            info!(
+                "Auto trim: removing {:.3}s of leading silence from {}",
+                start,
+                input.display()
+            );
+            start
+        }
+        Err(e) => {
+            warn!(
+                "Could not detect the start of {} ({}); converting untrimmed",
+                input.display(),
+                e
+            );
+            0.0
+        }
+    }
+}
+
+/// 入力ファイルの長さ（秒）を取得する（WAVはヘッダから、それ以外はffprobeで）
+fn probe_duration(path: &Path) -> Option<f32> {
+    if let Ok(reader) = WavReader::open(path) {
+        return Some(reader.duration() as f32 / reader.spec().sample_rate as f32);
+    }
+    run_ffprobe(
+        path,
+        &["-show_entries", "format=duration", "-of", "csv=p=0"],
+    )
+    .ok()
+    .and_then(|output| output.trim().parse().ok())
+}
+
+/// loudnorm の1パス目：filters に続けて loudnorm を掛けて測定し、ffmpeg が最後に出力する JSON を読む
+fn measure_loudnorm(
+    input: &Path,
+    trim_start: f32,
+    filters: &[String],
+    target: &LoudnormTarget,
+) -> Result<LoudnormMeasurement, AudioToolsError> {
+    let mut cmd = ffmpeg_command();
+    if trim_start > 0.0 {
+        cmd.args(["-ss", &format!("{:.6}", trim_start)]);
+    }
+    let mut chain = filters.to_vec();
+    chain.push(format!("{}:print_format=json", target.filter()));
+    cmd.arg("-i")
+        .arg(input)
+        .args(["-af", &chain.join(",")])
+        .args(["-f", "null", "-"]);
+
+    debug!("Running {:?}", cmd);
+    let result = cmd
+        .output()
+        .map_err(|e| AudioToolsError::Ffmpeg(format!("could not run ffmpeg: {}", e)))?;
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if !result.status.success() {
+        return Err(AudioToolsError::Ffmpeg(format!(
+            "{}\n{}",
+            result.status,
+            stderr.trim()
+        )));
+    }
+    stderr
+        .rfind('{')
+        .and_then(|start| {
+            let end = start + stderr[start..].find('}')?;
+            serde_json::from_str(&stderr[start..=end]).ok()
+        })
+        .ok_or_else(|| {
+            AudioToolsError::Ffmpeg(format!(
+                "could not read the loudnorm measurement of {}",
+                input.display()
+            ))
+        })
+}
+
+/// 入力ファイルのサンプリングレートを取得する
+fn probe_sample_rate(path: &Path) -> Option<u32> {
+    if let Ok(reader) = WavReader::open(path) {
+        return Some(reader.spec().sample_rate);
+    }
+    run_ffprobe(
+        path,
+        &[
+            "-select_streams",
+            "a:0",
+            "-show_entries",
+            "stream=sample_rate",
+            "-of",
+            "csv=p=0",
+        ],
+    )
+    .ok()
+    .and_then(|output| output.trim().parse().ok())
+}
+
+/// 入力ファイルのチャンネル数を取得する
+fn probe_channels(path: &Path) -> Option<u16> {
+    if let Ok(reader) = WavReader::open(path) {
+        return Some(reader.spec().channels);
+    }
+    run_ffprobe(
+        path,
+        &[
+            "-select_streams",
+            "a:0",
+            "-show_entries",
+            "stream=channels",
+            "-of",
+            "csv=p=0",
+        ],
+    )
+    .ok()
+    .and_then(|output| output.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::progress::{FileOutcome, ProgressEvent};
+
+    /// コマンドの -af の値（フィルタチェーン）
+    fn filter_chain(cmd: &Command) -> Option<String> {
+        let args: Vec<_> = cmd.get_args().collect();
+        args.iter()
+            .position(|arg| *arg == "-af")
+            .map(|i| args[i + 1].to_string_lossy().into_owned())
+    }
+
+    fn command_for(options: &ConvertOptions) -> Command {
+        build_convert_command(Path::new("in.wav"), Path::new("out.wav"), options).unwrap()
+    }
+
+    #[test]
+    fn remove_dc_adds_highpass_before_resampling() {
+        let options = ConvertOptions {
+            remove_dc: true,
+            sample_rate: Some(48000),
+            resampler_quality: Some(ResamplerQuality::High),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter_chain(&command_for(&options)).as_deref(),
+            Some("highpass=f=5,aresample=48000:resampler=soxr:precision=28")
+        );
+        assert_eq!(filter_chain(&command_for(&ConvertOptions::default())), None);
+    }
+
+    #[test]
+    fn gain_keeps_its_sign() {
+        for (gain, filter) in [(-3.5, "volume=-3.5dB"), (6.0, "volume=6dB")] {
+            let options = ConvertOptions {
+                gain_db: Some(gain),
+                ..Default::default()
+            };
+            assert_eq!(
+                filter_chain(&command_for(&options)).as_deref(),
+                Some(filter)
+            );
+        }
+        // 直流成分の除去の後に掛ける
+        let options = ConvertOptions {
+            remove_dc: true,
+            gain_db: Some(-1.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter_chain(&command_for(&options)).as_deref(),
+            Some("highpass=f=5,volume=-1dB")
+        );
+    }
+
+    /// dir に、silence 秒の無音に続けて 0.1 秒のサイン波が鳴る 48kHz モノラル WAV を書く
+    fn write_tone(dir: &Path, name: &str, silence: f32) -> PathBuf {
+        let path = dir.join(name);
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..(silence * 48000.0) as usize {
+            writer.write_sample(0i16).unwrap();
+        }
+        for i in 0..4800 {
+            let t = i as f32 / 48000.0;
+            let sample = (2.0 * std::f32::consts::PI * 440.0 * t).sin() * 0.5;
+            writer
+                .write_sample((sample * i16::MAX as f32) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    #[test]
+    fn invalid_target_is_reported_as_failed() {
+        // ffmpeg を起動できないと終了コードを確かめられないため、ffmpeg がある環境でだけ実行する
+        // （ffmpeg がなければ AUDIOTOOLS_FFMPEG=/bin/false cargo test invalid_target で確かめられる）
+        if ffmpeg_command().arg("-version").output().is_err() {
+            eprintln!("ffmpeg not found; skipping the conversion");
+            return;
+        }
+        let dir = crate::utils::temp_dir_path();
+        fs::create_dir_all(&dir).unwrap();
+        let input = write_tone(&dir, "tone.wav", 0.0);
+        // ffmpeg が受け付けないビットレート
+        let options = ConvertOptions {
+            output_dir: Some(dir.join("out")),
+            output_format: "mp3".to_string(),
+            bitrate: Some("not-a-bitrate".to_string()),
+            ..Default::default()
+        };
+        let mut outcomes = Vec::new();
+        let mut progress = |event: ProgressEvent<'_>| {
+            if let ProgressEvent::FileFinished { path, result } = event {
+                outcomes.push((path.to_path_buf(), result));
             }
+        };
+        let ok = convert_files(
+            std::slice::from_ref(&input),
+            &options,
+            &WalkOptions::default(),
+            Some(&mut progress),
+        )
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!ok);
+        assert_eq!(outcomes, vec![(input, FileOutcome::Failed)]);
+    }
+
+    #[test]
+    fn trim_silence_shortens_a_silent_head() {
+        let options = ConvertOptions {
+            trim_silence: Some(SilenceTrim {
+                threshold_db: -50.0,
+                duration: 0.05,
+            }),
+            ..Default::default()
+        };
+        let remove = "silenceremove=start_periods=1:start_duration=0.05:start_threshold=-50dB";
+        assert_eq!(
+            filter_chain(&command_for(&options)),
+            Some(format!("{},areverse,{},areverse", remove, remove))
+        );
+
+        // 実際の変換は ffmpeg がある環境でだけ確かめる
+        if ffmpeg_command().arg("-version").output().is_err() {
+            eprintln!("ffmpeg not found; skipping the conversion");
+            return;
         }
+        let dir = crate::utils::temp_dir_path();
+        fs::create_dir_all(&dir).unwrap();
+        let input = write_tone(&dir, "head.wav", 0.5);
+        let output = dir.join("trimmed.wav");
+        let result = convert_file(&input, &output, &options);
+        let frames = |path: &Path| WavReader::open(path).map(|reader| reader.duration());
+        let (before, after) = (frames(&input), frames(&output));
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        let (before, after) = (before.unwrap(), after.unwrap());
+        // 0.5 秒の無音がほぼ除かれ、0.1 秒のサイン波が残る
+        assert_eq!(before, 28800);
+        assert!(after < 9600, "{} frames after trimming", after);
+        assert!(after >= 4000, "{} frames after trimming", after);
     }
 }
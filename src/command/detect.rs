@@ -0,0 +1,55 @@
+use crate::error::AudioToolsError;
+use crate::utils::decode::load_samples;
+use crate::utils::detection::AutoStartDetection;
+use std::fs;
+use std::path::Path;
+
+/// オンセット検出のパラメータ
+#[derive(Clone, Debug)]
+pub struct OnsetOptions {
+    pub detection: AutoStartDetection,
+    /// 次のオンセットとみなすのに必要な無音の最短長（秒）
+    pub min_silence: f32,
+    /// 最初のオンセット（waveform・spectrum の --auto-start と同じ開始位置）だけを返す
+    pub first_only: bool,
+}
+
+/// ファイル全体を走査し、無音の後にスレッショルドを超えた位置（秒）をすべて返す
+pub fn detect_onsets(input: &Path, options: &OnsetOptions) -> Result<Vec<f32>, AudioToolsError> {
+    let (samples, sample_rate) = load_samples(input)?;
+    if samples.is_empty() {
+        return Err(AudioToolsError::InvalidInput(
+            "File contains no samples".to_string(),
+        ));
+    }
+
+    if options.first_only {
+        return Ok(options
+            .detection
+            .detect_start_time(&samples, sample_rate as f32)
+            .into_iter()
+            .collect());
+    }
+
+    Ok(options
+        .detection
+        .detect_sounds(&samples, sample_rate as f32, options.min_silence)
+        .into_iter()
+        .map(|(start, _)| start)
+        .collect())
+}
+
+/// waveform の --annotations-file で読める "time,label" のCSV（ラベルは連番）
+pub fn format_annotations(onsets: &[f32]) -> String {
+    let mut csv = "time,label\n".to_string();
+    for (i, time) in onsets.iter().enumerate() {
+        csv.push_str(&format!("{:.3},{}\n", time, i + 1));
+    }
+    csv
+}
+
+/// アノテーションをファイルに書き出す
+pub fn write_annotations(output: &Path, onsets: &[f32]) -> Result<(), AudioToolsError> {
+    fs::write(output, format_annotations(onsets))?;
+    Ok(())
+}
@@ -1,92 +1,1208 @@
+use super::spectrum::{spectral_summary, SpectralSummary};
+use crate::error::AudioToolsError;
+use crate::utils::decode::decode_audio;
+use crate::utils::detection::AutoStartDetection;
 use crate::utils::ffprobe::run_ffprobe;
-use crate::utils::wave_header::WavHeader;
-use crate::utils::{format_size, get_walker, is_audio_file};
+use crate::utils::hash::{hash_audio_data, hash_file, HashAlgorithm};
+use crate::utils::parallel::parallel_map;
+use crate::utils::progress::ProgressCallback;
+use crate::utils::summary::BatchSummary;
+use crate::utils::wave_header::{BroadcastExtension, WavHeader};
+use crate::utils::{
+    csv_escape, format_size, format_size_short, get_walkers, is_audio_file, is_stdio, probe_basic,
+    write_ndjson, WalkOptions,
+};
+use clap::ValueEnum;
+use log::{error, warn};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+/// 出力形式
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum InfoOutputFormat {
+    /// ファイルごとのテキストブロック
+    Text,
+    /// 1ファイル1行のCSV
+    Csv,
+    /// 1ファイル1行のJSON（調べるたびに書き出す）
+    Ndjson,
+}
+
+/// これを超える直流成分（約 -60 dBFS）を警告する
+pub const DC_OFFSET_THRESHOLD: f32 = 0.001;
+
+/// 位相相関がこれを下回るとモノラルにしたとき大きく打ち消し合う
+pub const NEGATIVE_CORRELATION_THRESHOLD: f32 = -0.3;
+
+/// 左右チャンネルの関係
+#[derive(Clone, Copy, Debug)]
+pub struct StereoMetrics {
+    /// 左右の相関係数（1: 同相、0: 無相関、-1: 逆相）
+    pub correlation: f32,
+    /// 全体に占めるサイド成分のエネルギーの割合（0: モノラル、0.5: 無相関、1: 逆相）
+    pub width: f32,
+}
+
+/// ステレオをモノラルに畳み込む前後のレベル（振幅）
+#[derive(Clone, Copy, Debug)]
+pub struct MonoFold {
+    /// 左右のうち大きい方のピーク
+    pub stereo_peak: f32,
+    pub mono_peak: f32,
+    /// 左右の平均パワーのRMS
+    pub stereo_rms: f32,
+    pub mono_rms: f32,
+}
+
+/// 先頭・末尾の無音を測る検出の設定
+#[derive(Clone, Debug)]
+pub struct SilenceDetection {
+    pub detection: AutoStartDetection,
+    /// 末尾の無音とみなす最短の長さ（秒）。これより短い余白は音の一部として扱う
+    pub min_silence: f32,
+}
+
+/// 先頭・末尾の無音の長さ
+#[derive(Clone, Copy, Debug)]
+pub struct SilenceReport {
+    /// ファイルの長さ（秒）
+    pub duration: f32,
+    /// 最初の音の開始と最後の音の終わり（秒）。全体が無音なら None
+    pub active: Option<(f32, f32)>,
+}
+
+impl SilenceReport {
+    /// 先頭の無音の長さ（秒）。全体が無音なら None
+    pub fn leading(&self) -> Option<f32> {
+        self.active.map(|(start, _)| start)
+    }
+
+    /// 末尾の無音の長さ（秒）。全体が無音なら None
+    pub fn trailing(&self) -> Option<f32> {
+        self.active.map(|(_, end)| (self.duration - end).max(0.0))
+    }
+
+    /// ファイル全体に占める割合（%）
+    pub fn percent(&self, seconds: f32) -> f32 {
+        if self.duration > 0.0 {
+            seconds / self.duration * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// レベル分布の最も低いビンの下限（dBFS）。これより小さいサンプル（無音を含む）は最も低いビンに数える
+pub const HISTOGRAM_FLOOR_DBFS: f32 = -96.0;
+
+/// テキスト出力の棒の最大の長さ（文字数）
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+/// サンプルの大きさ（dBFS）の分布。HISTOGRAM_FLOOR_DBFS から 0 dBFS までを等間隔に分け、
+/// 0 dBFS を超えるサンプル（浮動小数点のWAV）は最も高いビンに数える
+#[derive(Clone, Debug)]
+pub struct LevelHistogram {
+    /// 低いビンから順のサンプル数
+    pub counts: Vec<u64>,
+}
+
+impl LevelHistogram {
+    /// 全チャンネルのサンプルを bins 個のビンに分ける
+    pub fn new(samples: &[f32], bins: usize) -> Self {
+        let bins = bins.max(1);
+        let mut counts = vec![0u64; bins];
+        let width = -HISTOGRAM_FLOOR_DBFS / bins as f32;
+        for &sample in samples {
+            let db = 20.0 * sample.abs().max(1e-20).log10();
+            let bin = ((db - HISTOGRAM_FLOOR_DBFS) / width).floor();
+            counts[(bin.max(0.0) as usize).min(bins - 1)] += 1;
+        }
+        Self { counts }
+    }
+
+    /// ビン index の範囲（dBFS）
+    pub fn range(&self, index: usize) -> (f32, f32) {
+        let width = -HISTOGRAM_FLOOR_DBFS / self.counts.len() as f32;
+        let low = HISTOGRAM_FLOOR_DBFS + width * index as f32;
+        (low, low + width)
+    }
+
+    /// ビンごとの割合（%）
+    pub fn percentages(&self) -> Vec<f32> {
+        let total = self.counts.iter().sum::<u64>().max(1) as f32;
+        self.counts
+            .iter()
+            .map(|&count| count as f32 / total * 100.0)
+            .collect()
+    }
+}
+
+/// --histogram のビン数（1〜100）を解析する
+pub fn parse_histogram_bins(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(bins) if (1..=100).contains(&bins) => Ok(bins),
+        _ => Err(format!(
+            "Invalid number of histogram bins: {}. Use 1 to 100",
+            s
+        )),
+    }
+}
+
+/// 1チャンネルのレベル（振幅、0.0〜1.0）
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelLevel {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+const CSV_COLUMNS: &[&str] = &[
+    "path",
+    "container",
+    "size_bytes",
+    "sample_rate",
+    "channels",
+    "bit_depth",
+    "duration_seconds",
+    "codec",
+    "bit_rate",
+];
+
+/// info コマンドのパラメータ
+#[derive(Clone, Debug)]
+pub struct InfoOptions {
+    /// ffprobe で表示する format フィールド
+    pub fields: Vec<String>,
+    pub output_format: InfoOutputFormat,
+    /// ファイルのハッシュアルゴリズム
+    pub hash: HashAlgorithm,
+    /// WAVは data チャンクの中身だけをハッシュする（メタデータの違いを無視する）
+    pub audio_hash: bool,
+    /// チャンネルごとの直流成分を測定する（デコードできる形式のみ）
+    pub detect_dc: bool,
+    /// 平均スペクトルの重心とロールオフを求める（デコードできる形式のみ）
+    pub spectral: bool,
+    /// ステレオファイルの位相相関と広がりを求める（デコードできる形式のみ）
+    pub stereo_metrics: bool,
+    /// チャンネルごとのピークとRMSを求める（デコードできる形式のみ）
+    pub per_channel: bool,
+    /// 指定時は先頭・末尾の無音の長さを測る（デコードできる形式のみ）
+    pub silence_report: Option<SilenceDetection>,
+    /// 指定時はサンプルの大きさの分布をこの数のビンで求める（デコードできる形式のみ）
+    pub histogram: Option<usize>,
+    /// 同時に調べるファイル数（ファイルごとに ffprobe を起動する）
+    pub jobs: usize,
+    /// ffprobe を使わず、WAV・FLAC・Ogg のヘッダから基本情報だけを読む
+    pub no_ffprobe: bool,
+}
+
+/// 1ファイル分の情報（取得できない値は None）
+#[derive(Clone, Debug)]
+pub struct AudioInfo {
+    pub path: PathBuf,
+    /// ffprobe のコンテナ名（例: "wav", "flac"）
+    pub container: Option<String>,
+    pub size_bytes: u64,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub bit_depth: Option<u16>,
+    pub duration_seconds: Option<f64>,
+    pub codec: Option<String>,
+    pub bit_rate: Option<u64>,
+    /// InfoOptions::hash が None 以外のときのハッシュ値（16進数）
+    pub hash: Option<String>,
+    /// WAVファイルのヘッダ
+    pub wav_header: Option<WavHeader>,
+    /// BWFファイルの bext チャンク
+    pub broadcast: Option<BroadcastExtension>,
+    /// InfoOptions::detect_dc 指定時のチャンネルごとの平均値（-1.0〜1.0）
+    pub dc_offset: Option<Vec<f32>>,
+    /// InfoOptions::spectral 指定時のスペクトル重心とロールオフ
+    pub spectral: Option<SpectralSummary>,
+    /// InfoOptions::stereo_metrics 指定時の位相相関と広がり（2チャンネルで無音でない場合のみ）
+    pub stereo: Option<StereoMetrics>,
+    /// InfoOptions::per_channel 指定時のチャンネルごとのピークとRMS
+    pub channel_levels: Option<Vec<ChannelLevel>>,
+    /// InfoOptions::silence_report 指定時の先頭・末尾の無音
+    pub silence: Option<SilenceReport>,
+    /// InfoOptions::histogram 指定時のレベル分布
+    pub histogram: Option<LevelHistogram>,
+    /// ffprobe の format と最初の音声ストリーム（テキスト出力の --fields 用。
+    /// ffprobe が使えなければ Null）
+    pub probe_format: Value,
+    pub probe_stream: Value,
+}
+
+/// ファイルの情報を取得する
+pub fn audio_info(path: &Path, options: &InfoOptions) -> Result<AudioInfo, AudioToolsError> {
+    let size_bytes = fs::metadata(path)?.len();
+
+    let probe: Value = if options.no_ffprobe {
+        Value::Null
+    } else {
+        run_ffprobe(
+            path,
+            &["-print_format", "json", "-show_format", "-show_streams"],
+        )
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or(Value::Null)
+    };
+    let format = probe["format"].clone();
+    // 最初の音声ストリームを対象にする
+    let stream = probe["streams"]
+        .as_array()
+        .and_then(|streams| {
+            streams
+                .iter()
+                .find(|stream| stream["codec_type"] == "audio")
+        })
+        .cloned()
+        .unwrap_or(Value::Null);
+
+    // ffprobe は数値も文字列で返すことがあるため両方を受け付ける
+    let text = |value: &Value| -> Option<String> {
+        match value {
+            Value::String(s) if !s.is_empty() && s != "N/A" => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    };
+    fn number<T: std::str::FromStr>(value: Option<String>) -> Option<T> {
+        value.and_then(|v| v.parse().ok())
+    }
+    // PCMは bits_per_sample、ロスレス圧縮は bits_per_raw_sample にビット深度が入る
+    let bit_depth = number::<u16>(text(&stream["bits_per_raw_sample"]))
+        .or_else(|| number(text(&stream["bits_per_sample"])))
+        .filter(|&bits| bits != 0);
+
+    let (wav_header, broadcast) = if is_wav(path) {
+        let mut file = File::open(path)?;
+        let header = WavHeader::read_from_file(&mut file).ok();
+        let broadcast = BroadcastExtension::read_from_file(&mut file).ok().flatten();
+        (header, broadcast)
+    } else {
+        (None, None)
+    };
+    // ffprobeが使えない（または値を返さない）場合はWAV・FLAC・Oggのヘッダから基本情報を補う
+    let basic = probe_basic(path).ok().filter(|basic| basic.sample_rate > 0);
+
+    let analysis = analyze_samples(path, options);
+
+    Ok(AudioInfo {
+        path: path.to_path_buf(),
+        container: text(&format["format_name"])
+            .or_else(|| basic.as_ref().map(|b| b.container.to_string())),
+        size_bytes,
+        sample_rate: number(text(&stream["sample_rate"])).or(basic.as_ref().map(|b| b.sample_rate)),
+        channels: number(text(&stream["channels"])).or(basic.as_ref().map(|b| b.channels)),
+        bit_depth: bit_depth.or(basic.as_ref().and_then(|b| b.bit_depth)),
+        duration_seconds: number(text(&format["duration"]))
+            .or(basic.as_ref().and_then(|b| b.duration_seconds)),
+        codec: text(&stream["codec_name"])
+            .or_else(|| basic.as_ref().and_then(|b| b.codec).map(str::to_string)),
+        bit_rate: number(text(&format["bit_rate"])).or_else(|| number(text(&stream["bit_rate"]))),
+        hash: compute_hash(path, options)?,
+        wav_header,
+        broadcast,
+        dc_offset: analysis.dc_offset,
+        spectral: analysis.spectral,
+        stereo: analysis.stereo,
+        channel_levels: analysis.channel_levels,
+        silence: analysis.silence,
+        histogram: analysis.histogram,
+        probe_format: format,
+        probe_stream: stream,
+    })
+}
+
+/// デコードしたサンプルから求める値（指定されていないものは None）
+#[derive(Default)]
+struct SampleAnalysis {
+    dc_offset: Option<Vec<f32>>,
+    spectral: Option<SpectralSummary>,
+    stereo: Option<StereoMetrics>,
+    channel_levels: Option<Vec<ChannelLevel>>,
+    silence: Option<SilenceReport>,
+    histogram: Option<LevelHistogram>,
+}
+
+/// 直流成分・スペクトル・ステレオ・チャンネルごとのレベル・無音・レベル分布を求める
+/// （デコードは1回だけ行い、できない場合は警告して None）
+fn analyze_samples(path: &Path, options: &InfoOptions) -> SampleAnalysis {
+    if !options.detect_dc
+        && !options.spectral
+        && !options.stereo_metrics
+        && !options.per_channel
+        && options.silence_report.is_none()
+        && options.histogram.is_none()
+    {
+        return SampleAnalysis::default();
+    }
+    let audio = match decode_audio(path) {
+        Ok(audio) => audio,
+        Err(e) => {
+            warn!("Could not analyze samples of {}: {}", path.display(), e);
+            return SampleAnalysis::default();
+        }
+    };
+
+    let dc_offset = options.detect_dc.then(|| audio.channel_means());
+    let spectral = if options.spectral {
+        let summary = spectral_summary(&audio.mono(), audio.sample_rate);
+        if summary.is_none() {
+            warn!(
+                "Could not measure spectral centroid of {}: file is silent",
+                path.display()
+            );
+        }
+        summary
+    } else {
+        None
+    };
+    let stereo = if options.stereo_metrics && audio.channels == 2 {
+        let metrics = stereo_metrics(&audio.samples);
+        if metrics.is_none() {
+            warn!(
+                "Could not measure phase correlation of {}: a channel is silent",
+                path.display()
+            );
+        }
+        metrics
+    } else {
+        None
+    };
+    let channel_levels = options
+        .per_channel
+        .then(|| channel_levels(&audio.samples, audio.channels));
+    let silence = options
+        .silence_report
+        .as_ref()
+        .map(|silence| silence_report(&audio.mono(), audio.sample_rate, silence));
+    let histogram = options
+        .histogram
+        .map(|bins| LevelHistogram::new(&audio.samples, bins));
+    SampleAnalysis {
+        dc_offset,
+        spectral,
+        stereo,
+        channel_levels,
+        silence,
+        histogram,
+    }
+}
+
+/// 最初の音の開始（detect_start_time）と最後の音の終わり（detect_end_time）から
+/// 先頭・末尾の無音を求める
+pub fn silence_report(
+    samples: &[f32],
+    sample_rate: u32,
+    silence: &SilenceDetection,
+) -> SilenceReport {
+    let sample_rate = sample_rate as f32;
+    let sounds = silence
+        .detection
+        .detect_sounds(samples, sample_rate, silence.min_silence);
+    SilenceReport {
+        duration: samples.len() as f32 / sample_rate,
+        active: sounds
+            .first()
+            .zip(sounds.last())
+            .map(|(first, last)| (first.0, last.1)),
+    }
+}
+
+/// インターリーブされたサンプルをチャンネルごとに分けてピークとRMSを求める
+pub fn channel_levels(samples: &[f32], channels: u16) -> Vec<ChannelLevel> {
+    let channels = channels.max(1) as usize;
+    let mut peaks = vec![0.0f32; channels];
+    let mut squares = vec![0.0f64; channels];
+    for frame in samples.chunks_exact(channels) {
+        for (channel, &sample) in frame.iter().enumerate() {
+            peaks[channel] = peaks[channel].max(sample.abs());
+            squares[channel] += sample as f64 * sample as f64;
+        }
+    }
+    let frames = (samples.len() / channels).max(1) as f64;
+    peaks
+        .into_iter()
+        .zip(squares)
+        .map(|(peak, square)| ChannelLevel {
+            peak,
+            rms: (square / frames).sqrt() as f32,
+        })
+        .collect()
+}
+
+/// インターリーブされたステレオのサンプルから位相相関とミッド／サイドのエネルギー比を求める。
+/// どちらかのチャンネルが無音なら相関が定まらないため None
+pub fn stereo_metrics(samples: &[f32]) -> Option<StereoMetrics> {
+    let (mut left, mut right, mut cross) = (0.0f64, 0.0f64, 0.0f64);
+    for frame in samples.chunks_exact(2) {
+        let (l, r) = (frame[0] as f64, frame[1] as f64);
+        left += l * l;
+        right += r * r;
+        cross += l * r;
+    }
+    if left == 0.0 || right == 0.0 {
+        return None;
+    }
+
+    // ミッド (L+R)/2 とサイド (L-R)/2 のエネルギーは左右のエネルギーと相互相関から求まる
+    let mid = left + right + 2.0 * cross;
+    let side = left + right - 2.0 * cross;
+    Some(StereoMetrics {
+        correlation: (cross / (left * right).sqrt()) as f32,
+        width: (side / (mid + side)) as f32,
+    })
+}
+
+/// インターリーブされたステレオのサンプルを (L + R) * gain でモノラルにしたときの
+/// ピークとRMSを、元のステレオの値とあわせて求める
+pub fn mono_fold(samples: &[f32], gain: f32) -> MonoFold {
+    let (mut stereo_peak, mut mono_peak) = (0.0f32, 0.0f32);
+    let (mut stereo_power, mut mono_power) = (0.0f64, 0.0f64);
+    for frame in samples.chunks_exact(2) {
+        let (l, r) = (frame[0], frame[1]);
+        let mono = (l + r) * gain;
+        stereo_peak = stereo_peak.max(l.abs()).max(r.abs());
+        mono_peak = mono_peak.max(mono.abs());
+        stereo_power += (l as f64 * l as f64 + r as f64 * r as f64) / 2.0;
+        mono_power += mono as f64 * mono as f64;
+    }
+    let frames = (samples.len() / 2).max(1) as f64;
+    MonoFold {
+        stereo_peak,
+        mono_peak,
+        stereo_rms: (stereo_power / frames).sqrt() as f32,
+        mono_rms: (mono_power / frames).sqrt() as f32,
+    }
+}
+
+/// 各ファイルの情報を出力する。取得できなかったファイルがあれば false を返す
 pub fn get_audio_info(
-    input: &PathBuf,
+    inputs: &[PathBuf],
     output: Option<&PathBuf>,
-    fields: &[String],
-    recursive: bool,
-) {
-    let mut output_file =
-        output.map(|path| File::create(path).expect("Failed to create output file"));
-
-    for entry in get_walker(input, recursive) {
-        if let Some(ext) = entry.path().extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-
-            if is_audio_file(&ext_str) {
-                let file_size = fs::metadata(entry.path())
-                    .map(|m| format_size(m.len()))
-                    .unwrap_or_else(|_| "Unknown size".to_string());
-
-                // WAVEファイルの場合は詳細なヘッダ情報を読み取る
-                let mut additional_info = String::new();
-                if ext_str == "wav" {
-                    if let Ok(mut file) = File::open(entry.path()) {
-                        match WavHeader::read_from_file(&mut file) {
-                            Ok(header) => {
-                                additional_info = header.format_info();
-                            }
-                            Err(e) => {
-                                additional_info = format!("Error reading WAV header: {}", e);
-                            }
-                        }
+    options: &InfoOptions,
+    walk: &WalkOptions,
+    progress: Option<ProgressCallback>,
+) -> bool {
+    // "-" は標準出力に書く
+    let mut output_file = output
+        .filter(|path| !is_stdio(path))
+        .map(|path| File::create(path).expect("Failed to create output file"));
+
+    if let InfoOutputFormat::Csv = options.output_format {
+        let mut columns = CSV_COLUMNS.join(",");
+        if options.hash != HashAlgorithm::None {
+            columns.push_str(",hash");
+        }
+        if options.detect_dc {
+            columns.push_str(",dc_offset");
+        }
+        if options.spectral {
+            columns.push_str(",spectral_centroid_hz,spectral_rolloff_hz");
+        }
+        if options.stereo_metrics {
+            columns.push_str(",phase_correlation,stereo_width");
+        }
+        if options.per_channel {
+            columns.push_str(",channel_peak_dbfs,channel_rms_dbfs");
+        }
+        if options.silence_report.is_some() {
+            columns.push_str(
+                ",leading_silence_s,trailing_silence_s,leading_silence_pct,trailing_silence_pct,silent",
+            );
+        }
+        if options.histogram.is_some() {
+            columns.push_str(",histogram");
+        }
+        write_line(&mut output_file, &columns);
+    }
+
+    let mut summary = BatchSummary::for_walk(walk).with_progress(progress);
+    for (source, result) in probe_files(inputs, options, walk) {
+        if summary.should_stop() {
+            break;
+        }
+        let ext_str = source
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let path = walk.display_path(&source);
+        // 並行して調べた結果を --sort の順に受け取るため、開始の通知もこの順になる
+        summary.started(&path);
+        let info = match result {
+            Ok(info) => info,
+            Err(e) => {
+                let error_msg = format!(
+                    "File: {}\nError: Failed to get audio info: {}\n",
+                    path.display(),
+                    e
+                );
+                // CSV・NDJSONの行は読み手がそのまま解析するため、エラーはログにだけ出す
+                match &mut output_file {
+                    Some(file) if matches!(options.output_format, InfoOutputFormat::Text) => {
+                        writeln!(file, "{}", error_msg).expect("Failed to write to output file")
                     }
+                    _ => error!("{}", error_msg.trim_end()),
                 }
+                summary.failed(&path);
+                continue;
+            }
+        };
+        summary.processed(&path);
 
-                // ffprobeによる情報取得
-                let probe_result = run_ffprobe(
-                    entry.path(),
-                    &["-print_format", "json", "-show_format", "-show_streams"],
-                );
+        let text = match options.output_format {
+            InfoOutputFormat::Csv => csv_row(&info, options),
+            InfoOutputFormat::Text => text_block(&info, &ext_str, options),
+            InfoOutputFormat::Ndjson => {
+                write_ndjson(&mut output_file, &json_record(&info, options));
+                continue;
+            }
+        };
+        write_line(&mut output_file, &text);
+    }
 
-                match probe_result {
-                    Ok(_json_output) => {
-                        let format_info = run_ffprobe(
-                            entry.path(),
-                            &[
-                                "-show_entries",
-                                &format!("format={}", fields.join(",")),
-                                "-show_entries",
-                                "stream=codec_name,sample_rate,channels,bit_rate",
-                            ],
-                        )
-                        .unwrap_or_else(|_| "Format information unavailable".to_string());
-
-                        let info = format!(
-                            "File: {}\nFormat: {}\nSize: {}\n{}\n{}\n",
-                            entry.path().display(),
-                            ext_str.to_uppercase(),
-                            file_size,
-                            additional_info,
-                            format_info,
-                        );
-
-                        if let Some(file) = &mut output_file {
-                            writeln!(file, "{}", info).expect("Failed to write to output file");
-                        } else {
-                            println!("{}", info);
-                        }
-                    }
-                    Err(e) => {
-                        let error_msg = format!(
-                            "File: {}\nError: Failed to get audio info: {}\n",
-                            entry.path().display(),
-                            e
-                        );
-                        if let Some(file) = &mut output_file {
-                            writeln!(file, "{}", error_msg)
-                                .expect("Failed to write to output file");
-                        } else {
-                            eprintln!("{}", error_msg);
-                        }
-                    }
+    summary.report("analyzed");
+    !summary.has_failures()
+}
+
+/// 音声ファイルを列挙し、options.jobs 個のスレッドで並行して調べる。
+/// 結果は --sort の順に、元のパスと組にして返す（AudioInfo.path は表示用のパス）
+fn probe_files(
+    inputs: &[PathBuf],
+    options: &InfoOptions,
+    walk: &WalkOptions,
+) -> Vec<(PathBuf, Result<AudioInfo, AudioToolsError>)> {
+    let sources: Vec<PathBuf> = get_walkers(inputs, walk)
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|ext| is_audio_file(&ext.to_string_lossy().to_lowercase()))
+        })
+        .map(|entry| entry.into_path())
+        .collect();
+    let results = parallel_map(&sources, options.jobs, |source| {
+        let path = walk.display_path(source);
+        audio_info(source, options).map(|info| AudioInfo { path, ..info })
+    });
+    sources.into_iter().zip(results).collect()
+}
+
+/// 全ファイルのサンプリングレート・チャンネル数・ビット深度・コーデックを集計し、
+/// 統一されているか、多数派と異なるファイルを報告する。
+/// 読めないファイルがあれば false を返す（--fail-fast のときは集計を出力しない）
+pub fn summarize_audio_info(
+    inputs: &[PathBuf],
+    output: Option<&PathBuf>,
+    options: &InfoOptions,
+    walk: &WalkOptions,
+) -> bool {
+    let mut output_file = output
+        .filter(|path| !is_stdio(path))
+        .map(|path| File::create(path).expect("Failed to create output file"));
+
+    let mut infos = Vec::new();
+    let mut failed = 0;
+    for (source, result) in probe_files(inputs, options, walk) {
+        match result {
+            Ok(info) => infos.push(info),
+            Err(e) => {
+                let path = walk.display_path(&source);
+                error!("Failed to get audio info for {}: {}", path.display(), e);
+                failed += 1;
+                if walk.fail_fast {
+                    return false;
                 }
             }
         }
     }
+
+    write_line(&mut output_file, &summary_report(&infos, failed));
+    failed == 0
+}
+
+/// 集計結果のテキストを作成する
+fn summary_report(infos: &[AudioInfo], failed: usize) -> String {
+    fn value<T: ToString>(value: &Option<T>) -> String {
+        value
+            .as_ref()
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+    const LABELS: [&str; 4] = ["Sample rate", "Channels", "Bit depth", "Codec"];
+    let rows: Vec<[String; 4]> = infos
+        .iter()
+        .map(|info| {
+            [
+                value(&info.sample_rate),
+                value(&info.channels),
+                value(&info.bit_depth),
+                value(&info.codec),
+            ]
+        })
+        .collect();
+
+    let mut report = format!("Files: {}\n", infos.len());
+    if failed > 0 {
+        report.push_str(&format!("Unreadable: {}\n", failed));
+    }
+    report.push_str(&totals_report(infos));
+
+    let mut outliers = Vec::new();
+    for (index, label) in LABELS.iter().enumerate() {
+        // 値ごとのファイル一覧（件数の多い順）
+        let mut groups: BTreeMap<String, Vec<&Path>> = BTreeMap::new();
+        for (info, row) in infos.iter().zip(&rows) {
+            groups
+                .entry(row[index].clone())
+                .or_default()
+                .push(&info.path);
+        }
+        let mut groups: Vec<(String, Vec<&Path>)> = groups.into_iter().collect();
+        groups.sort_by_key(|(_, paths)| std::cmp::Reverse(paths.len()));
+
+        let values: Vec<String> = groups
+            .iter()
+            .map(|(value, paths)| format!("{} ({})", value, paths.len()))
+            .collect();
+        report.push_str(&format!("{}: {}\n", label, values.join(", ")));
+
+        // 最も多い値以外を外れ値とする
+        for (value, paths) in groups.iter().skip(1) {
+            for path in paths {
+                outliers.push(format!("  {} {}: {}", label, value, path.display()));
+            }
+        }
+    }
+
+    if outliers.is_empty() {
+        report.push_str("Result: uniform\n");
+    } else {
+        report.push_str("Result: not uniform. Files that differ from the most common value:\n");
+        report.push_str(&outliers.join("\n"));
+        report.push('\n');
+    }
+    report
+}
+
+/// 合計と1ファイルあたりの平均の再生時間とサイズ。
+/// 長さが分からないファイルは再生時間の合計と平均から除く
+fn totals_report(infos: &[AudioInfo]) -> String {
+    let durations: Vec<f64> = infos
+        .iter()
+        .filter_map(|info| info.duration_seconds)
+        .collect();
+    let total_duration: f64 = durations.iter().sum();
+    let total_bytes: u64 = infos.iter().map(|info| info.size_bytes).sum();
+
+    let mut report = format!(
+        "Total: {} across {} files, {}\n",
+        format_duration(total_duration),
+        infos.len(),
+        format_size_short(total_bytes)
+    );
+    if !infos.is_empty() {
+        let average_duration = match durations.len() {
+            0 => "unknown".to_string(),
+            count => format_duration(total_duration / count as f64),
+        };
+        report.push_str(&format!(
+            "Average: {}, {} per file\n",
+            average_duration,
+            format_size_short(total_bytes / infos.len() as u64)
+        ));
+    }
+    if durations.len() < infos.len() {
+        report.push_str(&format!(
+            "Duration unknown: {} files (not included in the total)\n",
+            infos.len() - durations.len()
+        ));
+    }
+    report
+}
+
+/// 再生時間を "3h 12m"、"4m 05s"、"12.3s" の形式にする
+fn format_duration(seconds: f64) -> String {
+    let whole = seconds.round() as u64;
+    if whole >= 3600 {
+        format!("{}h {:02}m", whole / 3600, whole % 3600 / 60)
+    } else if whole >= 60 {
+        format!("{}m {:02}s", whole / 60, whole % 60)
+    } else {
+        format!("{:.1}s", seconds)
+    }
+}
+
+fn write_line(output_file: &mut Option<File>, line: &str) {
+    if let Some(file) = output_file {
+        writeln!(file, "{}", line).expect("Failed to write to output file");
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// テキスト形式のファイルごとの情報ブロックを作成する
+fn text_block(info: &AudioInfo, ext_str: &str, options: &InfoOptions) -> String {
+    let hash_line = match &info.hash {
+        Some(digest) => format!("Hash: {}:{}\n", hash_label(&info.path, options), digest),
+        None => String::new(),
+    };
+
+    // WAVEファイルの場合は詳細なヘッダ情報を表示する
+    let mut additional_info = String::new();
+    if let Some(header) = &info.wav_header {
+        additional_info = header.format_info();
+        // BWFファイルは bext チャンクの情報も表示する
+        if let Some(bext) = &info.broadcast {
+            additional_info.push('\n');
+            additional_info.push_str(&bext.format_info(header.sample_rate()));
+        }
+    } else if ext_str == "wav" {
+        additional_info = "Error reading WAV header".to_string();
+    }
+
+    // チャンネルごとの直流成分（閾値を超えるものに印を付ける）
+    let dc_block = match &info.dc_offset {
+        Some(means) => {
+            let mut block = "DC Offset:\n".to_string();
+            for (channel, &mean) in means.iter().enumerate() {
+                block.push_str(&format!(
+                    "Channel {}: {:+.6} ({:.1} dBFS){}\n",
+                    channel + 1,
+                    mean,
+                    20.0 * mean.abs().log10(),
+                    if mean.abs() > DC_OFFSET_THRESHOLD {
+                        " [exceeds threshold]"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+            block.push('\n');
+            block
+        }
+        None => String::new(),
+    };
+
+    let spectral_block = match &info.spectral {
+        Some(summary) => format!(
+            "Spectral:\nCentroid: {:.1} Hz\nRolloff (85%): {:.1} Hz\n\n",
+            summary.centroid_hz, summary.rolloff_hz
+        ),
+        None => String::new(),
+    };
+
+    // 2チャンネル以外（と無音のチャンネルがあるファイル）は n/a と表示する
+    let stereo_block = if options.stereo_metrics {
+        match &info.stereo {
+            Some(stereo) => format!(
+                "Stereo:\nPhase Correlation: {:+.3}{}\nWidth: {:.3}\n\n",
+                stereo.correlation,
+                if stereo.correlation < NEGATIVE_CORRELATION_THRESHOLD {
+                    " [cancels in mono]"
+                } else {
+                    ""
+                },
+                stereo.width
+            ),
+            None => "Stereo:\nPhase Correlation: n/a\nWidth: n/a\n\n".to_string(),
+        }
+    } else {
+        String::new()
+    };
+
+    // 無音のチャンネルは -inf dBFS になる
+    let levels_block = match &info.channel_levels {
+        Some(levels) => {
+            let mut block = "Channel Levels:\n".to_string();
+            for (channel, level) in levels.iter().enumerate() {
+                block.push_str(&format!(
+                    "Channel {}: peak {:.1} dBFS, RMS {:.1} dBFS{}\n",
+                    channel + 1,
+                    amplitude_to_dbfs(level.peak),
+                    amplitude_to_dbfs(level.rms),
+                    if level.peak == 0.0 { " [silent]" } else { "" }
+                ));
+            }
+            block.push('\n');
+            block
+        }
+        None => String::new(),
+    };
+
+    // 全体が無音のファイルは先頭・末尾の区別がないため別に表示する
+    let silence_block = match &info.silence {
+        Some(silence) => match silence.leading().zip(silence.trailing()) {
+            Some((leading, trailing)) => format!(
+                "Silence:\nLeading: {:.3} s ({:.1}%)\nTrailing: {:.3} s ({:.1}%)\n\n",
+                leading,
+                silence.percent(leading),
+                trailing,
+                silence.percent(trailing)
+            ),
+            None => format!(
+                "Silence:\nEntire file is silent ({:.3} s) [silent]\n\n",
+                silence.duration
+            ),
+        },
+        None if options.silence_report.is_some() => "Silence:\nn/a\n\n".to_string(),
+        None => String::new(),
+    };
+
+    // 大きいレベルから順に、最も多いビンを HISTOGRAM_BAR_WIDTH 文字とした棒で表示する
+    let histogram_block = match &info.histogram {
+        Some(histogram) => {
+            let percentages = histogram.percentages();
+            let largest = percentages
+                .iter()
+                .copied()
+                .fold(0.0, f32::max)
+                .max(f32::EPSILON);
+            let mut block = "Level Histogram:\n".to_string();
+            for (index, percent) in percentages.iter().enumerate().rev() {
+                let (low, high) = histogram.range(index);
+                let low = if index == 0 {
+                    "-inf".to_string()
+                } else {
+                    format!("{:.1}", low)
+                };
+                let bar = (percent / largest * HISTOGRAM_BAR_WIDTH as f32).round() as usize;
+                block.push_str(&format!(
+                    "{:>6} to {:>5.1} dBFS {:>5.1}% {}\n",
+                    low,
+                    high,
+                    percent,
+                    "#".repeat(bar)
+                ));
+            }
+            block.push('\n');
+            block
+        }
+        None if options.histogram.is_some() => "Level Histogram:\nn/a\n\n".to_string(),
+        None => String::new(),
+    };
+
+    let format_info = probe_sections(info, &options.fields);
+
+    format!(
+        "File: {}\nFormat: {}\nSize: {}\n{}{}\n{}{}{}{}{}{}{}\n",
+        info.path.display(),
+        ext_str.to_uppercase(),
+        format_size(info.size_bytes),
+        hash_line,
+        additional_info,
+        dc_block,
+        spectral_block,
+        stereo_block,
+        levels_block,
+        silence_block,
+        histogram_block,
+        format_info,
+    )
+}
+
+/// ffprobe を使わずにヘッダから読んだ基本情報（読めなければ None）
+fn header_summary(info: &AudioInfo) -> Option<String> {
+    let sample_rate = info.sample_rate?;
+    let mut summary = "Header Information (without ffprobe):\n".to_string();
+    if let Some(codec) = &info.codec {
+        summary.push_str(&format!("Codec: {}\n", codec));
+    }
+    summary.push_str(&format!("Sample Rate: {} Hz\n", sample_rate));
+    if let Some(channels) = info.channels {
+        summary.push_str(&format!("Channels: {}\n", channels));
+    }
+    if let Some(bits) = info.bit_depth {
+        summary.push_str(&format!("Bit Depth: {} bits\n", bits));
+    }
+    if let Some(duration) = info.duration_seconds {
+        summary.push_str(&format!("Duration: {:.3} s\n", duration));
+    }
+    Some(summary)
+}
+
+/// ffprobe の既定の出力と同じ [STREAM] / [FORMAT] の key=value 形式で値を並べる。
+/// --fields は "format.duration" や "stream.sample_rate" のように表示先を指定でき、
+/// 指定のない名前は format にあれば format に、なければストリームに表示する。
+/// ストリームのフィールドを指定しない場合はストリームの基本の値を表示する
+fn probe_sections(info: &AudioInfo, fields: &[String]) -> String {
+    const STREAM_FIELDS: [&str; 4] = ["codec_name", "sample_rate", "channels", "bit_rate"];
+    if info.probe_format.is_null() {
+        return match header_summary(info) {
+            Some(summary) => summary,
+            None => "Format information unavailable".to_string(),
+        };
+    }
+
+    // (キー, 値がなくても空欄で表示するか)
+    let mut stream_keys: Vec<(&str, bool)> = if fields.iter().any(|f| f.starts_with("stream.")) {
+        Vec::new()
+    } else {
+        STREAM_FIELDS.iter().map(|&key| (key, false)).collect()
+    };
+    let mut format_keys: Vec<(&str, bool)> = Vec::new();
+    for field in fields {
+        if let Some(key) = field.strip_prefix("stream.") {
+            stream_keys.push((key, true));
+        } else if let Some(key) = field.strip_prefix("format.") {
+            format_keys.push((key, true));
+        } else if info.probe_format[field.as_str()].is_null()
+            && !info.probe_stream[field.as_str()].is_null()
+        {
+            if !stream_keys.iter().any(|&(key, _)| key == field) {
+                stream_keys.push((field, true));
+            }
+        } else {
+            format_keys.push((field, true));
+        }
+    }
+
+    let section = |name: &str, values: &Value, keys: &[(&str, bool)]| {
+        let mut block = format!("[{}]\n", name);
+        for &(key, required) in keys {
+            match &values[key] {
+                Value::String(value) => block.push_str(&format!("{}={}\n", key, value)),
+                Value::Number(value) => block.push_str(&format!("{}={}\n", key, value)),
+                // 存在しないフィールドは空欄にする
+                _ if required => block.push_str(&format!("{}=\n", key)),
+                _ => {}
+            }
+        }
+        block.push_str(&format!("[/{}]\n", name));
+        block
+    };
+
+    let mut sections = String::new();
+    if !info.probe_stream.is_null() || stream_keys.iter().any(|&(_, required)| required) {
+        sections.push_str(&section("STREAM", &info.probe_stream, &stream_keys));
+    }
+    sections.push_str(&section("FORMAT", &info.probe_format, &format_keys));
+    sections
+}
+
+/// CSVの1行を作成する（取得できない値は空欄にする）
+fn csv_row(info: &AudioInfo, options: &InfoOptions) -> String {
+    fn cell<T: ToString>(value: &Option<T>) -> String {
+        value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    let mut values = vec![
+        info.path.display().to_string(),
+        cell(&info.container),
+        info.size_bytes.to_string(),
+        cell(&info.sample_rate),
+        cell(&info.channels),
+        cell(&info.bit_depth),
+        cell(&info.duration_seconds),
+        cell(&info.codec),
+        cell(&info.bit_rate),
+    ];
+    if options.hash != HashAlgorithm::None {
+        values.push(cell(&info.hash));
+    }
+    if options.detect_dc {
+        // チャンネルごとの値をセミコロンで区切る
+        values.push(
+            info.dc_offset
+                .as_ref()
+                .map(|means| {
+                    means
+                        .iter()
+                        .map(|mean| mean.to_string())
+                        .collect::<Vec<_>>()
+                        .join(";")
+                })
+                .unwrap_or_default(),
+        );
+    }
+    if options.spectral {
+        values.push(cell(&info.spectral.map(|s| s.centroid_hz)));
+        values.push(cell(&info.spectral.map(|s| s.rolloff_hz)));
+    }
+    if options.stereo_metrics {
+        values.push(cell(&info.stereo.map(|s| s.correlation)));
+        values.push(cell(&info.stereo.map(|s| s.width)));
+    }
+    if options.per_channel {
+        // チャンネルごとの値をセミコロンで区切る（無音は -inf）
+        let joined = |level: fn(&ChannelLevel) -> f32| {
+            info.channel_levels
+                .as_ref()
+                .map(|levels| {
+                    levels
+                        .iter()
+                        .map(|l| format!("{:.2}", amplitude_to_dbfs(level(l))))
+                        .collect::<Vec<_>>()
+                        .join(";")
+                })
+                .unwrap_or_default()
+        };
+        values.push(joined(|level| level.peak));
+        values.push(joined(|level| level.rms));
+    }
+    if options.silence_report.is_some() {
+        let silence = info.silence.as_ref();
+        let leading = silence.and_then(|s| s.leading());
+        let trailing = silence.and_then(|s| s.trailing());
+        let percent =
+            |seconds: Option<f32>| silence.zip(seconds).map(|(s, seconds)| s.percent(seconds));
+        values.push(cell(&leading));
+        values.push(cell(&trailing));
+        values.push(cell(&percent(leading)));
+        values.push(cell(&percent(trailing)));
+        values.push(cell(&silence.map(|s| s.active.is_none())));
+    }
+    if options.histogram.is_some() {
+        // 低いビンから順のサンプル数をセミコロンで区切る
+        values.push(
+            info.histogram
+                .as_ref()
+                .map(|histogram| {
+                    histogram
+                        .counts
+                        .iter()
+                        .map(|count| count.to_string())
+                        .collect::<Vec<_>>()
+                        .join(";")
+                })
+                .unwrap_or_default(),
+        );
+    }
+
+    values
+        .iter()
+        .map(|value| csv_escape(value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// NDJSONの1ファイル分。キーはCSVの列名と同じで、取得できない値は null、
+/// チャンネルごとの値は配列にする。オプションの値は指定したときだけ出力する
+#[derive(Serialize)]
+struct InfoRecord<'a> {
+    path: String,
+    container: &'a Option<String>,
+    size_bytes: u64,
+    sample_rate: Option<u32>,
+    channels: Option<u16>,
+    bit_depth: Option<u16>,
+    duration_seconds: Option<f64>,
+    codec: &'a Option<String>,
+    bit_rate: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<&'a Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dc_offset: Option<&'a Option<Vec<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spectral_centroid_hz: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spectral_rolloff_hz: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phase_correlation: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stereo_width: Option<Option<f32>>,
+    /// 無音のチャンネル（-inf）は JSON で表せないため null になる
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel_peak_dbfs: Option<Option<Vec<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel_rms_dbfs: Option<Option<Vec<f32>>>,
+    /// 全体が無音のファイルは先頭・末尾の値が null になり、silent が true になる
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leading_silence_s: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trailing_silence_s: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leading_silence_pct: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trailing_silence_pct: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    silent: Option<Option<bool>>,
+    /// 低いビンから順のサンプル数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    histogram: Option<Option<&'a Vec<u64>>>,
+}
+
+fn json_record<'a>(info: &'a AudioInfo, options: &InfoOptions) -> InfoRecord<'a> {
+    let silence = |value: fn(&SilenceReport) -> Option<f32>| {
+        options
+            .silence_report
+            .as_ref()
+            .map(|_| info.silence.as_ref().and_then(value))
+    };
+    let levels = |level: fn(&ChannelLevel) -> f32| {
+        options.per_channel.then(|| {
+            info.channel_levels
+                .as_ref()
+                .map(|levels| levels.iter().map(|l| amplitude_to_dbfs(level(l))).collect())
+        })
+    };
+    InfoRecord {
+        path: info.path.display().to_string(),
+        container: &info.container,
+        size_bytes: info.size_bytes,
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        bit_depth: info.bit_depth,
+        duration_seconds: info.duration_seconds,
+        codec: &info.codec,
+        bit_rate: info.bit_rate,
+        hash: (options.hash != HashAlgorithm::None).then_some(&info.hash),
+        dc_offset: options.detect_dc.then_some(&info.dc_offset),
+        spectral_centroid_hz: options
+            .spectral
+            .then(|| info.spectral.map(|s| s.centroid_hz)),
+        spectral_rolloff_hz: options
+            .spectral
+            .then(|| info.spectral.map(|s| s.rolloff_hz)),
+        phase_correlation: options
+            .stereo_metrics
+            .then(|| info.stereo.map(|s| s.correlation)),
+        stereo_width: options.stereo_metrics.then(|| info.stereo.map(|s| s.width)),
+        channel_peak_dbfs: levels(|level| level.peak),
+        channel_rms_dbfs: levels(|level| level.rms),
+        leading_silence_s: silence(|s| s.leading()),
+        trailing_silence_s: silence(|s| s.trailing()),
+        leading_silence_pct: silence(|s| s.leading().map(|seconds| s.percent(seconds))),
+        trailing_silence_pct: silence(|s| s.trailing().map(|seconds| s.percent(seconds))),
+        silent: options
+            .silence_report
+            .as_ref()
+            .map(|_| info.silence.map(|s| s.active.is_none())),
+        histogram: options
+            .histogram
+            .map(|_| info.histogram.as_ref().map(|h| &h.counts)),
+    }
+}
+
+fn amplitude_to_dbfs(amplitude: f32) -> f32 {
+    20.0 * amplitude.log10()
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+}
+
+/// オプションに応じてファイル全体または WAV の data チャンクをハッシュする
+fn compute_hash(path: &Path, options: &InfoOptions) -> Result<Option<String>, std::io::Error> {
+    if options.audio_hash && is_wav(path) {
+        hash_audio_data(path, options.hash)
+    } else {
+        hash_file(path, options.hash)
+    }
+}
+
+/// テキスト出力でのハッシュの種類（例: "sha256", "sha256-audio"）
+fn hash_label(path: &Path, options: &InfoOptions) -> String {
+    if options.audio_hash && is_wav(path) {
+        format!("{}-audio", options.hash.name())
+    } else {
+        options.hash.name().to_string()
+    }
 }
@@ -1,82 +1,902 @@
-use crate::utils::{format_size, get_walker, is_audio_file};
+use super::info::channel_levels;
+use crate::error::AudioToolsError;
+use crate::utils::cache::FileCache;
+use crate::utils::decode::decode_audio;
+use crate::utils::detection::AutoStartDetection;
+use crate::utils::ffmpeg::ffmpeg_command;
+use crate::utils::progress::ProgressCallback;
+use crate::utils::summary::BatchSummary;
+use crate::utils::{
+    csv_escape, format_size, get_walkers, is_audio_file, is_stdio, load_samples, write_ndjson,
+    WalkOptions,
+};
+use clap::ValueEnum;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
-use std::process::Command;
-
-// Measure audio loudness according to EBU R128 standard
-pub fn measure_loudness(input: &PathBuf, output: Option<&PathBuf>, recursive: bool) {
-    let mut output_file =
-        output.map(|path| File::create(path).expect("Failed to create output file"));
-
-    for entry in get_walker(input, recursive) {
-        if let Some(ext) = entry.path().extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-
-            // 対応フォーマットのチェック
-            if is_audio_file(&ext_str) {
-                // ファイルサイズの取得と変換
-                let file_size = fs::metadata(entry.path())
-                    .map(|m| format_size(m.len()))
-                    .unwrap_or_else(|_| "Unknown size".to_string());
-
-                // ffmpegコマンドの実行
-                let loudness_output = Command::new("ffmpeg")
-                    .arg("-i")
-                    .arg(entry.path())
-                    .arg("-filter_complex")
-                    .arg("ebur128=peak=true")
-                    .arg("-f")
-                    .arg("null")
-                    .arg("-")
-                    .output();
-
-                match loudness_output {
-                    Ok(output) => {
-                        // 結果の出力
-                        let info = String::from_utf8_lossy(&output.stderr);
-                        let formatted_output = format!(
-                            "File: {}\nFormat: {}\nSize: {}\nLoudness Analysis:\n{}\n",
-                            entry.path().display(),
-                            ext_str.to_uppercase(),
-                            file_size,
-                            // EBU R128の関連する行のみを抽出
-                            info.lines()
-                                .filter(|line| {
-                                    line.contains("LUFS")
-                                        || line.contains("LU")
-                                        || line.contains("Summary")
-                                        || line.contains("Integrated")
-                                        || line.contains("Loudness")
-                                        || line.contains("Range")
-                                        || line.contains("True Peak")
-                                })
-                                .collect::<Vec<&str>>()
-                                .join("\n")
-                        );
-
-                        if let Some(file) = &mut output_file {
-                            writeln!(file, "{}", formatted_output)
-                                .expect("Failed to write to output file");
-                        } else {
-                            println!("{}", formatted_output);
-                        }
+use std::path::{Path, PathBuf};
+
+/// 出力形式
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum LoudnessOutputFormat {
+    /// ファイルごとのテキストブロック（ebur128 のサマリー）
+    Text,
+    /// 全ファイルの測定値をまとめたJSON配列
+    Json,
+    /// 1ファイル1行のJSON（測定するたびに書き出す）
+    Ndjson,
+    /// 1ファイル1行のCSV
+    Csv,
+}
+
+const CSV_COLUMNS: &[&str] = &[
+    "path",
+    "integrated_lufs",
+    "loudness_range_lu",
+    "true_peak_dbfs",
+    "threshold_lufs",
+];
+
+/// EBU R128 の測定結果（ffmpegの出力から読み取れない値は None）
+#[derive(Clone, Debug, Serialize)]
+pub struct LoudnessInfo {
+    /// 統合ラウドネス（LUFS）
+    #[serde(rename = "integrated_lufs")]
+    pub integrated: Option<f32>,
+    /// ラウドネスレンジ（LU）
+    #[serde(rename = "loudness_range_lu")]
+    pub range: Option<f32>,
+    /// トゥルーピーク（dBFS）
+    #[serde(rename = "true_peak_dbfs")]
+    pub true_peak: Option<f32>,
+    /// 統合ラウドネスのゲート閾値（LUFS）
+    #[serde(rename = "threshold_lufs")]
+    pub threshold: Option<f32>,
+    /// ラウドネスレンジの閾値・下限・上限（テキスト出力のサマリーにだけ使う）
+    #[serde(skip)]
+    pub range_detail: RangeDetail,
+    /// 前後の無音を除いた区間の測定結果（--gate-silence のときだけ）
+    #[serde(rename = "active_region", skip_serializing_if = "Option::is_none")]
+    pub active: Option<ActiveLoudness>,
+    /// サンプルのピーク・RMS・クレストファクター・DR（--dynamics のときだけ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamics: Option<Dynamics>,
+    /// 測定値を ebur128 のサマリーの書式で並べたテキスト
+    #[serde(skip)]
+    pub summary: String,
+}
+
+impl LoudnessInfo {
+    /// 測定値を ebur128 のサマリーと同じ書式で並べた summary を設定する
+    /// （読み取れなかった値の行は省く）。キャッシュの有無で出力が変わらないよう、
+    /// 測定した場合もキャッシュから読んだ場合もこの書式を使う
+    fn with_summary(mut self) -> Self {
+        let mut lines = vec!["Summary:".to_string()];
+        let mut section = |header: &str, values: &[(&str, Option<f32>, &str)]| {
+            lines.push(format!("  {}:", header));
+            for &(label, value, unit) in values {
+                if let Some(value) = value {
+                    lines.push(format!("    {:<11}{:5.1} {}", label, value, unit));
+                }
+            }
+        };
+        section(
+            "Integrated loudness",
+            &[
+                ("I:", self.integrated, "LUFS"),
+                ("Threshold:", self.threshold, "LUFS"),
+            ],
+        );
+        section(
+            "Loudness range",
+            &[
+                ("LRA:", self.range, "LU"),
+                ("Threshold:", self.range_detail.threshold, "LUFS"),
+                ("LRA low:", self.range_detail.low, "LUFS"),
+                ("LRA high:", self.range_detail.high, "LUFS"),
+            ],
+        );
+        section("True peak", &[("Peak:", self.true_peak, "dBFS")]);
+        self.summary = lines.join("\n");
+        self
+    }
+}
+
+/// ebur128 のサマリーにあるラウドネスレンジの閾値・下限・上限（LUFS）
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct RangeDetail {
+    pub threshold: Option<f32>,
+    pub low: Option<f32>,
+    pub high: Option<f32>,
+}
+
+/// 前後の無音を除いた、音のある区間の統合ラウドネス
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveLoudness {
+    /// 区間の開始（秒）
+    #[serde(rename = "start_s")]
+    pub start: f32,
+    /// 区間の終了（秒）
+    #[serde(rename = "end_s")]
+    pub end: f32,
+    /// 区間の統合ラウドネス（LUFS）
+    #[serde(rename = "integrated_lufs")]
+    pub integrated: Option<f32>,
+}
+
+/// DR を求めるブロックの長さ（秒）
+const DR_BLOCK_SECONDS: f32 = 3.0;
+
+/// DR の RMS に使う、大きい方からのブロックの割合
+const DR_LOUDEST_BLOCKS: f32 = 0.2;
+
+/// デコードしたサンプルから求めるダイナミクスの指標
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Dynamics {
+    /// 全チャンネルのサンプルピーク（dBFS）。無音なら None
+    #[serde(rename = "sample_peak_dbfs")]
+    pub peak: Option<f32>,
+    /// 全チャンネルのRMS（dBFS、サイン波のピークより 3 dB 低い）。無音なら None
+    #[serde(rename = "rms_dbfs")]
+    pub rms: Option<f32>,
+    /// ピークとRMSの差（dB）。無音なら None
+    #[serde(rename = "crest_factor_db")]
+    pub crest_factor: Option<f32>,
+    /// DR14（TT DR Meter）と同じ方法で求めたダイナミックレンジ（整数）。無音なら None
+    pub dr: Option<f32>,
+}
+
+/// --cache に保存する1ファイル分の測定結果。ffmpeg のログは保存せず、
+/// テキスト出力のサマリーは読み込み時に測定値から組み立て直す
+#[derive(Serialize, Deserialize)]
+pub struct CachedLoudness {
+    /// 測定時の --gate-silence の設定（設定が変われば測定し直す）
+    gate: Option<AutoStartDetection>,
+    integrated: Option<f32>,
+    range: Option<f32>,
+    true_peak: Option<f32>,
+    threshold: Option<f32>,
+    #[serde(default)]
+    range_detail: RangeDetail,
+    active: Option<ActiveLoudness>,
+    #[serde(default)]
+    dynamics: Option<Dynamics>,
+}
+
+impl CachedLoudness {
+    fn new(loudness: &LoudnessInfo, gate: Option<&AutoStartDetection>) -> Self {
+        Self {
+            gate: gate.cloned(),
+            integrated: loudness.integrated,
+            range: loudness.range,
+            true_peak: loudness.true_peak,
+            threshold: loudness.threshold,
+            range_detail: loudness.range_detail,
+            active: loudness.active.clone(),
+            dynamics: loudness.dynamics,
+        }
+    }
+
+    fn loudness(&self, dynamics: bool) -> LoudnessInfo {
+        LoudnessInfo {
+            integrated: self.integrated,
+            range: self.range,
+            true_peak: self.true_peak,
+            threshold: self.threshold,
+            range_detail: self.range_detail,
+            active: self.active.clone(),
+            dynamics: self.dynamics.filter(|_| dynamics),
+            summary: String::new(),
+        }
+        .with_summary()
+    }
+}
+
+/// JSON・NDJSON出力の1ファイル分
+#[derive(Serialize)]
+struct LoudnessRecord<'a> {
+    path: String,
+    #[serde(flatten)]
+    loudness: &'a LoudnessInfo,
+}
+
+impl<'a> LoudnessRecord<'a> {
+    fn new(path: &Path, loudness: &'a LoudnessInfo) -> Self {
+        Self {
+            path: path.display().to_string(),
+            loudness,
+        }
+    }
+}
+
+/// --check-target の基準のプリセット
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum TargetPreset {
+    /// -14 LUFS ±1 LU、トゥルーピーク -1 dBTP 以下
+    Youtube,
+    /// -14 LUFS ±1 LU、トゥルーピーク -1 dBTP 以下
+    Spotify,
+    /// -16 LUFS ±1 LU、トゥルーピーク -1 dBTP 以下
+    AppleMusic,
+    /// -23 LUFS ±0.5 LU、トゥルーピーク -1 dBTP 以下（放送）
+    EbuR128,
+    /// --target-lufs・--tolerance・--max-true-peak で指定した値だけを使う
+    Custom,
+}
+
+/// 納品先のラウドネス基準
+#[derive(Clone, Copy, Debug)]
+pub struct LoudnessTarget {
+    /// 統合ラウドネスの目標値（LUFS）
+    pub integrated: f32,
+    /// 目標値から許容する差（LU）
+    pub tolerance: f32,
+    /// トゥルーピークの上限（dBTP、None なら検査しない）
+    pub max_true_peak: Option<f32>,
+}
+
+impl LoudnessTarget {
+    /// プリセットの値を指定された値で上書きした基準を作る（custom では目標値が必須）
+    pub fn new(
+        preset: TargetPreset,
+        integrated: Option<f32>,
+        tolerance: Option<f32>,
+        max_true_peak: Option<f32>,
+    ) -> Result<Self, AudioToolsError> {
+        let base = match preset {
+            TargetPreset::Youtube | TargetPreset::Spotify => Some((-14.0, 1.0)),
+            TargetPreset::AppleMusic => Some((-16.0, 1.0)),
+            TargetPreset::EbuR128 => Some((-23.0, 0.5)),
+            TargetPreset::Custom => None,
+        };
+        let integrated = integrated.or(base.map(|(lufs, _)| lufs)).ok_or_else(|| {
+            AudioToolsError::InvalidInput("--check-target custom needs --target-lufs".to_string())
+        })?;
+        let tolerance = tolerance.or(base.map(|(_, lu)| lu)).unwrap_or(1.0);
+        if tolerance < 0.0 {
+            return Err(AudioToolsError::InvalidInput(format!(
+                "Tolerance must not be negative (got {} LU)",
+                tolerance
+            )));
+        }
+        Ok(Self {
+            integrated,
+            tolerance,
+            max_true_peak: max_true_peak.or(base.map(|_| -1.0)),
+        })
+    }
+
+    /// 測定結果を基準と比べ、合否と結果の説明を返す
+    /// （音のある区間を測定した場合はその統合ラウドネスで判定する）
+    fn check(&self, loudness: &LoudnessInfo) -> (bool, String) {
+        let (label, integrated) = match &loudness.active {
+            Some(active) => ("active integrated", active.integrated),
+            None => ("integrated", loudness.integrated),
+        };
+        let Some(integrated) = integrated else {
+            return (false, format!("{} loudness could not be measured", label));
+        };
+        let deviation = integrated - self.integrated;
+        let mut pass = deviation.abs() <= self.tolerance;
+        let mut result = format!("{} {:.1} LUFS ({:+.1} LU", label, integrated, deviation);
+        if !pass {
+            result.push_str(&format!(", allowed ±{:.1}", self.tolerance));
+        }
+        result.push(')');
+
+        if let Some(max_true_peak) = self.max_true_peak {
+            match loudness.true_peak {
+                Some(true_peak) if true_peak <= max_true_peak => {
+                    result.push_str(&format!(", true peak {:.1} dBTP", true_peak))
+                }
+                Some(true_peak) => {
+                    pass = false;
+                    result.push_str(&format!(
+                        ", true peak {:.1} dBTP ({:.1} dB above {:.1})",
+                        true_peak,
+                        true_peak - max_true_peak,
+                        max_true_peak
+                    ));
+                }
+                None => {
+                    pass = false;
+                    result.push_str(", true peak could not be measured");
+                }
+            }
+        }
+        (pass, result)
+    }
+}
+
+impl fmt::Display for LoudnessTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} LUFS ±{:.1} LU", self.integrated, self.tolerance)?;
+        if let Some(max_true_peak) = self.max_true_peak {
+            write!(f, ", true peak ≤ {:.1} dBTP", max_true_peak)?;
+        }
+        Ok(())
+    }
+}
+
+/// 100msごとに ebur128 フィルタが出力する途中経過の値
+#[derive(Clone, Copy, Debug)]
+pub struct LoudnessFrame {
+    /// 経過時間（秒）
+    pub time: f32,
+    /// モーメンタリーラウドネス（400ms, LUFS）
+    pub momentary: f32,
+    /// ショートタームラウドネス（3s, LUFS）
+    pub short_term: f32,
+}
+
+/// 1ファイルのラウドネスを測定する
+pub fn measure_loudness_file(path: &Path) -> Result<LoudnessInfo, AudioToolsError> {
+    Ok(parse_summary(&run_ebur128(path, None)?))
+}
+
+/// 前後の無音を除いた区間だけのラウドネスを測定する（音が見つからなければ None）。
+/// EBU R128 のゲートは区間内の静かな部分を除くもので、この区間の切り出しとは別に働く
+pub fn measure_active_loudness(
+    path: &Path,
+    detection: &AutoStartDetection,
+) -> Result<Option<ActiveLoudness>, AudioToolsError> {
+    let (samples, sample_rate) = load_samples(path)?;
+    let Some((start, end)) = detection.detect_active_range(&samples, sample_rate as f32) else {
+        return Ok(None);
+    };
+    let info = parse_summary(&run_ebur128(path, Some((start, end)))?);
+    Ok(Some(ActiveLoudness {
+        start,
+        end,
+        integrated: info.integrated,
+    }))
+}
+
+/// サンプルピーク・RMS・クレストファクターと DR を求める。
+/// DR はチャンネルごとに 3 秒のブロックに分け、RMS の大きい 20% のブロックの RMS と
+/// 2 番目に大きいブロックのピークの比をとり、チャンネル間で平均する
+pub fn measure_dynamics(path: &Path) -> Result<Dynamics, AudioToolsError> {
+    let audio = decode_audio(path)?;
+    let levels = channel_levels(&audio.samples, audio.channels);
+    let peak = levels.iter().map(|level| level.peak).fold(0.0, f32::max);
+    let power = levels
+        .iter()
+        .map(|level| level.rms * level.rms)
+        .sum::<f32>()
+        / levels.len().max(1) as f32;
+    let to_db = |amplitude: f32| (amplitude > 0.0).then(|| 20.0 * amplitude.log10());
+    let (peak_db, rms_db) = (to_db(peak), to_db(power.sqrt()));
+
+    let channels = audio.channels.max(1) as usize;
+    let block_frames = ((DR_BLOCK_SECONDS * audio.sample_rate as f32) as usize).max(1);
+    let values: Vec<f32> = (0..channels)
+        .filter_map(|channel| {
+            let samples: Vec<f32> = audio
+                .samples
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .copied()
+                .collect();
+            channel_dr(&samples, block_frames)
+        })
+        .collect();
+
+    Ok(Dynamics {
+        peak: peak_db,
+        rms: rms_db,
+        crest_factor: peak_db.zip(rms_db).map(|(peak, rms)| peak - rms),
+        // DR は整数で表す
+        dr: (values.len() == channels).then(|| {
+            (values.iter().sum::<f32>() / channels as f32)
+                .max(0.0)
+                .round()
+        }),
+    })
+}
+
+/// 1チャンネルの DR（dB）。無音なら None
+fn channel_dr(samples: &[f32], block_frames: usize) -> Option<f32> {
+    // ブロックの RMS はサイン波でピークと同じ値になるよう √2 倍する
+    let mut blocks: Vec<(f32, f32)> = samples
+        .chunks(block_frames)
+        .map(|block| {
+            let power =
+                block.iter().map(|&x| x as f64 * x as f64).sum::<f64>() / block.len() as f64;
+            let peak = block.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+            ((2.0 * power).sqrt() as f32, peak)
+        })
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let mut peaks: Vec<f32> = blocks.iter().map(|&(_, peak)| peak).collect();
+    peaks.sort_by(|a, b| b.total_cmp(a));
+    let peak = peaks.get(1).copied().unwrap_or(peaks[0]);
+
+    blocks.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let loudest = ((blocks.len() as f32 * DR_LOUDEST_BLOCKS).round() as usize).max(1);
+    let power = blocks[..loudest]
+        .iter()
+        .map(|&(rms, _)| rms * rms)
+        .sum::<f32>()
+        / loudest as f32;
+    let rms = power.sqrt();
+    (rms > 0.0 && peak > 0.0).then(|| 20.0 * (peak / rms).log10())
+}
+
+/// ファイル全体を測定し、gate があれば音のある区間も、dynamics ならダイナミクスも測定する。
+/// 区間やダイナミクスが求められない場合は警告してそれ以外の値を返す
+fn measure_with_gate(
+    path: &Path,
+    gate: Option<&AutoStartDetection>,
+    dynamics: bool,
+    walk: &WalkOptions,
+) -> Result<LoudnessInfo, AudioToolsError> {
+    let mut loudness = measure_loudness_file(path)?;
+    if dynamics {
+        match measure_dynamics(path) {
+            Ok(measured) => loudness.dynamics = Some(measured),
+            Err(e) => warn!(
+                "Could not measure the dynamics of {}: {}",
+                walk.display_path(path).display(),
+                e
+            ),
+        }
+    }
+    if let Some(detection) = gate {
+        let shown = walk.display_path(path);
+        match measure_active_loudness(path, detection) {
+            Ok(Some(active)) => loudness.active = Some(active),
+            Ok(None) => warn!(
+                "No sound above the threshold in {}; reporting the whole file only",
+                shown.display()
+            ),
+            Err(e) => warn!(
+                "Could not find the active region of {}: {}",
+                shown.display(),
+                e
+            ),
+        }
+    }
+    Ok(loudness)
+}
+
+/// cache に同じ設定の測定結果があり、ファイルが変わっていなければそれを使う。
+/// なければ measure_with_gate で測定して cache に加える（失敗は保存しない）
+fn measure_cached(
+    path: &Path,
+    gate: Option<&AutoStartDetection>,
+    dynamics: bool,
+    walk: &WalkOptions,
+    cache: &mut Option<FileCache<CachedLoudness>>,
+) -> Result<LoudnessInfo, AudioToolsError> {
+    let Some(cache) = cache else {
+        return measure_with_gate(path, gate, dynamics, walk);
+    };
+    if let Some(cached) = cache
+        .get(path)
+        .filter(|cached| cached.gate.as_ref() == gate && (!dynamics || cached.dynamics.is_some()))
+    {
+        debug!("Using cached loudness for {}", path.display());
+        return Ok(cached.loudness(dynamics));
+    }
+    let loudness = measure_with_gate(path, gate, dynamics, walk)?;
+    cache.insert(path, CachedLoudness::new(&loudness, gate));
+    Ok(loudness)
+}
+
+/// --cache の変更を書き戻す（書けなくても測定結果には影響しないためエラーは表示だけ）
+fn save_cache(cache: Option<FileCache<CachedLoudness>>) {
+    if let Some(cache) = cache {
+        if let Err(e) = cache.save() {
+            error!("Failed to write the loudness cache: {}", e);
+        }
+    }
+}
+
+/// ラウドネスの時間変化（100msごとの値）と最終的な測定結果を求める
+pub fn measure_loudness_curve(
+    path: &Path,
+) -> Result<(Vec<LoudnessFrame>, LoudnessInfo), AudioToolsError> {
+    let info = run_ebur128(path, None)?;
+    let frames = info.lines().filter_map(parse_frame).collect();
+    Ok((frames, parse_summary(&info)))
+}
+
+/// ebur128 フィルタを通して ffmpeg のログ（標準エラー出力）を返す。
+/// range を指定するとその区間（秒）だけを測定する
+fn run_ebur128(path: &Path, range: Option<(f32, f32)>) -> Result<String, AudioToolsError> {
+    let filter = match range {
+        Some((start, end)) => format!("atrim=start={}:end={},ebur128=peak=true", start, end),
+        None => "ebur128=peak=true".to_string(),
+    };
+    let mut cmd = ffmpeg_command();
+    cmd.arg("-i")
+        .arg(path)
+        .arg("-filter_complex")
+        .arg(filter)
+        .arg("-f")
+        .arg("null")
+        .arg("-");
+    debug!("Running {:?}", cmd);
+    let output = cmd
+        .output()
+        .map_err(|e| AudioToolsError::Ffmpeg(format!("could not run ffmpeg: {}", e)))?;
+    if !output.status.success() {
+        return Err(AudioToolsError::Ffmpeg(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+/// 途中経過の行（"t: 0.1  TARGET:-23 LUFS  M: -30.1 S:-120.7  I: ..."）を読み取る
+fn parse_frame(line: &str) -> Option<LoudnessFrame> {
+    // ラベルは行頭か空白の直後にあるものだけを探す（"TARGET:" などに一致させない）
+    let value = |label: &str| -> Option<f32> {
+        let start = line
+            .match_indices(label)
+            .find(|(i, _)| *i == 0 || line[..*i].ends_with(char::is_whitespace))?
+            .0;
+        line[start + label.len()..]
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    };
+    Some(LoudnessFrame {
+        time: value("t:")?,
+        momentary: value("M:")?,
+        short_term: value("S:")?,
+    })
+}
+
+/// ffmpeg のログから測定結果を読み取る
+fn parse_summary(info: &str) -> LoudnessInfo {
+    // 最後に出力されるサマリーの値を使う（途中経過の行は含まない）。
+    // サマリーでは統合ラウドネスの閾値が LRA の閾値より先に出力される
+    let summary = info.rfind("Summary:").map_or(info, |start| &info[start..]);
+    let value_in = |text: &str, label: &str| -> Option<f32> {
+        text.lines()
+            .filter_map(|line| line.trim().strip_prefix(label))
+            .find_map(|rest| rest.split_whitespace().next()?.parse().ok())
+    };
+    let summary_value = |label: &str| value_in(summary, label);
+    // LRA の閾値は "Loudness range:" の後の "Threshold:"
+    let range = summary
+        .find("Loudness range:")
+        .map_or("", |start| &summary[start..]);
+
+    LoudnessInfo {
+        integrated: summary_value("I:"),
+        range: summary_value("LRA:"),
+        true_peak: summary_value("Peak:"),
+        threshold: summary_value("Threshold:"),
+        range_detail: RangeDetail {
+            threshold: value_in(range, "Threshold:"),
+            low: value_in(range, "LRA low:"),
+            high: value_in(range, "LRA high:"),
+        },
+        active: None,
+        dynamics: None,
+        summary: String::new(),
+    }
+    .with_summary()
+}
+
+/// loudness コマンドの測定のパラメータ
+#[derive(Clone, Debug)]
+pub struct LoudnessOptions {
+    pub output_format: LoudnessOutputFormat,
+    /// 指定時は前後の無音を除いた区間も測定する
+    pub gate: Option<AutoStartDetection>,
+    /// サンプルピーク・RMS・クレストファクター・DR も求める
+    pub dynamics: bool,
+    /// 測定結果を再利用するキャッシュファイル
+    pub cache: Option<PathBuf>,
+}
+
+// Measure audio loudness according to EBU R128 standard. Returns false when a
+// file could not be measured
+pub fn measure_loudness(
+    inputs: &[PathBuf],
+    output: Option<&PathBuf>,
+    options: &LoudnessOptions,
+    walk: &WalkOptions,
+    progress: Option<ProgressCallback>,
+) -> bool {
+    let (output_format, gate, dynamics) = (
+        options.output_format,
+        options.gate.as_ref(),
+        options.dynamics,
+    );
+    let mut output_file = open_output(output);
+    let mut cache = options.cache.as_deref().map(FileCache::open);
+    let mut summary = BatchSummary::for_walk(walk).with_progress(progress);
+    match output_format {
+        LoudnessOutputFormat::Text | LoudnessOutputFormat::Ndjson => {}
+        LoudnessOutputFormat::Csv => {
+            let mut columns = CSV_COLUMNS.join(",");
+            if gate.is_some() {
+                columns.push_str(",active_start_s,active_end_s,active_integrated_lufs");
+            }
+            if dynamics {
+                columns.push_str(",sample_peak_dbfs,rms_dbfs,crest_factor_db,dr");
+            }
+            write_line(&mut output_file, &columns);
+        }
+        LoudnessOutputFormat::Json => {
+            // JSONは全ファイルの測定後に配列としてまとめて出力する
+            let mut results = Vec::new();
+            for target in loudness_targets(inputs, walk) {
+                if summary.should_stop() {
+                    break;
+                }
+                let path = walk.display_path(&target);
+                summary.started(&path);
+                match measure_cached(&target, gate, dynamics, walk, &mut cache) {
+                    Ok(loudness) => {
+                        summary.processed(&path);
+                        results.push((path, loudness));
                     }
                     Err(e) => {
-                        let error_msg = format!(
-                            "File: {}\nError: Failed to measure loudness: {}\n",
-                            entry.path().display(),
-                            e
-                        );
-                        if let Some(file) = &mut output_file {
-                            writeln!(file, "{}", error_msg)
-                                .expect("Failed to write to output file");
-                        } else {
-                            eprintln!("{}", error_msg);
-                        }
+                        error!("Failed to measure loudness of {}: {}", path.display(), e);
+                        summary.failed(&path);
                     }
                 }
             }
+            let records: Vec<LoudnessRecord> = results
+                .iter()
+                .map(|(path, loudness)| LoudnessRecord::new(path, loudness))
+                .collect();
+            let json = serde_json::to_string_pretty(&records).expect("Failed to serialize to JSON");
+            write_line(&mut output_file, &json);
+            save_cache(cache);
+            summary.report("measured");
+            return !summary.has_failures();
         }
     }
+
+    for target in loudness_targets(inputs, walk) {
+        if summary.should_stop() {
+            break;
+        }
+        let path = walk.display_path(&target);
+        summary.started(&path);
+        if let LoudnessOutputFormat::Csv | LoudnessOutputFormat::Ndjson = output_format {
+            match measure_cached(&target, gate, dynamics, walk, &mut cache) {
+                Ok(loudness) if matches!(output_format, LoudnessOutputFormat::Ndjson) => {
+                    summary.processed(&path);
+                    write_ndjson(&mut output_file, &LoudnessRecord::new(&path, &loudness));
+                }
+                Ok(loudness) => {
+                    summary.processed(&path);
+                    write_line(
+                        &mut output_file,
+                        &csv_row(&path, &loudness, gate.is_some(), dynamics),
+                    );
+                }
+                Err(e) => {
+                    error!("Failed to measure loudness of {}: {}", path.display(), e);
+                    summary.failed(&path);
+                }
+            }
+            continue;
+        }
+
+        let ext_str = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        // ファイルサイズの取得と変換
+        let file_size = fs::metadata(&target)
+            .map(|m| format_size(m.len()))
+            .unwrap_or_else(|_| "Unknown size".to_string());
+
+        match measure_cached(&target, gate, dynamics, walk, &mut cache) {
+            Ok(loudness) => {
+                summary.processed(&path);
+                // 結果の出力
+                let mut formatted_output = format!(
+                    "File: {}\nFormat: {}\nSize: {}\nLoudness Analysis:\n{}\n",
+                    path.display(),
+                    ext_str.to_uppercase(),
+                    file_size,
+                    loudness.summary
+                );
+                if let Some(active) = &loudness.active {
+                    let integrated = active
+                        .integrated
+                        .map_or("n/a".to_string(), |lufs| format!("{:.1} LUFS", lufs));
+                    formatted_output.push_str(&format!(
+                        "  Active region:\n    Start: {:.3} s\n    End: {:.3} s\n    I: {}\n",
+                        active.start, active.end, integrated
+                    ));
+                }
+                if let Some(measured) = &loudness.dynamics {
+                    let level = |value: Option<f32>, unit: &str| {
+                        value.map_or("n/a".to_string(), |db| format!("{:.1} {}", db, unit))
+                    };
+                    let dr = measured
+                        .dr
+                        .map_or("n/a".to_string(), |dr| format!("DR{:.0}", dr));
+                    formatted_output.push_str(&format!(
+                        "  Dynamics:\n    Peak: {}\n    RMS: {}\n    Crest factor: {}\n    DR: {}\n",
+                        level(measured.peak, "dBFS"),
+                        level(measured.rms, "dBFS"),
+                        level(measured.crest_factor, "dB"),
+                        dr
+                    ));
+                }
+                write_line(&mut output_file, &formatted_output);
+            }
+            Err(e) => {
+                let error_msg = format!(
+                    "File: {}\nError: Failed to measure loudness: {}\n",
+                    path.display(),
+                    e
+                );
+                if let Some(file) = &mut output_file {
+                    writeln!(file, "{}", error_msg).expect("Failed to write to output file");
+                } else {
+                    error!("{}", error_msg.trim_end());
+                }
+                summary.failed(&path);
+            }
+        }
+    }
+
+    save_cache(cache);
+    summary.report("measured");
+    !summary.has_failures()
+}
+
+/// 各ファイルを基準と比較して PASS/FAIL を出力する。
+/// 基準を満たさない（または測定できない）ファイルがあれば false を返す
+pub fn check_loudness_targets(
+    inputs: &[PathBuf],
+    output: Option<&PathBuf>,
+    target: &LoudnessTarget,
+    gate: Option<&AutoStartDetection>,
+    cache: Option<&Path>,
+    walk: &WalkOptions,
+    progress: Option<ProgressCallback>,
+) -> bool {
+    let mut output_file = open_output(output);
+    let mut cache = cache.map(FileCache::open);
+    let mut summary = BatchSummary::for_walk(walk).with_progress(progress);
+    info!("Target: {}", target);
+
+    for target_path in loudness_targets(inputs, walk) {
+        if summary.should_stop() {
+            break;
+        }
+        let path = walk.display_path(&target_path);
+        summary.started(&path);
+        let (pass, result) = match measure_cached(&target_path, gate, false, walk, &mut cache) {
+            Ok(loudness) => target.check(&loudness),
+            Err(e) => (false, format!("failed to measure loudness: {}", e)),
+        };
+        if pass {
+            summary.processed(&path);
+        } else {
+            summary.failed(&path);
+        }
+        let status = if pass { "PASS" } else { "FAIL" };
+        write_line(
+            &mut output_file,
+            &format!("{} {}: {}", status, path.display(), result),
+        );
+    }
+
+    save_cache(cache);
+    summary.report("passed");
+    !summary.has_failures()
+}
+
+/// 出力先のファイルを作成する（"-" や未指定は標準出力）
+fn open_output(output: Option<&PathBuf>) -> Option<File> {
+    output
+        .filter(|path| !is_stdio(path))
+        .map(|path| File::create(path).expect("Failed to create output file"))
+}
+
+/// 測定対象の音声ファイルを列挙する
+fn loudness_targets(inputs: &[PathBuf], walk: &WalkOptions) -> Vec<PathBuf> {
+    get_walkers(inputs, walk)
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|ext| is_audio_file(&ext.to_string_lossy().to_lowercase()))
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn write_line(output_file: &mut Option<File>, line: &str) {
+    if let Some(file) = output_file {
+        writeln!(file, "{}", line).expect("Failed to write to output file");
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// CSVの1行を作成する（取得できない値は空欄にする）。gated のときは音のある区間の列も加える
+fn csv_row(path: &Path, loudness: &LoudnessInfo, gated: bool, dynamics: bool) -> String {
+    let cell = |value: Option<f32>| value.map(|v| v.to_string()).unwrap_or_default();
+    let mut cells = vec![
+        csv_escape(&path.display().to_string()),
+        cell(loudness.integrated),
+        cell(loudness.range),
+        cell(loudness.true_peak),
+        cell(loudness.threshold),
+    ];
+    if gated {
+        let active = loudness.active.as_ref();
+        cells.extend([
+            cell(active.map(|active| active.start)),
+            cell(active.map(|active| active.end)),
+            cell(active.and_then(|active| active.integrated)),
+        ]);
+    }
+    if dynamics {
+        let measured = loudness.dynamics.as_ref();
+        cells.extend([
+            cell(measured.and_then(|d| d.peak)),
+            cell(measured.and_then(|d| d.rms)),
+            cell(measured.and_then(|d| d.crest_factor)),
+            cell(measured.and_then(|d| d.dr)),
+        ]);
+    }
+    cells.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ebur128=peak=true を通した ffmpeg のログ（途中経過の行を含む）
+    const EBUR128_LOG: &str = "\
+[Parsed_ebur128_0 @ 0x5581] t: 0.1       TARGET:-23 LUFS    M:-120.7 S:-120.7     I: -70.0 LUFS       LRA:   0.0 LU  FTPK: -6.1 dBFS  TPK: -6.1 dBFS
+[Parsed_ebur128_0 @ 0x5581] t: 0.2       TARGET:-23 LUFS    M: -18.4 S:-120.7     I: -18.4 LUFS       LRA:   0.0 LU  FTPK: -6.0 dBFS  TPK: -6.0 dBFS
+size=N/A time=00:00:03.00 bitrate=N/A speed= 150x
+[Parsed_ebur128_0 @ 0x5581] Summary:
+
+  Integrated loudness:
+    I:         -16.3 LUFS
+    Threshold: -26.4 LUFS
+
+  Loudness range:
+    LRA:         4.2 LU
+    Threshold: -36.5 LUFS
+    LRA low:   -19.1 LUFS
+    LRA high:  -14.9 LUFS
+
+  True peak:
+    Peak:       -0.8 dBFS
+";
+
+    #[test]
+    fn cached_summary_round_trips() {
+        let measured = parse_summary(EBUR128_LOG);
+        let cached = CachedLoudness::new(&measured, None).loudness(false);
+        assert_eq!(cached.summary, measured.summary);
+        assert!(!cached.summary.contains("t:"));
+
+        let reparsed = parse_summary(&cached.summary);
+        assert_eq!(reparsed.integrated, Some(-16.3));
+        assert_eq!(reparsed.threshold, Some(-26.4));
+        assert_eq!(reparsed.range, Some(4.2));
+        assert_eq!(reparsed.true_peak, Some(-0.8));
+        assert_eq!(reparsed.range_detail.threshold, Some(-36.5));
+        assert_eq!(reparsed.range_detail.low, Some(-19.1));
+        assert_eq!(reparsed.range_detail.high, Some(-14.9));
+    }
 }
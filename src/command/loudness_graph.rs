@@ -0,0 +1,227 @@
+use super::loudness::{measure_loudness_curve, LoudnessFrame};
+use crate::error::AudioToolsError;
+use crate::utils::plot::{validate_dimensions, ImageFormat, ImageOutputOptions, PlotColors};
+use crate::utils::{get_walker, is_audio_file, WalkOptions};
+use log::{error, info};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// ラウドネスグラフ生成のパラメータ
+#[derive(Clone, Debug)]
+pub struct LoudnessGraphOptions {
+    /// 縦軸の下限（LUFS）。これより小さい値は下限に張り付けて描画する
+    pub floor: f32,
+    /// モーメンタリーは wave、ショートタームは rms、統合値は annotation の色で描画する
+    pub colors: PlotColors,
+    /// タイトル・目盛り・凡例のフォント名
+    pub font: String,
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub fn create_loudness_graphs(
+    input: &PathBuf,
+    options: &LoudnessGraphOptions,
+    walk: &WalkOptions,
+    naming: &ImageOutputOptions,
+) -> bool {
+    let mut ok = true;
+    for entry in get_walker(input, walk) {
+        if let Some(ext) = entry.path().extension() {
+            if is_audio_file(&ext.to_string_lossy().to_lowercase()) {
+                let input_path = PathBuf::from(entry.path());
+                // 波形・スペクトログラムの画像と並べて置けるよう名前に _loudness を付ける
+                let stem = input_path.file_stem().unwrap_or_default().to_string_lossy();
+                let extension = options.format.extension();
+                let graph = input_path.with_file_name(format!("{}_loudness.{}", stem, extension));
+                let output_path = naming.image_path(&graph, input, extension);
+
+                if naming.is_up_to_date(&output_path, &[&input_path]) {
+                    info!(
+                        "Skipped: {} (image is newer than the source. Use --force to regenerate)",
+                        output_path.display()
+                    );
+                    continue;
+                }
+                if let Some(dir) = output_path.parent() {
+                    if let Err(e) = fs::create_dir_all(dir) {
+                        error!("Error creating {}: {}", dir.display(), e);
+                        if walk.fail_fast {
+                            return false;
+                        }
+                        ok = false;
+                        continue;
+                    }
+                }
+
+                match create_loudness_graph(&input_path, &output_path, options) {
+                    Ok(Some(integrated)) => info!(
+                        "Created loudness graph: {} -> {} (integrated {:.1} LUFS)",
+                        input_path.display(),
+                        output_path.display(),
+                        integrated
+                    ),
+                    Ok(None) => info!(
+                        "Created loudness graph: {} -> {}",
+                        input_path.display(),
+                        output_path.display()
+                    ),
+                    Err(e) => {
+                        error!("Error processing {}: {}", input_path.display(), e);
+                        if walk.fail_fast {
+                            return false;
+                        }
+                        ok = false;
+                    }
+                }
+            }
+        }
+    }
+    ok
+}
+
+/// モーメンタリー（400ms）とショートターム（3s）のラウドネスの推移を描画し、
+/// 統合ラウドネス（読み取れた場合）を返す
+pub fn create_loudness_graph(
+    input: &Path,
+    output: &Path,
+    options: &LoudnessGraphOptions,
+) -> Result<Option<f32>, AudioToolsError> {
+    validate_dimensions(options.width, options.height)?;
+    if options.floor >= 0.0 {
+        return Err(AudioToolsError::InvalidInput(
+            "Loudness floor must be below 0 LUFS".to_string(),
+        ));
+    }
+
+    let (frames, loudness) = measure_loudness_curve(input)?;
+    if frames.is_empty() {
+        return Err(AudioToolsError::Ffmpeg(
+            "ffmpeg reported no loudness values".to_string(),
+        ));
+    }
+
+    let title = input
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("Loudness");
+
+    match options.format {
+        ImageFormat::Png => {
+            let root =
+                BitMapBackend::new(output, (options.width, options.height)).into_drawing_area();
+            draw_loudness(&root, title, &frames, loudness.integrated, options)?;
+            root.present()?;
+        }
+        ImageFormat::Svg => {
+            let root = SVGBackend::new(output, (options.width, options.height)).into_drawing_area();
+            draw_loudness(&root, title, &frames, loudness.integrated, options)?;
+            root.present()?;
+        }
+    }
+
+    Ok(loudness.integrated)
+}
+
+/// 描画バックエンドに依存せずラウドネスの推移を描画する
+fn draw_loudness<DB>(
+    root: &DrawingArea<DB, Shift>,
+    title: &str,
+    frames: &[LoudnessFrame],
+    integrated: Option<f32>,
+    options: &LoudnessGraphOptions,
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let colors = &options.colors;
+    let font = options.font.as_str();
+    let floor = options.floor;
+    root.fill(&colors.background)?;
+
+    let end_time = frames
+        .iter()
+        .map(|frame| frame.time)
+        .fold(0.0f32, f32::max)
+        .max(0.1);
+
+    let mut chart = ChartBuilder::on(root)
+        .margin(40)
+        .caption(title, (font, 24).into_font().color(&colors.foreground))
+        .set_label_area_size(LabelAreaPosition::Left, 60)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .build_cartesian_2d(0.0..end_time, floor..0.0)?;
+
+    // 描画幅に応じて時間軸のラベル数を調整する
+    let x_labels = (chart.plotting_area().dim_in_pixel().0 / 80).max(2) as usize;
+
+    chart
+        .configure_mesh()
+        .label_style((font, 14).into_font().color(&colors.foreground))
+        .axis_style(colors.foreground)
+        .light_line_style(TRANSPARENT)
+        .bold_line_style(colors.grid)
+        .x_desc("Time (s)")
+        .y_desc("Loudness (LUFS)")
+        .x_labels(x_labels)
+        .x_label_formatter(&|x| {
+            if end_time >= 10.0 {
+                format!("{:.0}s", x)
+            } else {
+                format!("{:.1}s", x)
+            }
+        })
+        .y_labels(10)
+        .draw()?;
+
+    // 無音区間などで下限を下回る値は下限に揃える
+    let points = |value: fn(&LoudnessFrame) -> f32| -> Vec<(f32, f32)> {
+        frames
+            .iter()
+            .map(|frame| (frame.time, value(frame).max(floor)))
+            .collect()
+    };
+
+    let momentary = colors.wave;
+    chart
+        .draw_series(LineSeries::new(points(|frame| frame.momentary), momentary))?
+        .label("Momentary (400 ms)")
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], momentary));
+
+    let short_term = colors.rms.mix(1.0);
+    chart
+        .draw_series(LineSeries::new(
+            points(|frame| frame.short_term),
+            short_term.stroke_width(2),
+        ))?
+        .label("Short-term (3 s)")
+        .legend(move |(x, y)| {
+            PathElement::new(vec![(x, y), (x + 20, y)], short_term.stroke_width(2))
+        });
+
+    // 統合ラウドネスを基準線として描く
+    if let Some(integrated) = integrated.filter(|&value| value > floor) {
+        let annotation = colors.annotation;
+        chart
+            .draw_series(LineSeries::new(
+                vec![(0.0, integrated), (end_time, integrated)],
+                annotation,
+            ))?
+            .label(format!("Integrated ({:.1} LUFS)", integrated))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], annotation));
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(colors.background.mix(0.8))
+        .border_style(colors.foreground)
+        .label_font((font, 14).into_font().color(&colors.foreground))
+        .position(SeriesLabelPosition::LowerRight)
+        .draw()?;
+
+    Ok(())
+}
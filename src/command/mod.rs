@@ -1,6 +1,14 @@
+pub mod batch;
+pub mod concat;
 pub mod convert;
+pub mod detect;
 pub mod info;
 pub mod loudness;
+pub mod loudness_graph;
 pub mod normalize;
+pub mod roundtrip;
 pub mod spectrum;
+pub mod split;
+pub mod trim;
+pub mod verify;
 pub mod waveform;
@@ -1,61 +1,454 @@
-use super::convert;
-use crate::utils::detection::detect_peak_level;
-use crate::utils::get_walker;
-use std::path::PathBuf;
+use super::convert::{self, ConvertOptions, OverwritePolicy};
+use crate::error::AudioToolsError;
+use crate::utils::decode::normalize_sample;
+use crate::utils::detection::{detect_channel_peak_levels, overall_peak_level};
+use crate::utils::progress::ProgressCallback;
+use crate::utils::summary::BatchSummary;
+use crate::utils::{get_walkers, WalkOptions};
+use clap::ValueEnum;
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use log::{debug, error, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
 
+/// ソフトクリップを始める振幅（約 -0.9 dBFS）。目標レベル -1 dBFS 以下のピークには掛からない
+const SOFT_CLIP_KNEE: f32 = 0.9;
+
+/// ノーマライズのパラメータ
+#[derive(Clone, Debug)]
+pub struct NormalizeOptions {
+    /// 目標ピークレベル（dBFS）
+    pub level: f32,
+    /// ゲインの後にリミッターを掛ける場合の上限レベル（dBFS）
+    pub limiter_ceiling: Option<f32>,
+    /// WAV出力のビット深度（16、24 または 32）
+    pub bit_depth: u8,
+    /// WAV出力のサンプル形式（None の場合は 32 ビットなら浮動小数点、それ以外は整数）
+    pub sample_format: Option<WavSampleFormat>,
+    /// WAV同士の処理で、ゲイン後に SOFT_CLIP_KNEE を超える部分をなだらかに抑える
+    /// （指定しない場合は ±1.0 でハードクリップする）
+    pub soft_clip: bool,
+    /// WAV同士の処理で、量子化の前にTPDFディザを加える
+    pub dither: bool,
+    /// チャンネルごとにピークを検出し、それぞれを目標レベルに合わせる
+    pub per_channel: bool,
+    /// per_channel でも全チャンネルに最小のゲインを掛けて定位を保つ
+    pub link: bool,
+    pub force: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            level: -1.0,
+            limiter_ceiling: None,
+            bit_depth: 24,
+            sample_format: None,
+            soft_clip: false,
+            dither: false,
+            per_channel: false,
+            link: false,
+            force: false,
+        }
+    }
+}
+
+/// WAV出力のサンプル形式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WavSampleFormat {
+    /// 整数PCM
+    Int,
+    /// IEEE浮動小数点（32ビットのみ）
+    Float,
+}
+
+impl NormalizeOptions {
+    /// WAV出力のビット数とサンプル形式。ビット深度とサンプル形式の組み合わせもここで検証する
+    pub fn wav_format(&self) -> Result<(u16, SampleFormat), AudioToolsError> {
+        let format = self.sample_format.unwrap_or(if self.bit_depth == 32 {
+            WavSampleFormat::Float
+        } else {
+            WavSampleFormat::Int
+        });
+        match (self.bit_depth, format) {
+            (16 | 24 | 32, WavSampleFormat::Int) => Ok((self.bit_depth as u16, SampleFormat::Int)),
+            (32, WavSampleFormat::Float) => Ok((32, SampleFormat::Float)),
+            (16 | 24, WavSampleFormat::Float) => Err(AudioToolsError::InvalidInput(format!(
+                "Float samples are only written at 32-bit (got {}-bit)",
+                self.bit_depth
+            ))),
+            (depth, _) => Err(AudioToolsError::UnsupportedFormat(format!(
+                "{}-bit WAV. Supported depths are: [16, 24, 32]",
+                depth
+            ))),
+        }
+    }
+}
+
+/// 1ファイルのピークを目標レベル（dBFS）に合わせて書き出す。
+/// 出力形式は出力ファイルの拡張子で決まる。WAVからWAVへの処理はffmpegを使わず
+/// ゲインを直接掛け、それ以外とリミッターを使う場合はffmpegで変換する
+pub fn normalize_file(
+    input: &Path,
+    output: &Path,
+    options: &NormalizeOptions,
+) -> Result<(), AudioToolsError> {
+    normalize_measured(input, output, options, None)
+}
+
+/// normalize_file と同じ処理。peaks に測定済みのチャンネルごとのピーク（dBFS）を渡すと、
+/// WAV同士の処理ではファイルを読み直して測定しない
+fn normalize_measured(
+    input: &Path,
+    output: &Path,
+    options: &NormalizeOptions,
+    peaks: Option<Vec<f32>>,
+) -> Result<(), AudioToolsError> {
+    let extension = |path: &Path| {
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+    };
+    let output_format = extension(output).ok_or_else(|| {
+        AudioToolsError::InvalidInput(
+            "Output file needs an extension (wav, flac, or mp3)".to_string(),
+        )
+    })?;
+
+    let native = output_format == "wav"
+        && extension(input).as_deref() == Some("wav")
+        && options.limiter_ceiling.is_none();
+    if native {
+        return normalize_wav(input, output, options, peaks);
+    }
+    // ffmpegで書き出すWAVは16/24ビットの整数PCMだけ
+    if output_format == "wav" && options.bit_depth == 32 {
+        return Err(AudioToolsError::UnsupportedFormat(
+            "32-bit WAV output needs WAV input and no --limiter".to_string(),
+        ));
+    }
+    convert::convert_file(
+        input,
+        output,
+        &ConvertOptions {
+            output_format,
+            bit_depth: options.bit_depth,
+            overwrite: if options.force {
+                OverwritePolicy::Overwrite
+            } else {
+                OverwritePolicy::Skip
+            },
+            normalize_level: Some(options.level),
+            // 連動させる場合は最も大きいピークに合わせることになり、通常のノーマライズと同じ
+            normalize_per_channel: options.per_channel && !options.link,
+            limiter_ceiling: options.limiter_ceiling,
+            ..Default::default()
+        },
+    )
+}
+
+/// WAVを読み込み、ゲインを掛けて指定のビット深度とサンプル形式で書き出す。
+/// peaks がなければチャンネルごとのピークをここで測定する
+fn normalize_wav(
+    input: &Path,
+    output: &Path,
+    options: &NormalizeOptions,
+    peaks: Option<Vec<f32>>,
+) -> Result<(), AudioToolsError> {
+    let (bits_per_sample, sample_format) = options.wav_format()?;
+    if output.exists() && !options.force {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "{} already exists",
+            output.display()
+        )));
+    }
+
+    let peaks = match peaks {
+        Some(peaks) => peaks,
+        None => detect_channel_peak_levels(&input.to_path_buf())?,
+    };
+    let gains_db = if options.per_channel {
+        let gains: Vec<f32> = peaks.iter().map(|peak| options.level - peak).collect();
+        let linked = gains.iter().copied().fold(f32::INFINITY, f32::min);
+        for (c, (peak, gain)) in peaks.iter().zip(&gains).enumerate() {
+            info!(
+                "Channel {}: peak {:.1} dBFS, Target: {:.1} dBFS, Applying gain: {:.1} dB",
+                c + 1,
+                peak,
+                options.level,
+                if options.link { linked } else { *gain }
+            );
+        }
+        if options.link {
+            vec![linked; gains.len()]
+        } else {
+            gains
+        }
+    } else {
+        let current_peak = overall_peak_level(&peaks);
+        let gain_db = options.level - current_peak;
+        info!(
+            "Current peak: {:.1} dBFS, Target: {:.1} dBFS, Applying gain: {:.1} dB",
+            current_peak, options.level, gain_db
+        );
+        vec![gain_db]
+    };
+    let gains: Vec<f32> = gains_db.iter().map(|db| 10f32.powf(db / 20.0)).collect();
+
+    let mut reader = WavReader::open(input)?;
+    let spec = reader.spec();
+    let mut writer = WavWriter::create(
+        output,
+        WavSpec {
+            bits_per_sample,
+            sample_format,
+            ..spec
+        },
+    )?;
+    // 浮動小数点では ±1.0 を超えた値もそのまま残るため、クリップもディザも不要
+    let float = sample_format == SampleFormat::Float;
+
+    let scale = (1i64 << (bits_per_sample - 1)) as f32;
+    let mut dither = Tpdf::new();
+    let mut clipped = 0usize;
+    let mut index = 0usize;
+    let mut write = |sample: f32| -> Result<(), AudioToolsError> {
+        // インターリーブされたサンプルの順にチャンネルのゲインを選ぶ
+        let mut value = sample * gains[index % gains.len()];
+        index += 1;
+        if options.soft_clip {
+            value = soft_clip(value);
+        }
+        if float {
+            writer.write_sample(value)?;
+            return Ok(());
+        }
+        if !options.soft_clip && value.abs() > 1.0 {
+            clipped += 1;
+        }
+        let mut scaled = value * scale;
+        if options.dither {
+            scaled += dither.next();
+        }
+        writer.write_sample(scaled.round().clamp(-scale, scale - 1.0) as i32)?;
+        Ok(())
+    };
+    match spec.sample_format {
+        SampleFormat::Float => {
+            for sample in reader.samples::<f32>() {
+                write(sample?)?;
+            }
+        }
+        SampleFormat::Int => {
+            for sample in reader.samples::<i32>() {
+                write(normalize_sample(sample?, spec.bits_per_sample))?;
+            }
+        }
+    }
+    writer.finalize()?;
+
+    if clipped > 0 {
+        warn!(
+            "{} samples clipped in {} (use --soft-clip or --limiter to avoid hard clipping)",
+            clipped,
+            output.display()
+        );
+    }
+    debug!("Wrote {} without ffmpeg", output.display());
+    Ok(())
+}
+
+/// SOFT_CLIP_KNEE までは線形で、それより大きい振幅を tanh で ±1.0 未満に収める
+fn soft_clip(value: f32) -> f32 {
+    let magnitude = value.abs();
+    if magnitude <= SOFT_CLIP_KNEE {
+        return value;
+    }
+    let headroom = 1.0 - SOFT_CLIP_KNEE;
+    let shaped = SOFT_CLIP_KNEE + headroom * ((magnitude - SOFT_CLIP_KNEE) / headroom).tanh();
+    shaped.copysign(value)
+}
+
+/// 三角分布（±1 LSB）のディザを生成する。
+/// 同じ入力から同じ出力が得られるよう、固定シードの xorshift を使う
+struct Tpdf {
+    state: u32,
+}
+
+impl Tpdf {
+    fn new() -> Self {
+        Self { state: 0x9E37_79B9 }
+    }
+
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32
+    }
+
+    fn next(&mut self) -> f32 {
+        self.uniform() - self.uniform()
+    }
+}
+
+/// 入力をすべてノーマライズする。失敗したファイルがあれば Ok(false) を返す
 pub fn normalize_files(
-    input: &PathBuf,
+    inputs: &[PathBuf],
     output_dir: Option<&PathBuf>,
-    level: f32,
     input_format: &[String],
-    recursive: bool,
-    force: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
+    walk: &WalkOptions,
+    options: &NormalizeOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<bool, AudioToolsError> {
+    let level = options.level;
+    options.wav_format()?;
     // 入力フォーマットを小文字に変換
     let input_extensions: Vec<String> = input_format.iter().map(|f| f.to_lowercase()).collect();
 
+    if let Some(dir) = output_dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut summary = BatchSummary::for_walk(walk).with_progress(progress);
+
     // フォルダ内のファイルを走査
-    for entry in get_walker(input, recursive) {
+    for entry in get_walkers(inputs, walk) {
+        if summary.should_stop() {
+            break;
+        }
         if let Some(ext) = entry.path().extension() {
             let ext_str = ext.to_string_lossy().to_lowercase();
             if input_extensions.contains(&ext_str) {
-                // 各ファイルのピークレベルを検出
-                match detect_peak_level(&entry.path().to_path_buf()) {
-                    Ok(peak_dbfs) => {
-                        println!(
+                summary.started(entry.path());
+
+                // 各ファイルのピークレベルを検出（WAV同士の処理ではこの測定値を使う）
+                match detect_channel_peak_levels(&entry.path().to_path_buf()) {
+                    Ok(peaks) => {
+                        let peak_dbfs = overall_peak_level(&peaks);
+                        info!(
                             "Processing: {} (Peak level: {:.1} dBFS)",
                             entry.path().display(),
                             peak_dbfs
                         );
 
                         let gain = level - peak_dbfs;
-                        println!("Applying gain: {:.1} dB", gain);
+                        debug!("Applying gain: {:.1} dB", gain);
 
-                        // 変換処理の実行
-                        convert::convert_files(
-                            &entry.path().to_path_buf(),
-                            output_dir,
-                            false,
-                            &[ext_str],
-                            "wav",
-                            24,
-                            None,
-                            None,
-                            Some(&format!("_normalized_{}dB", level)),
-                            false,
-                            force,
-                            None,
-                            Some(level),
+                        let filename = format!(
+                            "{}_normalized_{}dB.wav",
+                            entry.path().file_stem().unwrap().to_string_lossy(),
+                            level
                         );
+                        let output = match output_dir {
+                            Some(dir) => dir.join(filename),
+                            None => entry.path().with_file_name(filename),
+                        };
+                        if output.exists() && !options.force {
+                            info!(
+                                "Skipped: {} (output file already exists. Use --force to overwrite)",
+                                output.display()
+                            );
+                            summary.skipped(entry.path());
+                            continue;
+                        }
+
+                        // 変換処理の実行
+                        match normalize_measured(entry.path(), &output, options, Some(peaks)) {
+                            Ok(()) => {
+                                summary.processed(entry.path());
+                                info!(
+                                    "Converted: {} -> {}",
+                                    entry.path().display(),
+                                    output.display()
+                                );
+                            }
+                            Err(e) => {
+                                summary.failed(entry.path());
+                                error!("Failed: {} ({})", entry.path().display(), e);
+                            }
+                        }
                     }
                     Err(e) => {
-                        println!("Error processing {}: {}", entry.path().display(), e);
-                        continue;
+                        error!("Error processing {}: {}", entry.path().display(), e);
+                        summary.failed(entry.path());
                     }
                 }
             }
         }
     }
 
-    Ok(())
+    summary.report("normalized");
+
+    Ok(!summary.has_failures())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 44.1kHz ステレオ 16 ビットのサイン波（ピーク -6 dBFS）を書く
+    fn write_source(path: &Path) -> WavSpec {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        for i in 0..4410 {
+            let t = i as f32 / 44100.0;
+            let sample = (2.0 * std::f32::consts::PI * 440.0 * t).sin() * 0.5;
+            let value = (sample * i16::MAX as f32) as i16;
+            writer.write_sample(value).unwrap();
+            writer.write_sample(value).unwrap();
+        }
+        writer.finalize().unwrap();
+        spec
+    }
+
+    #[test]
+    fn written_spec_round_trips() {
+        let dir = crate::utils::temp_dir_path();
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("source.wav");
+        let source = write_source(&input);
+
+        // (ビット深度, サンプル形式の指定, 書かれるべきビット数と形式)
+        let cases = [
+            (16, None, (16, SampleFormat::Int)),
+            (24, None, (24, SampleFormat::Int)),
+            (32, None, (32, SampleFormat::Float)),
+            (32, Some(WavSampleFormat::Int), (32, SampleFormat::Int)),
+        ];
+        let mut written = Vec::new();
+        for (i, (bit_depth, sample_format, _)) in cases.iter().enumerate() {
+            let output = dir.join(format!("out{}.wav", i));
+            let options = NormalizeOptions {
+                bit_depth: *bit_depth,
+                sample_format: *sample_format,
+                ..Default::default()
+            };
+            let result = normalize_file(&input, &output, &options);
+            written.push(result.and_then(|_| Ok(WavReader::open(&output)?.spec())));
+        }
+        let float_16 = normalize_file(
+            &input,
+            &dir.join("float16.wav"),
+            &NormalizeOptions {
+                bit_depth: 16,
+                sample_format: Some(WavSampleFormat::Float),
+                ..Default::default()
+            },
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        for ((_, _, (bits, format)), spec) in cases.iter().zip(written) {
+            let spec = spec.unwrap();
+            assert_eq!(spec.channels, source.channels);
+            assert_eq!(spec.sample_rate, source.sample_rate);
+            assert_eq!(spec.bits_per_sample, *bits);
+            assert_eq!(spec.sample_format, *format);
+        }
+        assert!(matches!(float_16, Err(AudioToolsError::InvalidInput(_))));
+    }
 }
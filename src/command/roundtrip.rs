@@ -0,0 +1,341 @@
+use super::convert::{convert_file, ConvertOptions, OverwritePolicy};
+use super::spectrum::{
+    create_spectrogram, stft, ExportUnits, FrequencyScale, MaxFrequency, SpectrogramOptions,
+    WindowFunction,
+};
+use crate::error::AudioToolsError;
+use crate::utils::plot::{ImageFormat, PlotColors};
+use crate::utils::progress::ProgressCallback;
+use crate::utils::summary::BatchSummary;
+use crate::utils::WalkOptions;
+use crate::utils::{format_size, get_walkers, is_audio_file, load_samples, temp_dir_path};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use log::{error, info};
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// エンコーダの遅延として探す最大のずれ（サンプル）
+const MAX_DELAY: usize = 8192;
+/// 遅延の検出に使う先頭部分の長さ（サンプル）
+const DELAY_SEARCH_SAMPLES: usize = 1 << 18;
+/// スペクトルの比較に使うFFTサイズ
+const COMPARE_WINDOW_SIZE: usize = 2048;
+/// 元の音の最大の帯域からこれ（dB）以上小さい帯域はスペクトルの比較から外す
+const COMPARE_FLOOR_DB: f32 = 90.0;
+
+/// roundtrip コマンドのパラメータ
+#[derive(Clone, Debug)]
+pub struct RoundtripOptions {
+    /// エンコードする形式（convert の --output-format と同じ）
+    pub format: String,
+    /// MP3のビットレート（未指定時は convert と同じ 320k）
+    pub bitrate: Option<String>,
+    /// 元の音との差分スペクトログラムを書き出す
+    pub diff_image: bool,
+    /// 差分スペクトログラムの出力先（未指定時は元のファイルの横）
+    pub output_dir: Option<PathBuf>,
+    pub colors: PlotColors,
+    pub font: String,
+}
+
+/// エンコードしてデコードし直した音と元の音の違い
+#[derive(Clone, Copy, Debug)]
+pub struct RoundtripReport {
+    /// エンコードしたファイルの大きさ（バイト）
+    pub encoded_bytes: u64,
+    /// デコードした音の遅れ（サンプル、負なら先行）
+    pub delay_samples: isize,
+    pub sample_rate: u32,
+    /// 位置を揃えた後の差のRMS（振幅）
+    pub rms_error: f32,
+    /// 元の音のRMSと差のRMSの比（dB）
+    pub snr_db: f32,
+    /// 平均スペクトルのレベル差が最も大きい周波数（Hz）とその差（dB）
+    pub max_deviation_hz: f32,
+    pub max_deviation_db: f32,
+}
+
+/// 1ファイルをエンコード・デコードし、元の音との違いを測る。
+/// diff_image を指定すると差分スペクトログラムも書き出す
+pub fn roundtrip_file(
+    input: &Path,
+    options: &RoundtripOptions,
+    diff_image: Option<&Path>,
+) -> Result<RoundtripReport, AudioToolsError> {
+    let dir = temp_dir_path();
+    fs::create_dir_all(&dir)?;
+    let result = roundtrip_in(input, options, diff_image, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+/// 一時ディレクトリ dir にエンコードしたファイルとデコードしたWAVを置いて比較する
+fn roundtrip_in(
+    input: &Path,
+    options: &RoundtripOptions,
+    diff_image: Option<&Path>,
+    dir: &Path,
+) -> Result<RoundtripReport, AudioToolsError> {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let encoded = dir.join(format!("{}.{}", stem, options.format.to_lowercase()));
+    let convert = ConvertOptions {
+        output_format: options.format.clone(),
+        bitrate: options.bitrate.clone(),
+        overwrite: OverwritePolicy::Overwrite,
+        ..ConvertOptions::default()
+    };
+    convert_file(input, &encoded, &convert)?;
+    let encoded_bytes = fs::metadata(&encoded)?.len();
+
+    let (original, sample_rate) = load_samples(input)?;
+    let (decoded, decoded_rate) = load_samples(&encoded)?;
+    if decoded_rate != sample_rate {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Decoded sample rate is {} Hz, but the source is {} Hz",
+            decoded_rate, sample_rate
+        )));
+    }
+
+    // エンコーダの遅延（先頭の無音やパディング）を取り除いて位置を揃える
+    let delay = find_delay(&original, &decoded);
+    let (original, decoded) = if delay >= 0 {
+        (
+            &original[..],
+            decoded.get(delay as usize..).unwrap_or_default(),
+        )
+    } else {
+        (
+            original.get(delay.unsigned_abs()..).unwrap_or_default(),
+            &decoded[..],
+        )
+    };
+    let len = original.len().min(decoded.len());
+    let (original, decoded) = (&original[..len], &decoded[..len]);
+    if len < COMPARE_WINDOW_SIZE {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "File too short to compare ({} samples after alignment)",
+            len
+        )));
+    }
+
+    let rms = |samples: &mut dyn Iterator<Item = f32>| {
+        (samples.map(|s| (s as f64).powi(2)).sum::<f64>() / len as f64).sqrt() as f32
+    };
+    let rms_error = rms(&mut original.iter().zip(decoded).map(|(a, b)| a - b));
+    let rms_original = rms(&mut original.iter().copied());
+    let snr_db = 20.0 * (rms_original / rms_error).log10();
+
+    let (max_deviation_hz, max_deviation_db) =
+        max_spectral_deviation(original, decoded, sample_rate as f32);
+
+    if let Some(image) = diff_image {
+        // 位置を揃えたデコード結果をWAVに戻し、元のファイルと比較する
+        let aligned = dir.join(format!("{}_decoded.wav", stem));
+        write_mono_wav(&aligned, decoded, sample_rate)?;
+        let mut spectrogram = diff_spectrogram_options(options);
+        spectrogram.compare = Some(input.to_path_buf());
+        create_spectrogram(&aligned, image, &spectrogram)?;
+    }
+
+    Ok(RoundtripReport {
+        encoded_bytes,
+        delay_samples: delay,
+        sample_rate,
+        rms_error,
+        snr_db,
+        max_deviation_hz,
+        max_deviation_db,
+    })
+}
+
+/// 相互相関が最大になるずれ（サンプル）を求める。正ならデコードした音が遅れている
+fn find_delay(original: &[f32], decoded: &[f32]) -> isize {
+    let len = original.len().min(decoded.len()).min(DELAY_SEARCH_SAMPLES);
+    // 循環相関が折り返さないよう、探す範囲の分だけゼロで埋める
+    let size = (len + MAX_DELAY).next_power_of_two();
+    let mut planner = FftPlanner::new();
+    let forward = planner.plan_fft_forward(size);
+    let inverse = planner.plan_fft_inverse(size);
+
+    let spectrum = |samples: &[f32]| {
+        let mut buffer: Vec<Complex<f32>> = samples[..len]
+            .iter()
+            .map(|&s| Complex::new(s, 0.0))
+            .collect();
+        buffer.resize(size, Complex::new(0.0, 0.0));
+        forward.process(&mut buffer);
+        buffer
+    };
+    let mut correlation: Vec<Complex<f32>> = spectrum(decoded)
+        .iter()
+        .zip(spectrum(original))
+        .map(|(d, o)| d * o.conj())
+        .collect();
+    inverse.process(&mut correlation);
+
+    // 添字 k はずれ k、size - k はずれ -k に当たる
+    let max_delay = MAX_DELAY.min(size / 2) as isize;
+    (-max_delay..=max_delay)
+        .max_by(|&a, &b| {
+            let value = |lag: isize| correlation[lag.rem_euclid(size as isize) as usize].re;
+            value(a).total_cmp(&value(b))
+        })
+        .unwrap_or(0)
+}
+
+/// 平均振幅スペクトルどうしを比べ、レベル差が最も大きい周波数と差（dB）を返す。
+/// 元の音でほとんど成分のない帯域は比べない
+fn max_spectral_deviation(original: &[f32], decoded: &[f32], sample_rate: f32) -> (f32, f32) {
+    let average = |samples: &[f32]| {
+        let frames = stft(
+            samples,
+            COMPARE_WINDOW_SIZE,
+            COMPARE_WINDOW_SIZE / 2,
+            WindowFunction::Hann,
+        );
+        let mut mean = vec![0.0f32; COMPARE_WINDOW_SIZE / 2];
+        for frame in &frames {
+            for (sum, &amplitude) in mean.iter_mut().zip(frame) {
+                *sum += amplitude / frames.len() as f32;
+            }
+        }
+        mean
+    };
+    let (original, decoded) = (average(original), average(decoded));
+
+    let to_db = |amplitude: f32| 20.0 * amplitude.max(1e-10).log10();
+    let loudest = original.iter().fold(0.0f32, |max, &a| max.max(a));
+    let floor = to_db(loudest) - COMPARE_FLOOR_DB;
+    let bin_hz = sample_rate / COMPARE_WINDOW_SIZE as f32;
+
+    original
+        .iter()
+        .zip(&decoded)
+        .enumerate()
+        .filter(|&(_, (&o, _))| to_db(o) > floor)
+        .map(|(bin, (&o, &d))| (bin as f32 * bin_hz, (to_db(d) - to_db(o)).abs()))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0.0, 0.0))
+}
+
+fn write_mono_wav(path: &Path, samples: &[f32], sample_rate: u32) -> Result<(), AudioToolsError> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let mut writer = WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// 差分スペクトログラムの設定（spectrum コマンドの既定値に合わせる）
+fn diff_spectrogram_options(options: &RoundtripOptions) -> SpectrogramOptions {
+    SpectrogramOptions {
+        window_size: 2048,
+        window_function: WindowFunction::Hann,
+        overlap: 0.75,
+        min_freq: 20.0,
+        max_freq: MaxFrequency::Nyquist,
+        freq_scale: FrequencyScale::Linear,
+        mel_bands: None,
+        average: false,
+        time_range: None,
+        auto_start: None,
+        annotations: None,
+        export: None,
+        export_units: ExportUnits::Db,
+        compare: None,
+        diff_range: 24.0,
+        gamma: 1.0,
+        normalize: false,
+        metadata: false,
+        colors: options.colors,
+        font: options.font.clone(),
+        no_image: false,
+        print_detection: false,
+        format: ImageFormat::Png,
+        width: 1200,
+        height: 600,
+        image_scale: 1.0,
+    }
+}
+
+/// 入力の音声ファイルをすべて往復させて結果を表示する。失敗したファイルがあれば false を返す
+pub fn roundtrip_files(
+    inputs: &[PathBuf],
+    options: &RoundtripOptions,
+    walk: &WalkOptions,
+    progress: Option<ProgressCallback>,
+) -> bool {
+    let mut summary = BatchSummary::for_walk(walk).with_progress(progress);
+    let codec = match &options.bitrate {
+        Some(bitrate) => format!("{} {}", options.format.to_uppercase(), bitrate),
+        None => options.format.to_uppercase(),
+    };
+
+    for entry in get_walkers(inputs, walk) {
+        if summary.should_stop() {
+            break;
+        }
+        let is_audio = entry
+            .path()
+            .extension()
+            .is_some_and(|ext| is_audio_file(&ext.to_string_lossy().to_lowercase()));
+        if !is_audio {
+            continue;
+        }
+        let source = entry.path();
+        let path = walk.display_path(source);
+        summary.started(&path);
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+        let image = options.diff_image.then(|| {
+            let name = format!("{}_roundtrip_{}.png", stem, options.format.to_lowercase());
+            match &options.output_dir {
+                Some(dir) => dir.join(name),
+                None => source.with_file_name(name),
+            }
+        });
+
+        match roundtrip_file(source, options, image.as_deref()) {
+            Ok(report) => {
+                summary.processed(&path);
+                println!(
+                    "{}: {}, {} encoded",
+                    path.display(),
+                    codec,
+                    format_size(report.encoded_bytes)
+                );
+                println!(
+                    "  Delay: {} samples ({:.1} ms)",
+                    report.delay_samples,
+                    report.delay_samples as f32 * 1000.0 / report.sample_rate as f32
+                );
+                println!(
+                    "  RMS error: {:.1} dBFS (SNR {:.1} dB)",
+                    20.0 * report.rms_error.max(1e-10).log10(),
+                    report.snr_db
+                );
+                println!(
+                    "  Max spectral deviation: {:.1} dB at {:.0} Hz",
+                    report.max_deviation_db, report.max_deviation_hz
+                );
+                if let Some(image) = &image {
+                    info!("Created difference spectrogram: {}", image.display());
+                }
+            }
+            Err(e) => {
+                summary.failed(&path);
+                error!("Error processing {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    summary.report("compared");
+    !summary.has_failures()
+}
@@ -1,17 +1,157 @@
-use crate::utils::get_walker;
-use hound::WavReader;
+use crate::error::AudioToolsError;
+use crate::utils::plot::{
+    metadata_path, scaled_size, validate_dimensions, validate_image_scale, ImageFormat,
+    ImageMetadata, ImageOutputOptions, PlotColors, PlottedSource, Theme,
+};
+use crate::utils::{get_walker, is_audio_file, load_samples, WalkOptions};
+use byteorder::{LittleEndian, WriteBytesExt};
+use clap::ValueEnum;
+use log::{error, info, warn};
+use plotters::coord::ranged1d::ValueFormatter;
+use plotters::coord::types::RangedCoordf32;
+use plotters::coord::Shift;
 use plotters::prelude::*;
-use plotters::style::RGBAColor;
 use rustfft::{num_complex::Complex, FftPlanner};
+use serde::Serialize;
 use std::f32::consts::PI;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use crate::utils::detection::AutoStartDetection;
-use crate::utils::time::{TimeRange, TimeSpecification};
+use crate::utils::time::{sample_range, TimeRange, TimeSpecification};
 
 // 定数定義
-const FONT_FAMILY: &str = "Fira Code";
-const BACKGROUND_COLOR: RGBColor = RGBColor(4, 20, 36);
+const MIN_DB: f32 = -128.0;
+const MAX_DB: f32 = 0.0;
+
+/// 周波数軸のスケール
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrequencyScale {
+    Linear,
+    Log,
+}
+
+/// FFTの窓関数
+///
+/// 矩形窓は周波数分解能が最も高いがリークが大きく、
+/// Blackman-Harris窓はメインローブが広い代わりにスペクトルリークを最小化する
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowFunction {
+    Hann,
+    Hamming,
+    Blackman,
+    Rectangular,
+    BlackmanHarris,
+}
+
+/// 数値データのエクスポート形式
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Npy,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Npy => "npy",
+        }
+    }
+}
+
+/// エクスポートする値の単位
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportUnits {
+    Db,
+    Linear,
+}
+
+/// 表示する最大周波数。ナイキスト周波数を超える値はナイキスト周波数に切り詰める
+#[derive(Clone, Copy, Debug)]
+pub enum MaxFrequency {
+    Hz(f32),
+    /// ファイルのナイキスト周波数（サンプリングレートの半分）
+    Nyquist,
+}
+
+/// スペクトログラム生成のパラメータ
+#[derive(Clone, Debug)]
+pub struct SpectrogramOptions {
+    pub window_size: usize,
+    pub window_function: WindowFunction,
+    pub overlap: f32,
+    pub min_freq: f32,
+    pub max_freq: MaxFrequency,
+    pub freq_scale: FrequencyScale,
+    /// メルフィルタ数（指定時はメルスペクトログラムを描画）
+    pub mel_bands: Option<usize>,
+    /// 時間方向に平均した振幅スペクトルを周波数対レベルの折れ線で描画する
+    pub average: bool,
+    pub time_range: Option<TimeRange>,
+    pub auto_start: Option<AutoStartDetection>,
+    pub annotations: Option<Vec<(f32, String)>>,
+    pub export: Option<ExportFormat>,
+    pub export_units: ExportUnits,
+    /// 比較するファイル（指定時は入力とのレベル差を描画）
+    pub compare: Option<PathBuf>,
+    /// 差分表示の色が飽和するレベル差（±dB）
+    pub diff_range: f32,
+    /// ヒートマップの明るさのガンマ（1未満で小さい成分が明るく、1より大きいと強い成分だけが目立つ）
+    pub gamma: f32,
+    /// 色の基準をフルスケールではなくファイル内の最大振幅にする（最大値が0dBになる）
+    pub normalize: bool,
+    /// 画像の横に解析設定を JSON で保存する
+    pub metadata: bool,
+    /// 背景・文字・アノテーションの色（ライトテーマではヒートマップも反転する）
+    pub colors: PlotColors,
+    /// タイトル・目盛り・アノテーションのフォント名
+    pub font: String,
+    /// 画像を書き出さず、測定値だけを表示する
+    pub no_image: bool,
+    /// --auto-start で検出した開始位置と描画範囲の終わり（秒）を標準出力に書く
+    pub print_detection: bool,
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// 寸法・文字・線幅・余白に掛ける倍率（印刷向けの高解像度出力）
+    pub image_scale: f32,
+}
+
+/// --metadata で画像の横に保存する解析設定
+#[derive(Debug, Serialize)]
+pub struct SpectrogramSettings {
+    pub window_size: usize,
+    pub window: WindowFunction,
+    pub overlap: f32,
+    pub hop_size: usize,
+    pub min_freq: f32,
+    /// ナイキスト周波数に切り詰めた後の値
+    pub max_freq: f32,
+    pub freq_scale: FrequencyScale,
+    pub mel_bands: Option<usize>,
+    pub average: bool,
+    /// 差分表示の色が飽和するレベル差（--compare のときだけ）
+    pub diff_range: Option<f32>,
+    pub gamma: f32,
+    pub normalize: bool,
+}
+
+pub type SpectrogramMetadata = ImageMetadata<SpectrogramSettings>;
+
+/// --max-freq の値を読み取る（"auto" はナイキスト周波数）
+pub fn parse_max_freq(s: &str) -> Result<MaxFrequency, String> {
+    if s.eq_ignore_ascii_case("auto") {
+        return Ok(MaxFrequency::Nyquist);
+    }
+    s.parse::<f32>()
+        .map(MaxFrequency::Hz)
+        .map_err(|_| format!("Invalid frequency '{}' (use a number in Hz or 'auto')", s))
+}
 
 pub fn parse_frequency_annotation(s: &str) -> Result<(f32, String), String> {
     let parts: Vec<&str> = s.split(':').collect();
@@ -28,93 +168,176 @@ pub fn parse_frequency_annotation(s: &str) -> Result<(f32, String), String> {
 
 pub fn create_spectrograms(
     input: &PathBuf,
-    window_size: usize,
-    overlap: f32,
-    min_freq: f32,
-    max_freq: f32,
-    time_range: Option<TimeRange>,
-    auto_start: Option<AutoStartDetection>,
-    recursive: bool,
-    annotations: Option<Vec<(f32, String)>>,
-) {
-    for entry in get_walker(input, recursive) {
+    options: &SpectrogramOptions,
+    walk: &WalkOptions,
+    naming: &ImageOutputOptions,
+) -> bool {
+    let mut ok = true;
+    for entry in get_walker(input, walk) {
         if let Some(ext) = entry.path().extension() {
-            if ext.to_string_lossy().to_lowercase() == "wav" {
+            if is_audio_file(&ext.to_string_lossy().to_lowercase()) {
                 let input_path = PathBuf::from(entry.path());
-                let output_path = input_path.with_extension("png");
-
-                match create_spectrogram(
-                    &input_path,
-                    &output_path,
-                    window_size,
-                    overlap,
-                    min_freq,
-                    max_freq,
-                    time_range.clone(),
-                    auto_start.clone(),
-                    annotations.clone(),
-                ) {
-                    Ok(_) => println!(
-                        "Created spectrogram: {} -> {}",
-                        input_path.display(),
+                let shown = walk.display_path(&input_path);
+                let output_path = naming.image_path(&input_path, input, options.format.extension());
+                // 画像と --export のデータがどちらもソースより新しければ作り直さない
+                let mut outputs = Vec::new();
+                if !options.no_image {
+                    outputs.push(output_path.clone());
+                }
+                if let Some(format) = options.export {
+                    outputs.push(output_path.with_extension(format.extension()));
+                }
+                if options.metadata && !options.no_image {
+                    outputs.push(metadata_path(&output_path));
+                }
+                if !outputs.is_empty()
+                    && outputs.iter().all(|output| {
+                        let mut sources = vec![input_path.as_path()];
+                        sources.extend(options.compare.as_deref());
+                        naming.is_up_to_date(output, &sources)
+                    })
+                {
+                    info!(
+                        "Skipped: {} (image is newer than the source. Use --force to regenerate)",
                         output_path.display()
-                    ),
-                    Err(e) => eprintln!("Error processing {}: {}", input_path.display(), e),
+                    );
+                    continue;
+                }
+                // --no-image でも --export のデータは出力先に書く
+                if !outputs.is_empty() {
+                    if let Some(dir) = output_path.parent() {
+                        if let Err(e) = fs::create_dir_all(dir) {
+                            error!("Error creating {}: {}", dir.display(), e);
+                            if walk.fail_fast {
+                                return false;
+                            }
+                            ok = false;
+                            continue;
+                        }
+                    }
+                }
+
+                match create_spectrogram(&input_path, &output_path, options) {
+                    Ok(metadata) => {
+                        if options.print_detection && options.auto_start.is_some() {
+                            metadata.print_range(&shown);
+                        }
+                        if options.no_image {
+                            info!("Analyzed: {} (no image written)", shown.display());
+                        } else {
+                            info!(
+                                "Created spectrogram: {} -> {}",
+                                shown.display(),
+                                output_path.display()
+                            );
+                        }
+                        if let Some(format) = options.export {
+                            info!(
+                                "Exported spectrogram data: {}",
+                                output_path.with_extension(format.extension()).display()
+                            );
+                        }
+                        if options.metadata && !options.no_image {
+                            metadata.save(&output_path, walk);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error processing {}: {}", shown.display(), e);
+                        if walk.fail_fast {
+                            return false;
+                        }
+                        ok = false;
+                    }
                 }
             }
         }
     }
+    ok
 }
 
 pub fn create_spectrogram(
-    input: &PathBuf,
-    output: &PathBuf,
-    window_size: usize,
-    overlap: f32,
-    min_freq: f32,
-    max_freq: f32,
-    time_range: Option<TimeRange>,
-    auto_start: Option<AutoStartDetection>,
-    annotations: Option<Vec<(f32, String)>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut reader = WavReader::open(input)?;
-    let spec = reader.spec();
-    let sample_rate = spec.sample_rate as f32;
-
-    // サンプルデータ取得
-    let samples: Vec<f32> = match spec.sample_format {
-        hound::SampleFormat::Float => reader
-            .samples::<f32>()
-            .map(|s| s.unwrap())
-            .collect::<Vec<f32>>()
-            .chunks(spec.channels as usize)
-            .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
-            .collect(),
-        hound::SampleFormat::Int => {
-            let bits = spec.bits_per_sample;
-            let max_value = (1 << (bits - 1)) as f32;
-            reader
-                .samples::<i32>()
-                .map(|s| s.unwrap() as f32 / max_value)
-                .collect::<Vec<f32>>()
-                .chunks(spec.channels as usize)
-                .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
-                .collect()
+    input: &Path,
+    output: &Path,
+    options: &SpectrogramOptions,
+) -> Result<SpectrogramMetadata, AudioToolsError> {
+    let window_size = options.window_size;
+    let min_freq = options.min_freq;
+
+    validate_dimensions(options.width, options.height)?;
+    validate_image_scale(options.image_scale)?;
+    if !(0.0..1.0).contains(&options.overlap) {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Overlap must be at least 0.0 and less than 1.0 (got {})",
+            options.overlap
+        )));
+    }
+    if options.compare.is_some() && options.diff_range <= 0.0 {
+        return Err(AudioToolsError::InvalidInput(
+            "Difference range must be greater than 0 dB".to_string(),
+        ));
+    }
+    if !(options.gamma > 0.0 && options.gamma.is_finite()) {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Gamma must be greater than 0 (got {})",
+            options.gamma
+        )));
+    }
+    if options.mel_bands == Some(0) {
+        return Err(AudioToolsError::InvalidInput(
+            "Number of mel bands must be at least 1".to_string(),
+        ));
+    }
+    if let FrequencyScale::Log = options.freq_scale {
+        if min_freq <= 0.0 {
+            return Err(AudioToolsError::InvalidInput(
+                "Minimum frequency must be positive for log frequency scale".to_string(),
+            ));
+        }
+    }
+
+    // サンプルデータ取得（モノラルにミックス）
+    let (samples, sample_rate) = load_samples(input)?;
+    let sample_rate = sample_rate as f32;
+    if samples.is_empty() {
+        return Err(AudioToolsError::InvalidInput(
+            "File contains no samples".to_string(),
+        ));
+    }
+
+    // ナイキスト周波数より上には何もないため、軸と目盛りはそこまでにする
+    let nyquist = sample_rate / 2.0;
+    let max_freq = match options.max_freq {
+        MaxFrequency::Hz(max_freq) if max_freq > nyquist => {
+            warn!(
+                "Maximum frequency {} Hz is above the Nyquist frequency of {} Hz in {}; using the Nyquist frequency",
+                max_freq,
+                nyquist,
+                input.display()
+            );
+            nyquist
         }
+        MaxFrequency::Hz(max_freq) => max_freq,
+        MaxFrequency::Nyquist => nyquist,
     };
+    if min_freq >= max_freq {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Minimum frequency ({} Hz) must be less than maximum frequency ({} Hz)",
+            min_freq, max_freq
+        )));
+    }
 
     let total_duration = samples.len() as f32 / sample_rate;
 
     // 自動開始点検出
-    let (start_time, end_time) = if let Some(auto_config) = auto_start {
+    let (start_time, end_time) = if let Some(auto_config) = &options.auto_start {
         let detected_start = auto_config
             .detect_start_time(&samples, sample_rate)
-            .ok_or("Failed to detect start time")?;
+            .ok_or_else(|| AudioToolsError::TimeRange("Failed to detect start time".to_string()))?;
 
-        let end_time = if let Some(range) = time_range {
+        let end_time = if let Some(range) = &options.time_range {
             TimeRange {
                 start: TimeSpecification::Seconds(detected_start),
-                end: range.end,
+                end: range.end.clone(),
             }
             .resolve(total_duration)
             .map_or(total_duration, |(_, end)| end)
@@ -123,158 +346,688 @@ pub fn create_spectrogram(
         };
 
         (detected_start, end_time)
-    } else if let Some(range) = time_range {
+    } else if let Some(range) = &options.time_range {
         range.resolve(total_duration)?
     } else {
         (0.0, total_duration)
     };
 
     // サンプル範囲の計算
-    let start_sample = (start_time * sample_rate) as usize;
-    let end_sample = (end_time * sample_rate) as usize;
-    let samples = samples[start_sample..end_sample].to_vec();
+    let range = sample_range(start_time, end_time, sample_rate, samples.len())?;
+    let mut samples = samples[range.clone()].to_vec();
+
+    // 比較対象も同じ範囲を切り出し、短い方に長さを揃える
+    let other = match &options.compare {
+        Some(path) => {
+            let (other, other_rate) = load_samples(path)?;
+            if other_rate as f32 != sample_rate {
+                return Err(AudioToolsError::InvalidInput(format!(
+                    "Sample rates differ: {} Hz vs {} Hz ({})",
+                    sample_rate,
+                    other_rate,
+                    path.display()
+                )));
+            }
+            let other = other
+                .get(range.start..range.end.min(other.len()))
+                .unwrap_or_default()
+                .to_vec();
+            samples.truncate(other.len());
+            Some(other)
+        }
+        None => None,
+    };
+    if samples.len() < window_size {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "File too short for window size {} ({} samples in range)",
+            window_size,
+            samples.len()
+        )));
+    }
 
     // FFT処理
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(window_size);
-    let hop_size = (window_size as f32 * (1.0 - overlap)) as usize;
+    let hop_size = hop_size(window_size, options.overlap);
 
-    // ハニング窓
-    let window: Vec<f32> = (0..window_size)
-        .map(|i| 0.5 * (1.0 - (2.0 * PI * i as f32 / window_size as f32).cos()))
-        .collect();
+    let plotted = |path: &Path| PlottedSource {
+        path: path.to_path_buf(),
+        sample_rate: sample_rate as u32,
+        start: start_time,
+        end: end_time,
+    };
+    let mut sources = vec![plotted(input)];
+    sources.extend(options.compare.as_deref().map(plotted));
+    let metadata = ImageMetadata::new(
+        sources,
+        SpectrogramSettings {
+            window_size,
+            window: options.window_function,
+            overlap: options.overlap,
+            hop_size,
+            min_freq,
+            max_freq,
+            freq_scale: options.freq_scale,
+            mel_bands: options.mel_bands,
+            average: options.average,
+            diff_range: other.as_ref().map(|_| options.diff_range),
+            gamma: options.gamma,
+            normalize: options.normalize,
+        },
+    );
 
-    // スペクトログラム計算
-    let mut spectrogram = Vec::new();
-    let mut i = 0;
-    while i + window_size <= samples.len() {
-        let mut buffer: Vec<Complex<f32>> = samples[i..i + window_size]
+    // 周波数ビンの計算を適切に行う
+    let freq_resolution = sample_rate / window_size as f32;
+
+    // スペクトログラム計算（振幅スペクトル）
+    let spectrogram = stft(&samples, window_size, hop_size, options.window_function);
+    if spectrogram.is_empty() {
+        return Err(AudioToolsError::InvalidInput(
+            "No spectrogram frames could be computed".to_string(),
+        ));
+    }
+
+    // 表示する各行（周波数帯域）の振幅・中心周波数・縦方向の範囲を求める
+    let (mut rows, centers, bands) =
+        spectrogram_rows(&spectrogram, options, freq_resolution, max_freq);
+
+    let mut other_rows = other.as_ref().map(|other| {
+        let other_spectrogram = stft(other, window_size, hop_size, options.window_function);
+        spectrogram_rows(&other_spectrogram, options, freq_resolution, max_freq).0
+    });
+
+    // 平均スペクトルは全フレームの振幅の平均を1行にしたもの（比較では平均どうしの差になる）
+    if options.average {
+        rows = vec![average_rows(&rows)];
+        other_rows = other_rows.map(|other_rows| vec![average_rows(&other_rows)]);
+    }
+
+    // 比較モードでは各セルのレベル差（dB）を描画・出力する
+    if let Some(other_rows) = other_rows {
+        rows = rows
             .iter()
-            .zip(window.iter())
-            .map(|(&s, &w)| Complex::new(s * w, 0.0))
+            .zip(&other_rows)
+            .map(|(row, other_row)| {
+                row.iter()
+                    .zip(other_row)
+                    .map(|(&a, &b)| amplitude_to_db(a) - amplitude_to_db(b))
+                    .collect()
+            })
             .collect();
+    }
 
-        fft.process(&mut buffer);
+    let total_time = samples.len() as f32 / sample_rate;
+    let time_per_frame = total_time / spectrogram.len() as f32;
+    // 時間軸はファイル上の時刻で表す（--start や自動検出した開始点から始まる）
+    let timeline = start_time..start_time + total_time;
 
-        // 周波数ビンの計算を適切に行う
-        let freq_resolution = sample_rate / window_size as f32;
-        let spectrum: Vec<f32> = buffer[..window_size / 2]
+    // 数値データのエクスポート
+    if let Some(format) = options.export {
+        let export_path = output.with_extension(format.extension());
+        // 差分は常にdB
+        let data: Vec<Vec<f32>> = match options.export_units {
+            _ if other.is_some() => rows.clone(),
+            ExportUnits::Db => rows
+                .iter()
+                .map(|row| row.iter().map(|&a| amplitude_to_db(a)).collect())
+                .collect(),
+            ExportUnits::Linear => rows.clone(),
+        };
+        match format {
+            ExportFormat::Csv => {
+                let hop_seconds = hop_size as f32 / sample_rate;
+                export_csv(&export_path, &data, &centers, start_time, hop_seconds)?
+            }
+            ExportFormat::Npy => export_npy(&export_path, &data)?,
+        }
+    }
+
+    let file_name = |path: &Path| -> String {
+        path.file_name().map_or("Spectrogram".to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        })
+    };
+    let title = match &options.compare {
+        Some(path) => format!("{} - {}", file_name(input), file_name(path)),
+        None => file_name(input),
+    };
+
+    if options.no_image {
+        if options.auto_start.is_some() {
+            println!("{}: detected start {:.3}s", title, start_time);
+        }
+        if other.is_some() {
+            let cells = rows.iter().flatten().count().max(1);
+            let mean = rows.iter().flatten().map(|d| d.abs()).sum::<f32>() / cells as f32;
+            let max = rows
+                .iter()
+                .flatten()
+                .fold(0.0f32, |max, d| max.max(d.abs()));
+            println!(
+                "{}: {:.3}s - {:.3}s, {} frames, mean difference {:.1} dB, max difference {:.1} dB",
+                title,
+                start_time,
+                end_time,
+                spectrogram.len(),
+                mean,
+                max
+            );
+            return Ok(metadata);
+        }
+        match summarize_frames(&spectrogram, freq_resolution) {
+            Some(summary) => println!(
+                "{}: {:.3}s - {:.3}s, {} frames, centroid {:.1} Hz, rolloff (85%) {:.1} Hz",
+                title,
+                start_time,
+                end_time,
+                spectrogram.len(),
+                summary.centroid_hz,
+                summary.rolloff_hz
+            ),
+            None => println!(
+                "{}: {:.3}s - {:.3}s, {} frames, silent",
+                title,
+                start_time,
+                end_time,
+                spectrogram.len()
+            ),
+        }
+        return Ok(metadata);
+    }
+
+    let plot = SpectrogramPlot {
+        title: &title,
+        rows: &rows,
+        bands: &bands,
+        centers: &centers,
+        time_per_frame,
+        timeline,
+        min_freq,
+        max_freq,
+        mel: options.mel_bands.is_some(),
+        difference_range: other.as_ref().map(|_| options.diff_range),
+        gamma: options.gamma,
+        reference: level_reference(&rows, options.normalize && other.is_none()),
+        annotations: options.annotations.as_deref(),
+        colors: &options.colors,
+        font: &options.font,
+        scale: options.image_scale,
+    };
+
+    // プロット作成
+    let size = scaled_size(options.width, options.height, options.image_scale);
+    match options.format {
+        ImageFormat::Png => {
+            let root = BitMapBackend::new(output, size).into_drawing_area();
+            render(&root, &plot, options)?;
+            root.present()?;
+        }
+        ImageFormat::Svg => {
+            let root = SVGBackend::new(output, size).into_drawing_area();
+            render(&root, &plot, options)?;
+            root.present()?;
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// --average の有無に応じてスペクトログラムか平均スペクトルを描画する
+fn render<DB>(
+    root: &DrawingArea<DB, Shift>,
+    plot: &SpectrogramPlot,
+    options: &SpectrogramOptions,
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    if options.average {
+        render_average_spectrum(root, plot, options.freq_scale)
+    } else {
+        render_spectrogram(root, plot, options.freq_scale)
+    }
+}
+
+/// フレームごとの行を帯域ごとに平均して1行にする
+fn average_rows(rows: &[Vec<f32>]) -> Vec<f32> {
+    let bands = rows.first().map_or(0, Vec::len);
+    (0..bands)
+        .map(|band| rows.iter().map(|row| row[band]).sum::<f32>() / rows.len() as f32)
+        .collect()
+}
+
+/// 振幅をヒートマップの色にする（下限以下は描画しない）。
+/// 0〜1に正規化したレベルを gamma 乗してから色にする
+fn level_color(amplitude: f32, theme: Theme, gamma: f32) -> Option<RGBAColor> {
+    let power = amplitude_to_db(amplitude);
+    let normalized_power = ((power - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0);
+    if normalized_power <= 0.0 {
+        return None;
+    }
+    let normalized_power = normalized_power.powf(gamma);
+    let green = (normalized_power * 255.0) as u8;
+    let blue = (normalized_power * normalized_power * 255.0) as u8;
+    // 明るい背景では補色（シアン→黒）にして強い成分ほど濃く見せる
+    let color = match theme {
+        Theme::Dark => RGBColor(255, green, blue),
+        Theme::Light => RGBColor(0, 255 - green, 255 - blue),
+    };
+    Some(color.mix(normalized_power as f64))
+}
+
+/// レベル表示で0dBとする振幅を求める。normalize 時は全フレームの最大振幅
+/// （無音なら最大値がないのでフルスケールのまま）
+fn level_reference(rows: &[Vec<f32>], normalize: bool) -> f32 {
+    let peak = rows.iter().flatten().fold(0.0f32, |max, &a| max.max(a));
+    if normalize && peak > 0.0 {
+        peak
+    } else {
+        1.0
+    }
+}
+
+/// レベル差を0dBを中心とする発散カラーマップの色にする。
+/// 入力の方が大きい成分は赤、比較対象の方が大きい成分は青で、差がなければ背景のまま
+fn difference_color(diff_db: f32, range: f32) -> Option<RGBAColor> {
+    let strength = (diff_db.abs() / range).min(1.0);
+    if strength <= 0.0 {
+        return None;
+    }
+    let color = if diff_db > 0.0 {
+        RGBColor(230, 60, 40)
+    } else {
+        RGBColor(40, 120, 230)
+    };
+    Some(color.mix(strength as f64))
+}
+
+/// 振幅スペクトルのフレームを表示する行（周波数帯域）の振幅に変換し、
+/// 各行の中心周波数と縦方向の範囲とあわせて返す（max_freq はナイキスト以下に切り詰めた値）
+fn spectrogram_rows(
+    spectrogram: &[Vec<f32>],
+    options: &SpectrogramOptions,
+    freq_resolution: f32,
+    max_freq: f32,
+) -> (Vec<Vec<f32>>, Vec<f32>, Vec<Band>) {
+    let (window_size, min_freq) = (options.window_size, options.min_freq);
+    if let Some(n_mels) = options.mel_bands {
+        let filterbank =
+            mel_filterbank(n_mels, window_size / 2, freq_resolution, min_freq, max_freq);
+        let rows: Vec<Vec<f32>> = spectrogram
+            .iter()
+            .map(|spectrum| {
+                filterbank
+                    .iter()
+                    .map(|weights| {
+                        let energy: f32 = weights
+                            .iter()
+                            .zip(spectrum.iter())
+                            .map(|(&w, &amplitude)| w * amplitude * amplitude)
+                            .sum();
+                        energy.sqrt()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // 各メルバンドは中心から隣接バンドとの中間点までを占める
+        let min_mel = hz_to_mel(min_freq);
+        let mel_step = (hz_to_mel(max_freq) - min_mel) / (n_mels + 1) as f32;
+        let mel_centers: Vec<f32> = (0..n_mels)
+            .map(|m| min_mel + (m + 1) as f32 * mel_step)
+            .collect();
+        let centers = mel_centers.iter().map(|&mel| mel_to_hz(mel)).collect();
+        let bands = mel_centers
             .iter()
-            .enumerate()
-            .map(|(bin, c)| {
-                let amplitude = c.norm() / window_size as f32;
+            .map(|&center| (center - mel_step / 2.0, center + mel_step / 2.0))
+            .collect();
+        (rows, centers, bands)
+    } else {
+        let visible_bins: Vec<usize> = (0..window_size / 2)
+            .filter(|&bin| {
                 let freq = bin as f32 * freq_resolution;
-                if freq >= min_freq && freq <= max_freq {
-                    20.0 * amplitude.log10()
-                } else {
-                    -128.0 // 表示範囲外の周波数は最小値に設定
-                }
+                freq >= min_freq && freq <= max_freq
+            })
+            .collect();
+        let rows = spectrogram
+            .iter()
+            .map(|spectrum| visible_bins.iter().map(|&bin| spectrum[bin]).collect())
+            .collect();
+        let centers = visible_bins
+            .iter()
+            .map(|&bin| bin as f32 * freq_resolution)
+            .collect();
+        let bands = visible_bins
+            .iter()
+            .map(|&bin| {
+                let freq = bin as f32 * freq_resolution;
+                (
+                    (freq - freq_resolution / 2.0).max(min_freq),
+                    (freq + freq_resolution / 2.0).min(max_freq),
+                )
             })
             .collect();
+        (rows, centers, bands)
+    }
+}
 
-        spectrogram.push(spectrum);
-        i += hop_size;
+/// 描画バックエンドに依存せずスペクトログラムのグラフを構築する
+fn render_spectrogram<DB>(
+    root: &DrawingArea<DB, Shift>,
+    plot: &SpectrogramPlot,
+    freq_scale: FrequencyScale,
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&plot.colors.background)?;
+
+    let (timeline, min_freq, max_freq) = (plot.timeline.clone(), plot.min_freq, plot.max_freq);
+    let scale = plot.scale;
+
+    // グラフ設定（目盛りは周波数スケールに合わせて生成）
+    let mut builder = ChartBuilder::on(root);
+    builder
+        .margin(40.0 * scale)
+        .caption(
+            plot.title,
+            (plot.font, 24.0 * scale)
+                .into_font()
+                .color(&plot.colors.foreground),
+        )
+        .set_label_area_size(LabelAreaPosition::Left, 60.0 * scale)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40.0 * scale);
+
+    match freq_scale {
+        _ if plot.mel => {
+            // メル軸はメル値で線形に配置し、ラベルはHzで表示する
+            let mut chart =
+                builder.build_cartesian_2d(timeline, hz_to_mel(min_freq)..hz_to_mel(max_freq))?;
+            draw_spectrogram(&mut chart, plot)?;
+        }
+        FrequencyScale::Linear => {
+            // 線形軸は plotters の等間隔目盛りをそのまま使う
+            let mut chart = builder.build_cartesian_2d(timeline, min_freq..max_freq)?;
+            draw_spectrogram(&mut chart, plot)?;
+        }
+        FrequencyScale::Log => {
+            let mut chart = builder.build_cartesian_2d(
+                timeline,
+                (min_freq..max_freq)
+                    .log_scale()
+                    .with_key_points(log_ticks(min_freq, max_freq)),
+            )?;
+            draw_spectrogram(&mut chart, plot)?;
+        }
     }
 
-    // プロット作成
-    let root = BitMapBackend::new(output.to_str().unwrap(), (1200, 600)).into_drawing_area();
-    root.fill(&BACKGROUND_COLOR)?;
+    Ok(())
+}
+
+/// 平均スペクトルを周波数（横軸）対レベル（縦軸, dB）の折れ線で描画する
+fn render_average_spectrum<DB>(
+    root: &DrawingArea<DB, Shift>,
+    plot: &SpectrogramPlot,
+    freq_scale: FrequencyScale,
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&plot.colors.background)?;
+
+    let row = plot.rows.first().map_or(&[][..], Vec::as_slice);
+    // 比較ではレベル差をそのまま、それ以外は基準振幅に対するレベルを描く
+    let (levels, y_range): (Vec<f32>, Range<f32>) = match plot.difference_range {
+        Some(range) => (row.to_vec(), -range..range),
+        None => {
+            let levels: Vec<f32> = row
+                .iter()
+                .map(|&amplitude| amplitude_to_db(amplitude / plot.reference))
+                .collect();
+            // 下限は最も小さい成分に合わせて10dB単位で切り下げる
+            let lowest = levels.iter().copied().fold(0.0f32, f32::min);
+            (levels, ((lowest / 10.0).floor() * 10.0).min(-10.0)..0.0)
+        }
+    };
+    let points: Vec<(f32, f32)> = plot
+        .centers
+        .iter()
+        .zip(&levels)
+        .map(|(&freq, &level)| (plot.to_axis(freq), level.clamp(y_range.start, y_range.end)))
+        .collect();
 
-    let min_db = -128.0;
-    let max_db = 0.0;
+    let (min_freq, max_freq, scale) = (plot.min_freq, plot.max_freq, plot.scale);
+    let mut builder = ChartBuilder::on(root);
+    builder
+        .margin(40.0 * scale)
+        .caption(
+            plot.title,
+            (plot.font, 24.0 * scale)
+                .into_font()
+                .color(&plot.colors.foreground),
+        )
+        .set_label_area_size(LabelAreaPosition::Left, 60.0 * scale)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40.0 * scale);
 
-    let title = input
-        .file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or("Spectrogram");
+    match freq_scale {
+        _ if plot.mel => {
+            let mut chart =
+                builder.build_cartesian_2d(hz_to_mel(min_freq)..hz_to_mel(max_freq), y_range)?;
+            draw_average_spectrum(&mut chart, plot, &points)?;
+        }
+        FrequencyScale::Linear => {
+            let mut chart = builder.build_cartesian_2d(min_freq..max_freq, y_range)?;
+            draw_average_spectrum(&mut chart, plot, &points)?;
+        }
+        FrequencyScale::Log => {
+            let mut chart = builder.build_cartesian_2d(
+                (min_freq..max_freq)
+                    .log_scale()
+                    .with_key_points(log_ticks(min_freq, max_freq)),
+                y_range,
+            )?;
+            draw_average_spectrum(&mut chart, plot, &points)?;
+        }
+    }
 
-    let total_time = samples.len() as f32 / sample_rate;
-    let total_frames = spectrogram.len();
-    let time_per_frame = total_time / total_frames as f32;
-
-    // 対数スケールの目盛り位置計算
-    let log_ticks: Vec<f32> = {
-        let mut ticks = Vec::new();
-        let mut freq = 10.0_f32;
-        while freq <= max_freq {
-            if freq >= min_freq {
-                ticks.push(freq);
-                if freq * 2.0 <= max_freq && freq * 2.0 >= min_freq {
-                    ticks.push(freq * 2.0);
-                }
-                if freq * 5.0 <= max_freq && freq * 5.0 >= min_freq {
-                    ticks.push(freq * 5.0);
-                }
+    Ok(())
+}
+
+/// 周波数軸の種類に依存せず平均スペクトルの折れ線とアノテーションを描画する
+fn draw_average_spectrum<DB, X>(
+    chart: &mut ChartContext<DB, Cartesian2d<X, RangedCoordf32>>,
+    plot: &SpectrogramPlot,
+    points: &[(f32, f32)],
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+    X: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    let scale = plot.scale;
+    let line_width = scale.round() as u32;
+    let (y_min, y_max) = {
+        let range = chart.y_range();
+        (range.start, range.end)
+    };
+
+    chart
+        .configure_mesh()
+        .label_style(
+            (plot.font, 14.0 * scale)
+                .into_font()
+                .color(&plot.colors.foreground),
+        )
+        .light_line_style(plot.colors.foreground.mix(0.05).stroke_width(line_width))
+        .bold_line_style(plot.colors.grid.stroke_width(line_width))
+        .axis_style(plot.colors.foreground.mix(0.5).stroke_width(line_width))
+        .x_desc(if plot.mel {
+            "Frequency (Hz, mel scale)"
+        } else {
+            "Frequency (Hz)"
+        })
+        .x_labels(10)
+        .x_label_formatter(&|x| format!("{:.0}", plot.to_hz(*x)))
+        .y_desc(if plot.difference_range.is_some() {
+            "Level difference (dB)"
+        } else {
+            "Level (dB)"
+        })
+        .y_labels(10)
+        .y_label_formatter(&|y| format!("{:.0}", y))
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(
+        points.iter().copied(),
+        plot.colors.wave.stroke_width(line_width * 2),
+    ))?;
+
+    // アノテーションは周波数の位置に縦線で描く
+    if let Some(annotations) = plot.annotations {
+        for (freq, label) in annotations.iter() {
+            if *freq >= plot.min_freq && *freq <= plot.max_freq {
+                let x = plot.to_axis(*freq);
+                chart.draw_series(LineSeries::new(
+                    vec![(x, y_min), (x, y_max)],
+                    plot.colors.annotation.stroke_width(line_width),
+                ))?;
+                chart.draw_series(std::iter::once(Text::new(
+                    label.to_string(),
+                    (x, y_max - (y_max - y_min) * 0.05),
+                    (plot.font, 16.0 * scale)
+                        .into_font()
+                        .color(&plot.colors.annotation),
+                )))?;
             }
-            freq *= 10.0;
         }
-        ticks.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        ticks
-    };
+    }
 
-    // グラフ設定
-    let mut chart = ChartBuilder::on(&root)
-        .margin(40)
-        .caption(title, (FONT_FAMILY, 24).into_font().color(&WHITE))
-        .set_label_area_size(LabelAreaPosition::Left, 60)
-        .set_label_area_size(LabelAreaPosition::Bottom, 40)
-        .build_cartesian_2d(0.0..total_time, min_freq..max_freq)?;
+    Ok(())
+}
+
+/// 縦軸上の帯域の範囲（下端, 上端）
+type Band = (f32, f32);
+
+/// 描画に必要なスペクトログラムデータ
+struct SpectrogramPlot<'a> {
+    title: &'a str,
+    /// フレームごとの各行の振幅（差分表示ではレベル差）
+    rows: &'a [Vec<f32>],
+    /// 各行が占める縦軸上の範囲（軸の単位）
+    bands: &'a [Band],
+    /// 各行の中心周波数（Hz）
+    centers: &'a [f32],
+    time_per_frame: f32,
+    /// 描画する区間のファイル上の時刻（秒）
+    timeline: Range<f32>,
+    min_freq: f32,
+    max_freq: f32,
+    mel: bool,
+    /// 差分表示のときの色が飽和するレベル差（±dB）。rows はdB差になる
+    difference_range: Option<f32>,
+    /// レベル表示の色のガンマ（差分表示では使わない）
+    gamma: f32,
+    /// 0dBとして描画する振幅（通常はフルスケールの1.0）
+    reference: f32,
+    annotations: Option<&'a [(f32, String)]>,
+    colors: &'a PlotColors,
+    font: &'a str,
+    /// 余白・文字・線幅の倍率（--image-scale）
+    scale: f32,
+}
+
+impl SpectrogramPlot<'_> {
+    /// 周波数（Hz）を縦軸の値に変換する
+    fn to_axis(&self, freq: f32) -> f32 {
+        if self.mel {
+            hz_to_mel(freq)
+        } else {
+            freq
+        }
+    }
+
+    /// 縦軸の値を周波数（Hz）に変換する
+    fn to_hz(&self, value: f32) -> f32 {
+        if self.mel {
+            mel_to_hz(value)
+        } else {
+            value
+        }
+    }
+}
+
+/// 周波数軸の種類に依存せずスペクトログラムを描画する
+fn draw_spectrogram<DB, Y>(
+    chart: &mut ChartContext<DB, Cartesian2d<RangedCoordf32, Y>>,
+    plot: &SpectrogramPlot,
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+    Y: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    let scale = plot.scale;
+    let line_width = scale.round() as u32;
+    // 描画幅に応じて時間軸のラベル数を調整する（倍率を掛けた画像でも同じ数にする）
+    let x_labels =
+        (chart.plotting_area().dim_in_pixel().0 as f32 / (55.0 * scale)).max(2.0) as usize;
 
     chart
         .configure_mesh()
-        .label_style((FONT_FAMILY, 14).into_font().color(&WHITE))
-        .light_line_style(RGBAColor(255, 255, 255, 0.05))
-        .bold_line_style(RGBAColor(255, 255, 255, 0.05))
-        .axis_style(RGBAColor(255, 255, 255, 0.5))
-        .x_labels(20)
+        .label_style(
+            (plot.font, 14.0 * scale)
+                .into_font()
+                .color(&plot.colors.foreground),
+        )
+        .light_line_style(plot.colors.foreground.mix(0.05).stroke_width(line_width))
+        .bold_line_style(plot.colors.foreground.mix(0.05).stroke_width(line_width))
+        .axis_style(plot.colors.foreground.mix(0.5).stroke_width(line_width))
+        .x_labels(x_labels)
         .x_label_formatter(&|x| format!("{:.1}", x))
-        .y_desc("Frequency (Hz)")
+        .y_desc(if plot.mel {
+            "Frequency (Hz, mel scale)"
+        } else {
+            "Frequency (Hz)"
+        })
         .x_desc("Time (s)")
-        .y_labels(log_ticks.len())
-        .y_label_formatter(&|y| format!("{:.0}", y))
+        .y_labels(10)
+        .y_label_formatter(&|y| format!("{:.0}", plot.to_hz(*y)))
         .draw()?;
 
-    // スペクトログラムデータの描画
-    let nyquist_freq = sample_rate / 2.0;
-    let freq_bins = window_size / 2;
-
-    for (frame, spectrum) in spectrogram.iter().enumerate() {
-        let time = frame as f32 * time_per_frame;
-
-        for (bin, &power) in spectrum.iter().enumerate() {
-            let freq = (bin as f32 * nyquist_freq) / freq_bins as f32;
-
-            if freq >= min_freq && freq <= max_freq {
-                let normalized_power = ((power - min_db) / (max_db - min_db)).max(0.0).min(1.0);
-                if normalized_power > 0.0 {
-                    let color = {
-                        let power = normalized_power.max(0.0).min(1.0);
-                        &RGBColor(255, (power * 255.0) as u8, (power * power * 255.0) as u8)
-                            .mix(power as f64)
-                    };
-
-                    chart.draw_series(std::iter::once(Circle::new(
-                        (time, freq),
-                        2.0,
-                        color.filled(),
-                    )))?;
-                }
+    // スペクトログラムデータの描画（各行を帯域幅の矩形として配置）
+    for (frame, row) in plot.rows.iter().enumerate() {
+        let time = plot.timeline.start + frame as f32 * plot.time_per_frame;
+
+        for (&value, &(low, high)) in row.iter().zip(plot.bands.iter()) {
+            let color = match plot.difference_range {
+                Some(range) => difference_color(value, range),
+                None => level_color(value / plot.reference, plot.colors.theme, plot.gamma),
+            };
+            if let Some(color) = color {
+                chart.draw_series(std::iter::once(Rectangle::new(
+                    [(time, low), (time + plot.time_per_frame, high)],
+                    color.filled(),
+                )))?;
             }
         }
     }
 
     // アノテーションの描画
-    if let Some(annotations) = annotations {
+    if let Some(annotations) = plot.annotations {
         for (freq, label) in annotations.iter() {
-            if *freq >= min_freq && *freq <= max_freq {
+            if *freq >= plot.min_freq && *freq <= plot.max_freq {
+                let y = plot.to_axis(*freq);
                 chart.draw_series(LineSeries::new(
-                    vec![(0.0, *freq), (total_time, *freq)],
-                    &GREEN,
+                    vec![(plot.timeline.start, y), (plot.timeline.end, y)],
+                    plot.colors.annotation.stroke_width(line_width),
                 ))?;
                 chart.draw_series(std::iter::once(Text::new(
                     label.to_string(),
-                    (total_time - 0.1, *freq - 100.0),
-                    (FONT_FAMILY, 16).into_font().color(&GREEN),
+                    (plot.timeline.end - 0.1, y),
+                    (plot.font, 16.0 * scale)
+                        .into_font()
+                        .color(&plot.colors.annotation),
                 )))?;
             }
         }
@@ -282,3 +1035,340 @@ pub fn create_spectrogram(
 
     Ok(())
 }
+
+/// 対数軸の目盛り位置を計算する（各デケードの 1, 2, 5 倍）
+fn log_ticks(min_freq: f32, max_freq: f32) -> Vec<f32> {
+    let mut ticks = Vec::new();
+    let mut decade = 10.0_f32.powf(min_freq.log10().floor());
+    while decade <= max_freq {
+        for factor in [1.0, 2.0, 5.0] {
+            let freq = decade * factor;
+            if (min_freq..=max_freq).contains(&freq) {
+                ticks.push(freq);
+            }
+        }
+        decade *= 10.0;
+    }
+    ticks
+}
+
+/// 重なり率からフレーム間の移動量（サンプル数）を求める。
+/// 切り捨てだと 2048 * 0.25 が 511 になりうるため丸め、常に1サンプル以上進める
+pub fn hop_size(window_size: usize, overlap: f32) -> usize {
+    ((window_size as f64 * (1.0 - overlap as f64)).round() as usize).max(1)
+}
+
+/// 短時間フーリエ変換を行い、フレームごとの振幅スペクトル（window_size / 2 ビン）を返す
+pub fn stft(
+    samples: &[f32],
+    window_size: usize,
+    hop_size: usize,
+    window_function: WindowFunction,
+) -> Vec<Vec<f32>> {
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(window_size);
+    let window = make_window(window_function, window_size);
+
+    let mut spectrogram = Vec::new();
+    let mut i = 0;
+    while i + window_size <= samples.len() {
+        let mut buffer: Vec<Complex<f32>> = samples[i..i + window_size]
+            .iter()
+            .zip(window.iter())
+            .map(|(&s, &w)| Complex::new(s * w, 0.0))
+            .collect();
+
+        fft.process(&mut buffer);
+
+        let spectrum: Vec<f32> = buffer[..window_size / 2]
+            .iter()
+            .map(|c| c.norm() / window_size as f32)
+            .collect();
+
+        spectrogram.push(spectrum);
+        i += hop_size.max(1);
+    }
+    spectrogram
+}
+
+/// 音色の明るさの指標
+#[derive(Clone, Copy, Debug)]
+pub struct SpectralSummary {
+    /// スペクトル重心（Hz）
+    pub centroid_hz: f32,
+    /// エネルギーの85%が含まれる上限周波数（Hz）
+    pub rolloff_hz: f32,
+}
+
+/// スペクトル重心とロールオフを求めるときのFFTサイズ
+const SUMMARY_WINDOW_SIZE: usize = 2048;
+/// ロールオフとするエネルギーの割合
+const ROLLOFF_RATIO: f32 = 0.85;
+
+/// 全フレームの振幅スペクトルを平均し、その重心とロールオフを求める。
+/// 窓より短いファイルは窓の中央に置き、前後を無音で埋めて1フレームとして扱う。
+/// 無音のファイルは None
+pub fn spectral_summary(samples: &[f32], sample_rate: u32) -> Option<SpectralSummary> {
+    let mut padded = Vec::with_capacity(samples.len().max(SUMMARY_WINDOW_SIZE));
+    let padding = SUMMARY_WINDOW_SIZE.saturating_sub(samples.len());
+    padded.resize(padding / 2, 0.0);
+    padded.extend_from_slice(samples);
+    padded.resize(padded.len() + padding - padding / 2, 0.0);
+    let frames = stft(
+        &padded,
+        SUMMARY_WINDOW_SIZE,
+        SUMMARY_WINDOW_SIZE / 2,
+        WindowFunction::Hann,
+    );
+
+    summarize_frames(&frames, sample_rate as f32 / SUMMARY_WINDOW_SIZE as f32)
+}
+
+/// STFTの各フレームを平均したスペクトルから重心とロールオフを求める
+fn summarize_frames(frames: &[Vec<f32>], freq_resolution: f32) -> Option<SpectralSummary> {
+    let bins = frames.first().map_or(0, |spectrum| spectrum.len());
+    let mut average = vec![0.0f32; bins];
+    for spectrum in frames {
+        for (sum, &amplitude) in average.iter_mut().zip(spectrum) {
+            *sum += amplitude / frames.len() as f32;
+        }
+    }
+
+    let total_amplitude: f32 = average.iter().sum();
+    let total_energy: f32 = average.iter().map(|a| a * a).sum();
+    if total_amplitude <= 0.0 || total_energy <= 0.0 {
+        return None;
+    }
+
+    let centroid_hz = average
+        .iter()
+        .enumerate()
+        .map(|(bin, &amplitude)| bin as f32 * freq_resolution * amplitude)
+        .sum::<f32>()
+        / total_amplitude;
+
+    let mut cumulative = 0.0;
+    let rolloff_bin = average
+        .iter()
+        .position(|&amplitude| {
+            cumulative += amplitude * amplitude;
+            cumulative >= ROLLOFF_RATIO * total_energy
+        })
+        .unwrap_or(average.len() - 1);
+
+    Some(SpectralSummary {
+        centroid_hz,
+        rolloff_hz: rolloff_bin as f32 * freq_resolution,
+    })
+}
+
+/// 指定された種類の窓関数を作成する
+pub fn make_window(kind: WindowFunction, size: usize) -> Vec<f32> {
+    (0..size)
+        .map(|i| {
+            let x = 2.0 * PI * i as f32 / size as f32;
+            match kind {
+                WindowFunction::Hann => 0.5 * (1.0 - x.cos()),
+                WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+                WindowFunction::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                WindowFunction::Rectangular => 1.0,
+                WindowFunction::BlackmanHarris => {
+                    0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos()
+                        - 0.01168 * (3.0 * x).cos()
+                }
+            }
+        })
+        .collect()
+}
+
+/// 振幅をdBに変換する（無音は最小値に丸める）
+fn amplitude_to_db(amplitude: f32) -> f32 {
+    if amplitude > 0.0 {
+        (20.0 * amplitude.log10()).max(MIN_DB)
+    } else {
+        MIN_DB
+    }
+}
+
+/// スペクトログラムをCSVで書き出す
+///
+/// 1行目は各ビンの中心周波数、各行の先頭列はフレーム（STFTの窓）の先頭のファイル上の時刻。
+/// フレームは hop_seconds ずつ進む
+fn export_csv(
+    path: &Path,
+    data: &[Vec<f32>],
+    centers: &[f32],
+    start_time: f32,
+    hop_seconds: f32,
+) -> Result<(), AudioToolsError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    let header: Vec<String> = centers.iter().map(|f| format!("{:.2}", f)).collect();
+    writeln!(writer, "time,{}", header.join(","))?;
+
+    for (frame, row) in data.iter().enumerate() {
+        let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        writeln!(
+            writer,
+            "{:.6},{}",
+            start_time + frame as f32 * hop_seconds,
+            values.join(",")
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// スペクトログラムを NumPy の .npy 形式（float32, フレーム × ビン）で書き出す
+fn export_npy(path: &Path, data: &[Vec<f32>]) -> Result<(), AudioToolsError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    let bins = data.first().map_or(0, |row| row.len());
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        data.len(),
+        bins
+    );
+    // マジック(6) + バージョン(2) + ヘッダ長(2) + ヘッダを64バイト境界に揃える
+    let padding = 64 - (10 + header.len() + 1) % 64;
+    header.push_str(&" ".repeat(padding % 64));
+    header.push('\n');
+
+    writer.write_all(b"\x93NUMPY")?;
+    writer.write_all(&[1, 0])?;
+    writer.write_u16::<LittleEndian>(header.len() as u16)?;
+    writer.write_all(header.as_bytes())?;
+    for row in data {
+        for &value in row {
+            writer.write_f32::<LittleEndian>(value)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// 周波数（Hz）をメル値に変換する（HTK式）
+pub fn hz_to_mel(freq: f32) -> f32 {
+    2595.0 * (1.0 + freq / 700.0).log10()
+}
+
+/// メル値を周波数（Hz）に変換する（HTK式）
+pub fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10.0_f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// min_freq..max_freq をメル尺度で等分した三角フィルタバンクを作成する
+///
+/// 戻り値はフィルタごとの各FFTビンに対する重み
+fn mel_filterbank(
+    n_mels: usize,
+    n_bins: usize,
+    freq_resolution: f32,
+    min_freq: f32,
+    max_freq: f32,
+) -> Vec<Vec<f32>> {
+    let min_mel = hz_to_mel(min_freq);
+    let mel_step = (hz_to_mel(max_freq) - min_mel) / (n_mels + 1) as f32;
+    let edges: Vec<f32> = (0..n_mels + 2)
+        .map(|i| mel_to_hz(min_mel + i as f32 * mel_step))
+        .collect();
+
+    (0..n_mels)
+        .map(|m| {
+            let (lower, center, upper) = (edges[m], edges[m + 1], edges[m + 2]);
+            (0..n_bins)
+                .map(|bin| {
+                    let freq = bin as f32 * freq_resolution;
+                    if freq <= lower || freq >= upper {
+                        0.0
+                    } else if freq <= center {
+                        (freq - lower) / (center - lower)
+                    } else {
+                        (upper - freq) / (upper - center)
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn window_endpoints() {
+        // (窓関数, 先頭の値, 中央の値)。周期窓なので中央が最大値になる
+        let cases = [
+            (WindowFunction::Hann, 0.0, 1.0),
+            (WindowFunction::Hamming, 0.08, 1.0),
+            (WindowFunction::Blackman, 0.0, 1.0),
+            (WindowFunction::Rectangular, 1.0, 1.0),
+            (WindowFunction::BlackmanHarris, 0.00006, 1.0),
+        ];
+        for (kind, first, middle) in cases {
+            let window = make_window(kind, 2048);
+            assert_eq!(window.len(), 2048);
+            assert_close(window[0], first);
+            assert_close(window[1024], middle);
+        }
+    }
+
+    #[test]
+    fn hop_size_from_overlap() {
+        assert_eq!(hop_size(2048, 0.75), 512);
+        assert_eq!(hop_size(2048, 0.0), 2048);
+        // 1.0 に近い重なり率でも1サンプルは進める
+        assert_eq!(hop_size(2048, 0.9999), 1);
+        assert_eq!(hop_size(2048, 1.0), 1);
+    }
+
+    #[test]
+    fn create_spectrogram_rejects_full_overlap() {
+        let options = SpectrogramOptions {
+            window_size: 2048,
+            window_function: WindowFunction::Hann,
+            overlap: 1.0,
+            min_freq: 0.0,
+            max_freq: MaxFrequency::Nyquist,
+            freq_scale: FrequencyScale::Linear,
+            mel_bands: None,
+            average: false,
+            time_range: None,
+            auto_start: None,
+            annotations: None,
+            export: None,
+            export_units: ExportUnits::Db,
+            compare: None,
+            diff_range: 12.0,
+            gamma: 1.0,
+            normalize: false,
+            metadata: false,
+            colors: Theme::Dark.spectrum_colors(),
+            font: "sans-serif".to_string(),
+            no_image: true,
+            print_detection: false,
+            format: ImageFormat::Png,
+            width: 800,
+            height: 400,
+            image_scale: 1.0,
+        };
+        // 入力を読む前に設定の検証で失敗する
+        let result =
+            create_spectrogram(Path::new("missing.wav"), Path::new("missing.png"), &options);
+        assert!(
+            matches!(result, Err(AudioToolsError::InvalidInput(message)) if message.contains("Overlap"))
+        );
+    }
+}
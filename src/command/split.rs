@@ -0,0 +1,117 @@
+use crate::error::AudioToolsError;
+use crate::utils::detection::AutoStartDetection;
+use crate::utils::time::TimeSpecification;
+use crate::utils::wav_io::WavData;
+use log::info;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 分割位置の決め方
+#[derive(Clone, Debug)]
+pub enum SplitMode {
+    /// 一定の長さごとに分割
+    Every(TimeSpecification),
+    /// 指定した時刻で分割
+    At(Vec<TimeSpecification>),
+    /// 無音区間で区切られた音ごとに分割
+    OnSilence {
+        detection: AutoStartDetection,
+        /// 区切りとみなす無音の最短長（秒）
+        min_silence: f32,
+    },
+}
+
+/// 分割処理のパラメータ
+#[derive(Clone, Debug)]
+pub struct SplitOptions {
+    pub mode: SplitMode,
+    pub output_dir: Option<PathBuf>,
+    pub force: bool,
+}
+
+pub fn split_file(input: &Path, options: &SplitOptions) -> Result<(), AudioToolsError> {
+    let wav = WavData::read(input)?;
+    let sample_rate = wav.spec.sample_rate as f32;
+    let total_duration = wav.duration();
+
+    let segments = match &options.mode {
+        SplitMode::Every(length) => {
+            let length = length.to_seconds(total_duration);
+            if length <= 0.0 {
+                return Err(AudioToolsError::InvalidInput(
+                    "Segment length must be longer than 0".to_string(),
+                ));
+            }
+            let count = (total_duration / length).ceil() as usize;
+            (0..count)
+                .map(|i| {
+                    (
+                        i as f32 * length,
+                        ((i + 1) as f32 * length).min(total_duration),
+                    )
+                })
+                .collect()
+        }
+        SplitMode::At(points) => {
+            let mut cuts: Vec<f32> = points
+                .iter()
+                .map(|point| point.to_seconds(total_duration))
+                .filter(|&t| t > 0.0 && t < total_duration)
+                .collect();
+            cuts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            cuts.dedup();
+
+            let mut bounds = vec![0.0];
+            bounds.extend(cuts);
+            bounds.push(total_duration);
+            bounds.windows(2).map(|w| (w[0], w[1])).collect()
+        }
+        SplitMode::OnSilence {
+            detection,
+            min_silence,
+        } => detection.detect_sounds(&wav.mono(), sample_rate, *min_silence),
+    };
+
+    // 浮動小数点の誤差で最後の区間が0フレームになることがあるため、空の区間は除く
+    let segments: Vec<(f32, f32)> = segments
+        .into_iter()
+        .filter(|&(start, end)| ((start * sample_rate) as usize) < (end * sample_rate) as usize)
+        .collect();
+    if segments.is_empty() {
+        return Err(AudioToolsError::InvalidInput(
+            "No segments found".to_string(),
+        ));
+    }
+
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let output_dir = match &options.output_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            dir.clone()
+        }
+        None => input.parent().unwrap_or(Path::new("")).to_path_buf(),
+    };
+
+    for (i, (start, end)) in segments.iter().enumerate() {
+        let output = output_dir.join(format!("{}_{:03}.wav", stem, i + 1));
+        if output.exists() && !options.force {
+            info!(
+                "Skipped: {} (output file already exists. Use --force to overwrite)",
+                output.display()
+            );
+            continue;
+        }
+
+        let start_frame = (start * sample_rate) as usize;
+        let end_frame = (end * sample_rate) as usize;
+        wav.write_frames(&output, start_frame, end_frame)?;
+        info!(
+            "Created: {} ({:.3}s - {:.3}s)",
+            output.display(),
+            start,
+            end
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use crate::error::AudioToolsError;
+use crate::utils::detection::AutoStartDetection;
+use crate::utils::ffmpeg::ffmpeg_command;
+use crate::utils::ffprobe::run_ffprobe;
+use crate::utils::time::{sample_range, TimeRange, TimeSpecification};
+use crate::utils::wav_io::WavData;
+use log::debug;
+use std::path::Path;
+
+/// 切り出し処理のパラメータ
+#[derive(Clone, Debug)]
+pub struct TrimOptions {
+    pub time_range: Option<TimeRange>,
+    pub auto_start: Option<AutoStartDetection>,
+    pub force: bool,
+}
+
+pub fn trim_file(
+    input: &Path,
+    output: &Path,
+    options: &TrimOptions,
+) -> Result<(f32, f32), AudioToolsError> {
+    if options.time_range.is_none() && options.auto_start.is_none() {
+        return Err(AudioToolsError::InvalidInput(
+            "Specify --start, --end, or --auto-start".to_string(),
+        ));
+    }
+    if output.exists() && !options.force {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "{} already exists. Use --force to overwrite",
+            output.display()
+        )));
+    }
+
+    let is_wav = input
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    if is_wav {
+        trim_wav(input, output, options)
+    } else {
+        trim_with_ffmpeg(input, output, options)
+    }
+}
+
+/// WAVはサンプリングレート・ビット深度を保ったままhoundで書き出す
+fn trim_wav(
+    input: &Path,
+    output: &Path,
+    options: &TrimOptions,
+) -> Result<(f32, f32), AudioToolsError> {
+    let wav = WavData::read(input)?;
+    let sample_rate = wav.spec.sample_rate as f32;
+    let total_duration = wav.duration();
+
+    // 時間範囲の設定
+    let (start_time, end_time) = if let Some(auto_config) = &options.auto_start {
+        let detected_start = auto_config
+            .detect_start_time(&wav.mono(), sample_rate)
+            .ok_or_else(|| AudioToolsError::TimeRange("Failed to detect start time".to_string()))?;
+
+        let end_time = if let Some(range) = &options.time_range {
+            TimeRange {
+                start: TimeSpecification::Seconds(detected_start),
+                end: range.end.clone(),
+            }
+            .resolve(total_duration)
+            .map_or(total_duration, |(_, end)| end)
+        } else {
+            total_duration
+        };
+
+        (detected_start, end_time)
+    } else if let Some(range) = &options.time_range {
+        range.resolve(total_duration)?
+    } else {
+        (0.0, total_duration)
+    };
+
+    let range = sample_range(start_time, end_time, sample_rate, wav.frames())?;
+    wav.write_frames(output, range.start, range.end)?;
+
+    Ok((start_time, end_time))
+}
+
+/// WAV以外はffmpegの -ss / -to で切り出す
+fn trim_with_ffmpeg(
+    input: &Path,
+    output: &Path,
+    options: &TrimOptions,
+) -> Result<(f32, f32), AudioToolsError> {
+    if options.auto_start.is_some() {
+        return Err(AudioToolsError::InvalidInput(
+            "Auto start detection is only supported for WAV input".to_string(),
+        ));
+    }
+
+    let total_duration: f32 = run_ffprobe(
+        input,
+        &["-show_entries", "format=duration", "-of", "csv=p=0"],
+    )?
+    .trim()
+    .parse()
+    .map_err(|_| AudioToolsError::Ffmpeg("Could not read duration with ffprobe".to_string()))?;
+
+    let (start_time, end_time) = match &options.time_range {
+        Some(range) => range.resolve(total_duration)?,
+        None => (0.0, total_duration),
+    };
+
+    let mut cmd = ffmpeg_command();
+    cmd.arg(if options.force { "-y" } else { "-n" })
+        .arg("-i")
+        .arg(input)
+        .args(["-ss", &start_time.to_string(), "-to", &end_time.to_string()])
+        .arg(output);
+    debug!("Running {:?}", cmd);
+    let result = cmd
+        .output()
+        .map_err(|e| AudioToolsError::Ffmpeg(format!("could not run ffmpeg: {}", e)))?;
+    if !result.status.success() {
+        return Err(AudioToolsError::Ffmpeg(
+            String::from_utf8_lossy(&result.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok((start_time, end_time))
+}
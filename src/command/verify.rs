@@ -0,0 +1,151 @@
+use crate::error::AudioToolsError;
+use crate::utils::progress::ProgressCallback;
+use crate::utils::summary::BatchSummary;
+use crate::utils::wave_header::{RiffChunk, WavHeader};
+use crate::utils::{get_walkers, WalkOptions};
+use byteorder::{LittleEndian, ReadBytesExt};
+use log::{error, info};
+use std::fs::File;
+use std::io::{ErrorKind, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// WAVファイルのRIFF構造を検査し、見つかった問題を返す（問題がなければ空）。
+/// 検査するのはヘッダとチャンクの大きさだけで、サンプルはデコードしない
+pub fn verify_wav(path: &Path) -> Result<Vec<String>, AudioToolsError> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let chunks = match WavHeader::read_chunks(&mut file) {
+        Ok(chunks) => chunks,
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            return Ok(vec!["File is too short for a RIFF header".to_string()])
+        }
+        Err(e) => return Ok(vec![e.to_string()]),
+    };
+    let mut problems = Vec::new();
+
+    // RIFFチャンクの大きさはファイル長から先頭8バイトを引いた値になる
+    file.seek(SeekFrom::Start(4))?;
+    let riff_size = file.read_u32::<LittleEndian>()?;
+    if riff_size as u64 != file_len - 8 {
+        problems.push(format!(
+            "RIFF size is {} bytes, but {} bytes follow the RIFF header",
+            riff_size,
+            file_len - 8
+        ));
+    }
+
+    problems.extend(chunk_layout_problems(&chunks, file_len));
+
+    let data = chunks.iter().find(|chunk| &chunk.id == b"data");
+    let header = WavHeader::read_from_file(&mut file).ok();
+    match (&header, data) {
+        (None, _) if chunks.iter().any(|chunk| &chunk.id == b"fmt ") => {
+            problems.push("fmt chunk is too short".to_string())
+        }
+        (None, _) => problems.push("No fmt chunk".to_string()),
+        (Some(_), None) => problems.push("No data chunk".to_string()),
+        (Some(header), Some(data)) => {
+            let block_align = header.block_align() as u32;
+            if block_align == 0 {
+                problems.push("fmt chunk has a block align of 0".to_string());
+            } else if data.declared_size % block_align != 0 {
+                problems.push(format!(
+                    "data chunk holds {} bytes, which is not a whole number of {}-byte frames",
+                    data.declared_size, block_align
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// 各チャンクが宣言どおりの大きさでファイル内に収まり、偶数境界に並んでいるかを調べる
+fn chunk_layout_problems(chunks: &[RiffChunk], file_len: u64) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        // 奇数長のチャンクにパディングがないと、次のチャンクIDが1バイトずれて読める
+        if !chunk.id.iter().all(|&b| b.is_ascii_graphic() || b == b' ') {
+            let mut problem = format!("Invalid chunk id at byte {}", chunk.offset - 8);
+            if let Some(previous) = i.checked_sub(1).map(|i| &chunks[i]) {
+                if previous.size % 2 == 1 {
+                    problem.push_str(&format!(
+                        " (the odd-sized '{}' chunk before it may be missing its pad byte)",
+                        previous.id_str()
+                    ));
+                }
+            }
+            problems.push(problem);
+            // 以降は位置がずれているため検査しない
+            return problems;
+        }
+
+        if chunk.declared_size > chunk.size {
+            problems.push(format!(
+                "'{}' chunk declares {} bytes, but only {} remain in the file (truncated)",
+                chunk.id_str(),
+                chunk.declared_size,
+                chunk.size
+            ));
+        }
+    }
+
+    let end = chunks.last().map_or(12, |chunk| {
+        chunk.offset + chunk.size as u64 + (chunk.size as u64 & 1)
+    });
+    if end > file_len && chunks.last().is_some_and(|c| c.declared_size == c.size) {
+        problems.push("Missing pad byte after the odd-sized last chunk".to_string());
+    } else if end < file_len {
+        problems.push(format!(
+            "{} trailing bytes after the last chunk",
+            file_len - end
+        ));
+    }
+
+    problems
+}
+
+/// WAVファイルをすべて検査し、問題のあったファイルがあれば false を返す
+pub fn verify_files(
+    inputs: &[PathBuf],
+    walk: &WalkOptions,
+    progress: Option<ProgressCallback>,
+) -> bool {
+    let mut summary = BatchSummary::for_walk(walk).with_progress(progress);
+
+    for entry in get_walkers(inputs, walk) {
+        if summary.should_stop() {
+            break;
+        }
+        let is_wav = entry
+            .path()
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+        if !is_wav {
+            continue;
+        }
+        summary.started(entry.path());
+
+        match verify_wav(entry.path()) {
+            Ok(problems) if problems.is_empty() => {
+                summary.processed(entry.path());
+                info!("OK: {}", entry.path().display());
+            }
+            Ok(problems) => {
+                summary.failed(entry.path());
+                for problem in problems {
+                    error!("{}: {}", entry.path().display(), problem);
+                }
+            }
+            Err(e) => {
+                summary.failed(entry.path());
+                error!("{}: {}", entry.path().display(), e);
+            }
+        }
+    }
+
+    summary.report("passed");
+    !summary.has_failures()
+}
@@ -1,19 +1,36 @@
+use crate::error::AudioToolsError;
+use crate::utils::decode::{decode_audio, normalize_sample};
 use crate::utils::detection::AutoStartDetection;
-use crate::utils::get_walker;
-use crate::utils::time::{TimeRange, TimeSpecification};
+use crate::utils::plot::{
+    metadata_path, scaled_size, validate_dimensions, validate_image_scale, ImageFormat,
+    ImageMetadata, ImageOutputOptions, PlotColors, PlottedSource,
+};
+use crate::utils::time::{sample_range, TimeRange, TimeSpecification};
+use crate::utils::{get_walker, is_audio_file, WalkOptions};
 use clap::ValueEnum;
 use hound::WavReader;
+use log::{debug, error, info, warn};
+use plotters::coord::Shift;
 use plotters::prelude::*;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-const FONT_FAMILY: &str = "Fira Code";
-const BACKGROUND_COLOR: RGBColor = RGBColor(4, 20, 36);
-const PEAK_COLOR: RGBColor = RGBColor(0, 128, 255);
-const RMS_COLOR: RGBAColor = RGBAColor(0, 255, 128, 0.5);
-const GRID_COLOR: RGBAColor = RGBAColor(255, 255, 255, 0.2);
-const ANNOTATION_COLOR: RGBColor = RGBColor(255, 255, 0);
+/// これ以上のサンプル数（全チャンネル合計）のWAVは全サンプルを保持せずストリーミングで集計する
+const STREAMING_THRESHOLD_SAMPLES: u64 = 1 << 25;
+/// 重ね描き時に入力ファイルごとに割り当てる色
+const OVERLAY_COLORS: [RGBColor; 6] = [
+    RGBColor(0, 128, 255),
+    RGBColor(255, 128, 0),
+    RGBColor(0, 200, 120),
+    RGBColor(220, 60, 220),
+    RGBColor(240, 220, 40),
+    RGBColor(120, 220, 255),
+];
 
-#[derive(Clone, Copy, ValueEnum)]
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum WaveformScale {
     Amplitude,
     Decibel,
@@ -32,89 +49,359 @@ pub fn parse_time_annotation(s: &str) -> Result<(f32, String), String> {
     Ok((time, parts[1].to_string()))
 }
 
+/// 表示するチャンネル
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WaveformChannel {
+    /// 全チャンネルの平均
+    Mix,
+    Left,
+    Right,
+    /// 左右を上下に並べて表示
+    Split,
+}
+
+/// 波形画像生成のパラメータ
+#[derive(Clone, Debug)]
+pub struct WaveformOptions {
+    pub scale: WaveformScale,
+    /// デシベル表示の下限（dBFS）
+    pub db_floor: f32,
+    pub channel: WaveformChannel,
+    pub time_range: Option<TimeRange>,
+    pub auto_start: Option<AutoStartDetection>,
+    pub annotations: Option<Vec<(f32, String)>>,
+    pub show_rms: bool,
+    /// RMSウィンドウ長（ミリ秒）。短いとトランジェントを追従し、長いと全体のエネルギーを示す
+    pub rms_window_ms: f32,
+    /// エンベロープではなく全サンプルを折れ線で描画する
+    pub raw: bool,
+    /// クリップしているサンプルを表示・報告する
+    pub show_clipping: bool,
+    /// クリップとみなす振幅の絶対値
+    pub clip_threshold: f32,
+    /// 画像の横に描画設定を JSON で保存する
+    pub metadata: bool,
+    /// 背景・波形・RMS・アノテーションなどの色
+    pub colors: PlotColors,
+    /// タイトル・目盛り・アノテーションのフォント名
+    pub font: String,
+    /// 画像を書き出さず、測定値だけを表示する
+    pub no_image: bool,
+    /// --auto-start で検出した開始位置と描画範囲の終わり（秒）を標準出力に書く
+    pub print_detection: bool,
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// 寸法・文字・線幅・余白に掛ける倍率（印刷向けの高解像度出力）
+    pub image_scale: f32,
+}
+
+/// --metadata で画像の横に保存する描画設定
+#[derive(Debug, Serialize)]
+pub struct WaveformSettings {
+    pub scale: WaveformScale,
+    /// デシベル表示の下限（デシベル表示のときだけ）
+    pub db_floor: Option<f32>,
+    pub channel: WaveformChannel,
+    /// RMSウィンドウ長（--show-rms のときだけ）
+    pub rms_window_ms: Option<f32>,
+    pub raw: bool,
+    /// クリップとみなす振幅（--show-clipping のときだけ）
+    pub clip_threshold: Option<f32>,
+}
+
+pub type WaveformMetadata = ImageMetadata<WaveformSettings>;
+
+impl WaveformSettings {
+    fn new(options: &WaveformOptions) -> Self {
+        Self {
+            scale: options.scale,
+            db_floor: matches!(options.scale, WaveformScale::Decibel).then_some(options.db_floor),
+            channel: options.channel,
+            rms_window_ms: options.show_rms.then_some(options.rms_window_ms),
+            raw: options.raw,
+            clip_threshold: options.show_clipping.then_some(options.clip_threshold),
+        }
+    }
+}
+
 pub fn create_waveforms(
     input: &PathBuf,
-    scale: WaveformScale,
-    time_range: Option<TimeRange>,
-    auto_start: Option<AutoStartDetection>,
-    recursive: bool,
-    annotations: Option<Vec<(f32, String)>>,
-    show_rms: bool,
-) {
-    for entry in get_walker(input, recursive) {
+    options: &WaveformOptions,
+    walk: &WalkOptions,
+    naming: &ImageOutputOptions,
+) -> bool {
+    let mut ok = true;
+    for entry in get_walker(input, walk) {
         if let Some(ext) = entry.path().extension() {
-            if ext.to_string_lossy().to_lowercase() == "wav" {
+            if is_audio_file(&ext.to_string_lossy().to_lowercase()) {
                 let input_path = PathBuf::from(entry.path());
-                let output_path = input_path.with_extension("png");
-
-                match create_waveform(
-                    &input_path,
-                    &output_path,
-                    scale,
-                    time_range.clone(),
-                    auto_start.clone(),
-                    annotations.clone(),
-                    show_rms,
-                ) {
-                    Ok(_) => println!(
-                        "Created waveform: {} -> {}",
-                        input_path.display(),
+                let output_path = naming.image_path(&input_path, input, options.format.extension());
+                let is_up_to_date = |output: &Path| naming.is_up_to_date(output, &[&input_path]);
+                if !options.no_image
+                    && is_up_to_date(&output_path)
+                    && (!options.metadata || is_up_to_date(&metadata_path(&output_path)))
+                {
+                    info!(
+                        "Skipped: {} (image is newer than the source. Use --force to regenerate)",
                         output_path.display()
-                    ),
-                    Err(e) => eprintln!("Error processing {}: {}", input_path.display(), e),
+                    );
+                    continue;
+                }
+                if !options.no_image {
+                    if let Some(dir) = output_path.parent() {
+                        if let Err(e) = fs::create_dir_all(dir) {
+                            error!("Error creating {}: {}", dir.display(), e);
+                            if walk.fail_fast {
+                                return false;
+                            }
+                            ok = false;
+                            continue;
+                        }
+                    }
+                }
+
+                let result = create_waveform(&input_path, &output_path, options);
+                if let Ok(metadata) = &result {
+                    if options.print_detection && options.auto_start.is_some() {
+                        metadata.print_range(&walk.display_path(&input_path));
+                    }
+                }
+                match result {
+                    Ok(_) if options.no_image => {
+                        info!("Analyzed: {} (no image written)", input_path.display())
+                    }
+                    Ok(metadata) => {
+                        info!(
+                            "Created waveform: {} -> {}",
+                            input_path.display(),
+                            output_path.display()
+                        );
+                        if options.metadata {
+                            metadata.save(&output_path, walk);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error processing {}: {}", input_path.display(), e);
+                        if walk.fail_fast {
+                            return false;
+                        }
+                        ok = false;
+                    }
                 }
             }
         }
     }
+    ok
 }
 
 pub fn create_waveform(
-    input: &PathBuf,
-    output: &PathBuf,
-    scale: WaveformScale,
-    time_range: Option<TimeRange>,
-    auto_start: Option<AutoStartDetection>,
-    annotations: Option<Vec<(f32, String)>>,
-    show_rms: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut reader = WavReader::open(input)?;
-    let spec = reader.spec();
-    let sample_rate = spec.sample_rate as f32;
+    input: &Path,
+    output: &Path,
+    options: &WaveformOptions,
+) -> Result<WaveformMetadata, AudioToolsError> {
+    validate_options(options)?;
 
-    // サンプルデータの読み込み
-    let samples: Vec<f32> = match spec.sample_format {
-        hound::SampleFormat::Float => reader
-            .samples::<f32>()
-            .map(|s| s.unwrap())
-            .collect::<Vec<f32>>()
-            .chunks(spec.channels as usize)
-            .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+    let waveform = load_waveform(input, options)?;
+    let metadata = ImageMetadata::new(
+        vec![waveform.plotted_source(input)],
+        WaveformSettings::new(options),
+    );
+    let plots: Vec<WaveformPlot> = waveform
+        .tracks
+        .iter()
+        .map(|(label, track)| WaveformPlot {
+            title: label,
+            data: track,
+            sample_rate: waveform.sample_rate,
+            start_time: waveform.start_time,
+            end_time: waveform.end_time,
+            color: options.colors.wave,
+        })
+        .collect();
+
+    // クリップ検出結果の出力
+    if options.show_clipping {
+        for plot in &plots {
+            report_clipping(plot, options.clip_threshold);
+        }
+    }
+    if options.no_image {
+        report_metrics(&plots, options);
+        return Ok(metadata);
+    }
+
+    // プロット作成（チャンネルごとに1段）
+    let panels: Vec<(&str, Vec<WaveformPlot>)> = plots
+        .into_iter()
+        .map(|plot| (plot.title, vec![plot]))
+        .collect();
+    render(output, &panels, options)?;
+    Ok(metadata)
+}
+
+/// 複数ファイルの波形を同じ軸に色分けして重ねて描画する
+pub fn create_waveform_overlay(
+    inputs: &[PathBuf],
+    output: &Path,
+    options: &WaveformOptions,
+) -> Result<WaveformMetadata, AudioToolsError> {
+    validate_options(options)?;
+
+    let waveforms = inputs
+        .iter()
+        .map(|input| load_waveform(input, options))
+        .collect::<Result<Vec<_>, _>>()?;
+    let metadata = ImageMetadata::new(
+        inputs
+            .iter()
+            .zip(&waveforms)
+            .map(|(input, waveform)| waveform.plotted_source(input))
             .collect(),
-        hound::SampleFormat::Int => {
-            let bits = spec.bits_per_sample;
-            let max_value = (1 << (bits - 1)) as f32;
-            reader
-                .samples::<i32>()
-                .map(|s| s.unwrap() as f32 / max_value)
-                .collect::<Vec<f32>>()
-                .chunks(spec.channels as usize)
-                .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
-                .collect()
+        WaveformSettings::new(options),
+    );
+
+    let plots: Vec<WaveformPlot> = waveforms
+        .iter()
+        .flat_map(|waveform| {
+            waveform
+                .tracks
+                .iter()
+                .map(move |(label, track)| (waveform, label, track))
+        })
+        .enumerate()
+        .map(|(i, (waveform, label, track))| WaveformPlot {
+            title: label,
+            data: track,
+            sample_rate: waveform.sample_rate,
+            start_time: waveform.start_time,
+            end_time: waveform.end_time,
+            color: OVERLAY_COLORS[i % OVERLAY_COLORS.len()].to_rgba(),
+        })
+        .collect();
+
+    if options.show_clipping {
+        for plot in &plots {
+            report_clipping(plot, options.clip_threshold);
         }
-    };
+    }
+    if options.no_image {
+        report_metrics(&plots, options);
+        return Ok(metadata);
+    }
+
+    render(output, &[("Overlay", plots)], options)?;
+    Ok(metadata)
+}
+
+fn validate_options(options: &WaveformOptions) -> Result<(), AudioToolsError> {
+    validate_dimensions(options.width, options.height)?;
+    validate_image_scale(options.image_scale)?;
+    if options.rms_window_ms <= 0.0 {
+        return Err(AudioToolsError::InvalidInput(
+            "RMS window must be longer than 0 ms".to_string(),
+        ));
+    }
+    if options.db_floor >= 0.0 {
+        return Err(AudioToolsError::InvalidInput(
+            "Decibel floor must be negative".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 読み込み・範囲選択済みの波形データ
+struct LoadedWaveform {
+    sample_rate: f32,
+    start_time: f32,
+    end_time: f32,
+    /// (ラベル, 選択範囲の波形)
+    tracks: Vec<(String, TrackData)>,
+}
+
+impl LoadedWaveform {
+    fn plotted_source(&self, path: &Path) -> PlottedSource {
+        PlottedSource {
+            path: path.to_path_buf(),
+            sample_rate: self.sample_rate as u32,
+            start: self.start_time,
+            end: self.end_time,
+        }
+    }
+}
+
+/// 1チャンネル分の描画データ
+enum TrackData {
+    /// 選択範囲の全サンプル
+    Samples(Vec<f32>),
+    /// 長時間ファイルをストリーミングで集計した結果
+    Summary(WaveformSummary),
+}
+
+/// 描画列ごとに集計した波形
+struct WaveformSummary {
+    /// 列ごとの (最小値, 最大値)
+    envelope: Vec<(f32, f32)>,
+    /// 列ごとのRMSの最大値
+    rms: Vec<f32>,
+    /// 選択範囲全体のRMS
+    total_rms: f32,
+    clip_runs: Vec<ClipRun>,
+}
+
+/// しきい値以上のサンプルが連続する区間
+struct ClipRun {
+    /// 選択範囲の先頭からのサンプル位置
+    start: usize,
+    len: usize,
+    /// 区間の最初のサンプル値（マーカーの位置に使う）
+    value: f32,
+}
+
+/// 音声ファイルを読み込み、時間範囲とチャンネルを選択する
+fn load_waveform(
+    input: &Path,
+    options: &WaveformOptions,
+) -> Result<LoadedWaveform, AudioToolsError> {
+    // 長いWAVは全サンプルを読み込まずに集計する
+    // （全サンプル描画と開始点検出は全体が必要なため通常の読み込みを行う）
+    if !options.raw && options.auto_start.is_none() {
+        if let Ok(reader) = WavReader::open(input) {
+            let total_samples = reader.duration() as u64 * reader.spec().channels as u64;
+            if total_samples >= STREAMING_THRESHOLD_SAMPLES {
+                debug!("Streaming {} ({} samples)", input.display(), total_samples);
+                return stream_waveform(input, reader, options);
+            }
+        }
+    }
+
+    // サンプルデータの読み込み（インターリーブのまま）
+    let audio = decode_audio(input)?;
+    let sample_rate = audio.sample_rate as f32;
+
+    // モノラルへのダウンミックス（開始点検出にも使用）
+    let channel_count = audio.channels as usize;
+    let samples = audio.mono();
+    let interleaved = audio.samples;
+    if samples.is_empty() {
+        return Err(AudioToolsError::InvalidInput(
+            "File contains no samples".to_string(),
+        ));
+    }
 
     let total_duration = samples.len() as f32 / sample_rate;
 
     // 時間範囲の設定
-    let (start_time, end_time) = if let Some(auto_config) = auto_start {
+    let (start_time, end_time) = if let Some(auto_config) = &options.auto_start {
         let detected_start = auto_config
             .detect_start_time(&samples, sample_rate)
-            .ok_or("Failed to detect start time")?;
+            .ok_or_else(|| AudioToolsError::TimeRange("Failed to detect start time".to_string()))?;
 
-        let end_time = if let Some(range) = time_range {
+        let end_time = if let Some(range) = &options.time_range {
             TimeRange {
                 start: TimeSpecification::Seconds(detected_start),
-                end: range.end,
+                end: range.end.clone(),
             }
             .resolve(total_duration)
             .map_or(total_duration, |(_, end)| end)
@@ -123,45 +410,326 @@ pub fn create_waveform(
         };
 
         (detected_start, end_time)
-    } else if let Some(range) = time_range {
+    } else if let Some(range) = &options.time_range {
         range.resolve(total_duration)?
     } else {
         (0.0, total_duration)
     };
 
     // サンプル範囲の切り出し
-    let start_sample = (start_time * sample_rate) as usize;
-    let end_sample = (end_time * sample_rate) as usize;
-    let samples = samples[start_sample..end_sample].to_vec();
+    let range = sample_range(start_time, end_time, sample_rate, samples.len())?;
 
-    // RMS値の計算
-    let window_size = (sample_rate * 0.02) as usize; // 20ms window
-    let rms_values = calculate_rms(&samples, window_size);
+    // 表示するチャンネルの選択（モノラルファイルは常に単一チャンネル）
+    let extract = |source: TrackSource| -> Vec<f32> {
+        match source {
+            TrackSource::Mix => samples[range.clone()].to_vec(),
+            TrackSource::Channel(channel) => interleaved
+                .iter()
+                .skip(channel.min(channel_count - 1))
+                .step_by(channel_count)
+                .skip(range.start)
+                .take(range.len())
+                .copied()
+                .collect(),
+        }
+    };
+
+    Ok(LoadedWaveform {
+        sample_rate,
+        start_time,
+        end_time,
+        tracks: select_tracks(input, options.channel, channel_count)
+            .into_iter()
+            .map(|(label, source)| (label, TrackData::Samples(extract(source))))
+            .collect(),
+    })
+}
 
-    // プロット作成
-    let root = BitMapBackend::new(output.to_str().unwrap(), (1200, 600)).into_drawing_area();
-    root.fill(&BACKGROUND_COLOR)?;
+/// 描画する波形の元になるチャンネル
+#[derive(Clone, Copy)]
+enum TrackSource {
+    /// 全チャンネルの平均
+    Mix,
+    Channel(usize),
+}
 
+impl TrackSource {
+    fn value(self, frame: &[f32]) -> f32 {
+        match self {
+            TrackSource::Mix => frame.iter().sum::<f32>() / frame.len() as f32,
+            TrackSource::Channel(channel) => frame[channel.min(frame.len() - 1)],
+        }
+    }
+}
+
+/// チャンネル指定からパネルごとのラベルと元チャンネルを決める
+fn select_tracks(
+    input: &Path,
+    channel: WaveformChannel,
+    channel_count: usize,
+) -> Vec<(String, TrackSource)> {
     let title = input
         .file_name()
         .and_then(|name| name.to_str())
         .unwrap_or("Waveform");
 
+    match channel {
+        WaveformChannel::Left => vec![(format!("{} (L)", title), TrackSource::Channel(0))],
+        WaveformChannel::Right => vec![(format!("{} (R)", title), TrackSource::Channel(1))],
+        WaveformChannel::Split if channel_count >= 2 => vec![
+            (format!("{} (L)", title), TrackSource::Channel(0)),
+            (format!("{} (R)", title), TrackSource::Channel(1)),
+        ],
+        _ => vec![(title.to_string(), TrackSource::Mix)],
+    }
+}
+
+/// WAVをブロック単位で読みながら、画像幅分の列ごとにピーク・RMS・クリップを集計する
+fn stream_waveform(
+    input: &Path,
+    mut reader: WavReader<std::io::BufReader<std::fs::File>>,
+    options: &WaveformOptions,
+) -> Result<LoadedWaveform, AudioToolsError> {
+    let spec = reader.spec();
+    let sample_rate = spec.sample_rate as f32;
+    let channel_count = spec.channels as usize;
+    let total_frames = reader.duration() as usize;
+    let total_duration = total_frames as f32 / sample_rate;
+
+    let (start_time, end_time) = match &options.time_range {
+        Some(range) => range.resolve(total_duration)?,
+        None => (0.0, total_duration),
+    };
+    let range = sample_range(start_time, end_time, sample_rate, total_frames)?;
+    reader.seek(range.start as u32)?;
+
+    let frames = range.len();
+    let (width, _) = scaled_size(options.width, options.height, options.image_scale);
+    let bucket_size = frames.div_ceil(width as usize).max(1);
+    let rms_window = ((sample_rate * options.rms_window_ms / 1000.0) as usize).max(1);
+
+    let sources = select_tracks(input, options.channel, channel_count);
+    let mut builders: Vec<SummaryBuilder> = sources
+        .iter()
+        .map(|_| SummaryBuilder::new(bucket_size, rms_window, options.clip_threshold))
+        .collect();
+
+    let samples: Box<dyn Iterator<Item = Result<f32, hound::Error>>> = match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(reader.samples::<f32>()),
+        hound::SampleFormat::Int => Box::new(
+            reader
+                .samples::<i32>()
+                .map(move |s| s.map(|s| normalize_sample(s, spec.bits_per_sample))),
+        ),
+    };
+
+    let mut frame = Vec::with_capacity(channel_count);
+    for sample in samples.take(frames * channel_count) {
+        frame.push(sample?);
+        if frame.len() == channel_count {
+            for ((_, source), builder) in sources.iter().zip(builders.iter_mut()) {
+                builder.push(source.value(&frame));
+            }
+            frame.clear();
+        }
+    }
+
+    Ok(LoadedWaveform {
+        sample_rate,
+        start_time,
+        end_time,
+        tracks: sources
+            .into_iter()
+            .zip(builders)
+            .map(|((label, _), builder)| (label, TrackData::Summary(builder.finish())))
+            .collect(),
+    })
+}
+
+/// サンプルを1つずつ受け取り WaveformSummary を組み立てる
+struct SummaryBuilder {
+    bucket_size: usize,
+    rms_window: usize,
+    clip_threshold: f32,
+    position: usize,
+    sum_squares: f64,
+    window_len: usize,
+    /// 集計中のRMSウィンドウが始まった列
+    window_column: usize,
+    total_squares: f64,
+    summary: WaveformSummary,
+}
+
+impl SummaryBuilder {
+    fn new(bucket_size: usize, rms_window: usize, clip_threshold: f32) -> Self {
+        Self {
+            bucket_size,
+            rms_window,
+            clip_threshold,
+            position: 0,
+            sum_squares: 0.0,
+            window_len: 0,
+            window_column: 0,
+            total_squares: 0.0,
+            summary: WaveformSummary {
+                envelope: Vec::new(),
+                rms: Vec::new(),
+                total_rms: 0.0,
+                clip_runs: Vec::new(),
+            },
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        let column = self.position / self.bucket_size;
+        if column == self.summary.envelope.len() {
+            self.summary.envelope.push((sample, sample));
+            self.summary.rms.push(0.0);
+        } else {
+            let (min, max) = &mut self.summary.envelope[column];
+            *min = min.min(sample);
+            *max = max.max(sample);
+        }
+
+        // RMSはウィンドウごとに求め、列の中で最大のものを残す
+        if self.window_len == 0 {
+            self.window_column = column;
+        }
+        self.sum_squares += (sample * sample) as f64;
+        self.total_squares += (sample * sample) as f64;
+        self.window_len += 1;
+        if self.window_len == self.rms_window {
+            self.flush_rms(column);
+        }
+
+        if sample.abs() >= self.clip_threshold {
+            push_clip(&mut self.summary.clip_runs, self.position, sample);
+        }
+        self.position += 1;
+    }
+
+    /// ウィンドウのRMSを、ウィンドウが重なる全ての列（始まった列から column まで）に残す。
+    /// ウィンドウが列より長くても、途中の列のRMSが0にならない
+    fn flush_rms(&mut self, column: usize) {
+        let rms = (self.sum_squares / self.window_len as f64).sqrt() as f32;
+        for value in &mut self.summary.rms[self.window_column..=column] {
+            *value = value.max(rms);
+        }
+        self.sum_squares = 0.0;
+        self.window_len = 0;
+    }
+
+    fn finish(mut self) -> WaveformSummary {
+        if self.window_len > 0 {
+            let column = self.summary.rms.len() - 1;
+            self.flush_rms(column);
+        }
+        self.summary.total_rms = (self.total_squares / self.position.max(1) as f64).sqrt() as f32;
+        self.summary
+    }
+}
+
+/// 出力形式に応じたバックエンドで描画する
+fn render(
+    output: &Path,
+    panels: &[(&str, Vec<WaveformPlot>)],
+    options: &WaveformOptions,
+) -> Result<(), AudioToolsError> {
+    let size = scaled_size(options.width, options.height, options.image_scale);
+    match options.format {
+        ImageFormat::Png => {
+            let root = BitMapBackend::new(output, size).into_drawing_area();
+            draw_tracks(&root, panels, options)?;
+            root.present()?;
+        }
+        ImageFormat::Svg => {
+            let root = SVGBackend::new(output, size).into_drawing_area();
+            draw_tracks(&root, panels, options)?;
+            root.present()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// チャンネルごとの波形を縦に並べて描画する
+fn draw_tracks<DB>(
+    root: &DrawingArea<DB, Shift>,
+    panels: &[(&str, Vec<WaveformPlot>)],
+    options: &WaveformOptions,
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&options.colors.background)?;
+
+    let areas = root.split_evenly((panels.len(), 1));
+    for (area, (title, plots)) in areas.iter().zip(panels.iter()) {
+        draw_waveform(area, title, plots, options)?;
+    }
+
+    Ok(())
+}
+
+/// 描画対象の波形データ
+struct WaveformPlot<'a> {
+    title: &'a str,
+    data: &'a TrackData,
+    sample_rate: f32,
+    start_time: f32,
+    end_time: f32,
+    color: RGBAColor,
+}
+
+/// 描画バックエンドに依存せず波形を描画する
+///
+/// 複数の波形を渡すと同じ軸に重ねて描画し、凡例を表示する。
+/// 時間軸は全波形の範囲を含むよう最も長いものに合わせる。
+fn draw_waveform<DB>(
+    root: &DrawingArea<DB, Shift>,
+    title: &str,
+    plots: &[WaveformPlot],
+    options: &WaveformOptions,
+) -> Result<(), AudioToolsError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let scale = options.scale;
+    let start_time = plots
+        .iter()
+        .map(|plot| plot.start_time)
+        .fold(f32::MAX, f32::min);
+    let end_time = plots
+        .iter()
+        .map(|plot| plot.end_time)
+        .fold(f32::MIN, f32::max);
+    let overlay = plots.len() > 1;
+    let colors = &options.colors;
+    let font = options.font.as_str();
+    // 余白・文字・線幅は --image-scale に合わせて大きくする
+    let px = options.image_scale;
+    let line_width = px.round() as u32;
+
     // Y軸の範囲設定
     let (y_min, y_max) = match scale {
         WaveformScale::Amplitude => (-1.0, 1.0),
-        WaveformScale::Decibel => (-60.0, 0.0),
+        WaveformScale::Decibel => (options.db_floor, 0.0),
     };
 
-    let mut chart = ChartBuilder::on(&root)
-        .margin(40)
-        .caption(title, (FONT_FAMILY, 24).into_font().color(&WHITE))
-        .set_label_area_size(LabelAreaPosition::Left, 60)
-        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+    let mut chart = ChartBuilder::on(root)
+        .margin(40.0 * px)
+        .caption(
+            title,
+            (font, 24.0 * px).into_font().color(&colors.foreground),
+        )
+        .set_label_area_size(LabelAreaPosition::Left, 60.0 * px)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40.0 * px)
         .build_cartesian_2d(start_time..end_time, y_min..y_max)?;
 
-    /// 時間の長さに応じて適切なグリッド間隔を決定する
-    fn calculate_grid_interval(duration: f32) -> f32 {
+    /// 時間の長さと描画幅に応じて適切なグリッド間隔を決定する
+    fn calculate_grid_interval(duration: f32, plot_width: f32) -> f32 {
         let intervals = [
             0.001, 0.002, 0.005, // ミリ秒単位
             0.01, 0.02, 0.05, // 10ミリ秒単位
@@ -169,13 +737,15 @@ pub fn create_waveform(
             1.0, 2.0, 5.0, // 秒単位
             10.0, 20.0, 30.0, // 10秒単位
             60.0, 120.0, 300.0, // 分単位
+            600.0, 1200.0, 1800.0, 3600.0, // 10分以上
         ];
 
-        // 目標とするグリッド数（画面の見やすさを考慮）
-        const TARGET_GRID_COUNT: f32 = 10.0;
+        // ラベル1つあたりの目標幅（ピクセル）。画像幅に応じてグリッド数を増減させる
+        const PIXELS_PER_GRID: f32 = 100.0;
+        let target_grid_count = (plot_width / PIXELS_PER_GRID).max(2.0);
 
         // 最適な間隔を探す
-        let ideal_interval = duration / TARGET_GRID_COUNT;
+        let ideal_interval = duration / target_grid_count;
 
         // 理想的な間隔に最も近い定義済み間隔を選択
         intervals
@@ -191,15 +761,17 @@ pub fn create_waveform(
 
     // グリッドとラベルの設定
     let duration = end_time - start_time;
-    let grid_interval = calculate_grid_interval(duration);
+    // 倍率を掛ける前の幅で数え、拡大してもグリッドの数を変えない
+    let plot_width = chart.plotting_area().dim_in_pixel().0 as f32 / px;
+    let grid_interval = calculate_grid_interval(duration, plot_width);
     let grid_count = (duration / grid_interval).ceil() as usize;
 
     chart
         .configure_mesh()
-        .label_style((FONT_FAMILY, 14).into_font().color(&WHITE))
-        .axis_style(&WHITE)
-        // .light_line_style(RGBAColor(255, 255, 255, 0.1))
-        .bold_line_style(GRID_COLOR)
+        .label_style((font, 14.0 * px).into_font().color(&colors.foreground))
+        .axis_style(colors.foreground.stroke_width(line_width))
+        .light_line_style(TRANSPARENT)
+        .bold_line_style(colors.grid.stroke_width(line_width))
         .x_desc("Time (s)")
         .y_desc(match scale {
             WaveformScale::Amplitude => "Amplitude",
@@ -208,7 +780,7 @@ pub fn create_waveform(
         .x_labels(grid_count)
         .y_labels(match scale {
             WaveformScale::Amplitude => 5, // -1.0から1.0まで0.5間隔（5区間）
-            WaveformScale::Decibel => 10,  // -60dBから0dBまで6dB間隔
+            WaveformScale::Decibel => 10,  // フロアから0dBまで10区間
         })
         .x_label_formatter(&|x| {
             // グリッド間隔に応じてフォーマットを調整
@@ -224,60 +796,162 @@ pub fn create_waveform(
         })
         .draw()?;
 
-    // 波形の描画
-    let time_points: Vec<f32> = (0..samples.len())
-        .map(|i| start_time + i as f32 / sample_rate)
-        .collect();
+    for plot in plots {
+        let sample_rate = plot.sample_rate;
+        let start_time = plot.start_time;
+        let end_time = plot.end_time;
+        let color = plot.color;
+        // 重ね描きでは下の波形が透けて見えるよう半透明にする
+        let peak_style = if overlay {
+            color.mix(0.6).filled()
+        } else {
+            color.filled()
+        };
+        let rms_color = if overlay { color.mix(0.3) } else { colors.rms };
 
-    // RMS波形の描画を条件付きに
-    if show_rms {
-        let rms_points: Vec<(f32, f32)> = time_points
-            .iter()
-            .zip(rms_values.iter())
-            .map(|(&t, &rms)| match scale {
+        // ピーク波形の描画
+        let series = if let (true, TrackData::Samples(samples)) = (options.raw, plot.data) {
+            // 全サンプルを折れ線で描画（短いクリップ向け）
+            let peak_points: Vec<(f32, f32)> = samples
+                .iter()
+                .enumerate()
+                .map(|(i, &sample)| {
+                    let t = start_time + i as f32 / sample_rate;
+                    match scale {
+                        WaveformScale::Amplitude => (t, sample),
+                        WaveformScale::Decibel => (t, amplitude_to_db(sample, options.db_floor)),
+                    }
+                })
+                .collect();
+
+            chart.draw_series(LineSeries::new(
+                peak_points.iter().map(|&(x, y)| (x, y)),
+                peak_style.stroke_width(line_width),
+            ))?
+        } else {
+            // 描画幅1ピクセルごとの最小値・最大値を縦棒で描画する
+            let envelope = match plot.data {
+                TrackData::Samples(samples) => Cow::Owned(calculate_envelope(
+                    samples,
+                    chart.plotting_area().dim_in_pixel().0 as usize,
+                )),
+                TrackData::Summary(summary) => Cow::Borrowed(&summary.envelope),
+            };
+            let column_duration = (end_time - start_time) / envelope.len().max(1) as f32;
+
+            chart.draw_series(envelope.iter().enumerate().map(|(i, &(min, max))| {
+                let t0 = start_time + i as f32 * column_duration;
+                // 丸め誤差で列の間に隙間ができないよう隣の列まで重ねて描く
+                let t1 = (t0 + column_duration * 2.0).min(end_time);
+                let (low, high) = match scale {
+                    WaveformScale::Amplitude => (min, max),
+                    WaveformScale::Decibel => (
+                        y_min,
+                        amplitude_to_db(min.abs().max(max.abs()), options.db_floor),
+                    ),
+                };
+                Rectangle::new([(t0, low), (t1, high)], peak_style)
+            }))?
+        };
+
+        // 重ね描きでは凡例に波形の色とファイル名を対応させる
+        if overlay {
+            let (half, width) = ((5.0 * px) as i32, (10.0 * px) as i32);
+            series.label(plot.title).legend(move |(x, y)| {
+                Rectangle::new([(x, y - half), (x + width, y + half)], color.filled())
+            });
+        }
+
+        // RMS波形の描画を条件付きに（ピーク波形の上に重ねる）
+        if options.show_rms {
+            let rms_points: Vec<(f32, f32)> = match plot.data {
+                TrackData::Samples(samples) => calculate_rms(
+                    samples,
+                    rms_window_size(sample_rate, options, samples.len()),
+                )
+                .into_iter()
+                .enumerate()
+                .map(|(i, rms)| (start_time + i as f32 / sample_rate, rms))
+                .collect::<Vec<_>>(),
+                // 集計済みの場合は列の中央に置く
+                TrackData::Summary(summary) => {
+                    let column_duration = (end_time - start_time) / summary.rms.len().max(1) as f32;
+                    summary
+                        .rms
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &rms)| (start_time + (i as f32 + 0.5) * column_duration, rms))
+                        .collect::<Vec<_>>()
+                }
+            }
+            .into_iter()
+            .map(|(t, rms)| match scale {
                 WaveformScale::Amplitude => (t, rms),
-                WaveformScale::Decibel => (t, amplitude_to_db(rms)),
+                WaveformScale::Decibel => (t, amplitude_to_db(rms, options.db_floor)),
             })
             .collect();
 
-        chart.draw_series(AreaSeries::new(
-            rms_points.iter().map(|&(x, y)| (x, y)),
-            0.0,
-            &RMS_COLOR,
-        ))?;
-    }
+            chart.draw_series(AreaSeries::new(
+                rms_points.iter().map(|&(x, y)| (x, y)),
+                match scale {
+                    WaveformScale::Amplitude => 0.0,
+                    WaveformScale::Decibel => y_min,
+                },
+                rms_color,
+            ))?;
+        }
 
-    // ピーク波形の描画
-    let peak_points: Vec<(f32, f32)> = time_points
-        .iter()
-        .zip(samples.iter())
-        .map(|(&t, &sample)| match scale {
-            WaveformScale::Amplitude => (t, sample),
-            WaveformScale::Decibel => (t, amplitude_to_db(sample)),
-        })
-        .collect();
+        // クリップしているサンプルのマーカー描画（集計済みの場合は区間ごとに1つ）
+        if options.show_clipping {
+            let markers: Vec<(usize, f32)> = match plot.data {
+                TrackData::Samples(samples) => find_clip_runs(samples, options.clip_threshold)
+                    .iter()
+                    .flat_map(|run| (run.start..run.start + run.len).map(|i| (i, samples[i])))
+                    .collect(),
+                TrackData::Summary(summary) => summary
+                    .clip_runs
+                    .iter()
+                    .map(|run| (run.start, run.value))
+                    .collect(),
+            };
+            chart.draw_series(markers.into_iter().map(|(i, value)| {
+                let t = start_time + i as f32 / sample_rate;
+                let y = match scale {
+                    WaveformScale::Amplitude => value,
+                    WaveformScale::Decibel => amplitude_to_db(value, options.db_floor),
+                };
+                Circle::new((t, y), 3.0 * px, colors.clip.filled())
+            }))?;
+        }
+    }
 
-    chart.draw_series(LineSeries::new(
-        peak_points.iter().map(|&(x, y)| (x, y)),
-        &PEAK_COLOR,
-    ))?;
+    if overlay {
+        chart
+            .configure_series_labels()
+            .background_style(colors.background.mix(0.8))
+            .border_style(colors.foreground)
+            .label_font((font, 14.0 * px).into_font().color(&colors.foreground))
+            .position(SeriesLabelPosition::UpperRight)
+            .draw()?;
+    }
 
     // アノテーションの描画
-    if let Some(annotations) = annotations {
-        for (time, label) in annotations {
+    if let Some(annotations) = &options.annotations {
+        for (time, label) in annotations.iter() {
+            let time = *time;
             if time >= start_time && time <= end_time {
                 // 垂直線の描画
                 chart.draw_series(LineSeries::new(
                     vec![(time, y_min), (time, y_max)],
-                    &ANNOTATION_COLOR,
+                    colors.annotation.stroke_width(line_width),
                 ))?;
 
                 // ラベルの描画
                 let label_y = y_max - (y_max - y_min) * 0.1;
                 chart.draw_series(std::iter::once(Text::new(
-                    label,
+                    label.clone(),
                     (time, label_y),
-                    (FONT_FAMILY, 16).into_font().color(&ANNOTATION_COLOR),
+                    (font, 16.0 * px).into_font().color(&colors.annotation),
                 )))?;
             }
         }
@@ -286,15 +960,135 @@ pub fn create_waveform(
     Ok(())
 }
 
+/// 絶対値がしきい値以上のサンプルを連続区間ごとにまとめる
+fn find_clip_runs(samples: &[f32], threshold: f32) -> Vec<ClipRun> {
+    let mut runs = Vec::new();
+    for (i, &sample) in samples.iter().enumerate() {
+        if sample.abs() >= threshold {
+            push_clip(&mut runs, i, sample);
+        }
+    }
+    runs
+}
+
+/// クリップしたサンプルを直前の区間に連結するか、新しい区間を作る
+fn push_clip(runs: &mut Vec<ClipRun>, position: usize, sample: f32) {
+    match runs.last_mut() {
+        Some(run) if run.start + run.len == position => run.len += 1,
+        _ => runs.push(ClipRun {
+            start: position,
+            len: 1,
+            value: sample,
+        }),
+    }
+}
+
+/// クリップ数と発生時刻を標準出力に表示する（連続するサンプルは1区間にまとめる）
+fn report_clipping(plot: &WaveformPlot, threshold: f32) {
+    let computed;
+    let runs = match plot.data {
+        TrackData::Samples(samples) => {
+            computed = find_clip_runs(samples, threshold);
+            &computed
+        }
+        TrackData::Summary(summary) => &summary.clip_runs,
+    };
+    println!(
+        "Clipping in {}: {} samples at or above {:.3} ({:.1} dBFS)",
+        plot.title,
+        runs.iter().map(|run| run.len).sum::<usize>(),
+        threshold,
+        20.0 * threshold.log10()
+    );
+
+    for run in runs {
+        println!(
+            "  {:.4}s ({} samples)",
+            plot.start_time + run.start as f32 / plot.sample_rate,
+            run.len
+        );
+    }
+}
+
+/// 画像を書き出さない場合に、トラックごとの範囲・ピーク・RMS・クリップ数を表示する
+fn report_metrics(plots: &[WaveformPlot], options: &WaveformOptions) {
+    let db = |amplitude: f32| 20.0 * amplitude.max(1e-10).log10();
+
+    for plot in plots {
+        let (peak, rms, clipped) = match plot.data {
+            TrackData::Samples(samples) => {
+                let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+                let sum_squares: f64 = samples.iter().map(|&s| (s * s) as f64).sum();
+                let rms = (sum_squares / samples.len().max(1) as f64).sqrt() as f32;
+                let clipped = find_clip_runs(samples, options.clip_threshold)
+                    .iter()
+                    .map(|run| run.len)
+                    .sum::<usize>();
+                (peak, rms, clipped)
+            }
+            TrackData::Summary(summary) => (
+                summary.envelope.iter().fold(0.0f32, |peak, &(min, max)| {
+                    peak.max(min.abs()).max(max.abs())
+                }),
+                summary.total_rms,
+                summary.clip_runs.iter().map(|run| run.len).sum(),
+            ),
+        };
+
+        if options.auto_start.is_some() {
+            println!("{}: detected start {:.3}s", plot.title, plot.start_time);
+        }
+        println!(
+            "{}: {:.3}s - {:.3}s, peak {:.1} dBFS, RMS {:.1} dBFS, {} clipped samples",
+            plot.title,
+            plot.start_time,
+            plot.end_time,
+            db(peak),
+            db(rms),
+            clipped
+        );
+    }
+}
+
+/// サンプルを columns 個の区間に分け、区間ごとの (最小値, 最大値) を求める
+fn calculate_envelope(samples: &[f32], columns: usize) -> Vec<(f32, f32)> {
+    if samples.is_empty() || columns == 0 {
+        return Vec::new();
+    }
+
+    let bucket_size = samples.len().div_ceil(columns);
+    samples
+        .chunks(bucket_size)
+        .map(|bucket| {
+            bucket.iter().fold((f32::MAX, f32::MIN), |(min, max), &s| {
+                (min.min(s), max.max(s))
+            })
+        })
+        .collect()
+}
+
+/// --rms-window-ms をサンプル数にする。1サンプル未満は1サンプルに、
+/// 選択範囲より長い場合は警告して選択範囲の長さに制限する
+fn rms_window_size(sample_rate: f32, options: &WaveformOptions, frames: usize) -> usize {
+    let window_size = ((sample_rate * options.rms_window_ms / 1000.0) as usize).max(1);
+    if window_size > frames.max(1) {
+        warn!(
+            "RMS window of {} ms is longer than the selected range ({:.3} s); using the whole range",
+            options.rms_window_ms,
+            frames as f32 / sample_rate
+        );
+        return frames.max(1);
+    }
+    window_size
+}
+
+/// 各サンプルを中心とする window_size サンプルのRMS（端では範囲内のサンプルだけを使う）
 fn calculate_rms(samples: &[f32], window_size: usize) -> Vec<f32> {
     let mut rms_values = Vec::with_capacity(samples.len());
     for i in 0..samples.len() {
-        let start = if i < window_size / 2 {
-            0
-        } else {
-            i - window_size / 2
-        };
-        let end = (i + window_size / 2).min(samples.len());
+        let start = i.saturating_sub(window_size / 2);
+        // 奇数の長さでも window_size サンプルになるよう、後ろ側を切り上げる
+        let end = (i + window_size.div_ceil(2)).min(samples.len());
 
         let sum_squares: f32 = samples[start..end].iter().map(|&x| x * x).sum();
         let rms = (sum_squares / (end - start) as f32).sqrt();
@@ -303,10 +1097,37 @@ fn calculate_rms(samples: &[f32], window_size: usize) -> Vec<f32> {
     rms_values
 }
 
-fn amplitude_to_db(amplitude: f32) -> f32 {
-    if amplitude.abs() < 1e-6 {
-        -60.0
+/// 振幅をdBに変換する（floor 未満は floor に丸める）
+fn amplitude_to_db(amplitude: f32, floor: f32) -> f32 {
+    if amplitude.abs() <= 0.0 {
+        floor
     } else {
-        (20.0 * amplitude.abs().log10()).max(-60.0)
+        (20.0 * amplitude.abs().log10()).max(floor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_rms_window_covers_every_column() {
+        // 10 サンプルの列に 25 サンプルのウィンドウ
+        let mut builder = SummaryBuilder::new(10, 25, 1.0);
+        for _ in 0..100 {
+            builder.push(0.5);
+        }
+        let summary = builder.finish();
+        assert_eq!(summary.rms.len(), 10);
+        for rms in summary.rms {
+            assert!((rms - 0.5).abs() < 1e-6, "column RMS {}", rms);
+        }
+    }
+
+    #[test]
+    fn rms_window_of_one_sample() {
+        // 1サンプルのウィンドウは各サンプルの絶対値になる（0 / 0 の NaN にならない）
+        let rms = calculate_rms(&[0.5, -0.5, 0.25], 1);
+        assert_eq!(rms, vec![0.5, 0.5, 0.25]);
     }
 }
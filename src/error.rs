@@ -0,0 +1,39 @@
+use plotters::drawing::DrawingAreaErrorKind;
+use thiserror::Error;
+
+// Error type shared by the library API and the command modules
+#[derive(Debug, Error)]
+pub enum AudioToolsError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to decode WAV: {0}")]
+    WavDecode(#[from] hound::Error),
+
+    // ffmpeg/ffprobe could not be started or exited with an error
+    #[error("ffmpeg failed: {0}")]
+    Ffmpeg(String),
+
+    #[error("{0}")]
+    TimeRange(String),
+
+    #[error("Failed to draw plot: {0}")]
+    Plot(String),
+
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+
+    // Option values or inputs that can't be processed as given
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+// plotters reports errors generically over the drawing backend
+impl<E> From<DrawingAreaErrorKind<E>> for AudioToolsError
+where
+    E: std::error::Error + Send + Sync,
+{
+    fn from(error: DrawingAreaErrorKind<E>) -> Self {
+        AudioToolsError::Plot(error.to_string())
+    }
+}
@@ -1,6 +1,16 @@
 pub mod command;
+pub mod error;
 pub mod utils;
 
+pub use command::convert::{convert_file, convert_files, ConvertOptions};
+pub use command::info::{audio_info, AudioInfo, InfoOptions};
+pub use command::loudness::{measure_loudness_file, LoudnessInfo};
+pub use command::normalize::{normalize_file, normalize_files, NormalizeOptions, WavSampleFormat};
+pub use command::spectrum::{create_spectrogram as render_spectrogram, SpectrogramOptions};
+pub use command::waveform::{create_waveform as render_waveform, WaveformOptions};
+pub use error::AudioToolsError;
+pub use utils::progress::{BatchReport, FileOutcome, ProgressCallback, ProgressEvent};
+
 pub const AUDIO_EXTENSIONS: &[&str] = &[
     "wav", "flac", "mp3", "aac", "m4a", "ogg", "wma", "aiff", "alac", "opus",
 ];
@@ -1,14 +1,44 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{ArgGroup, Parser, Subcommand};
+use log::{error, info};
+use plotters::style::RGBAColor;
+use std::path::{Path, PathBuf};
 
 use audiotools::command::{
-    convert, info, loudness, normalize,
-    spectrum::{self, parse_frequency_annotation},
-    waveform::{self, parse_time_annotation, WaveformScale},
+    batch,
+    concat::{self, ConcatOptions},
+    convert::{
+        self, ChannelLayout, ConvertOptions, DownmixMode, FadeCurve, LoudnormTarget, MonoCheck,
+        OverwritePolicy, ResamplerQuality, SilenceTrim,
+    },
+    detect::{self, OnsetOptions},
+    info::{self, InfoOptions, InfoOutputFormat, SilenceDetection},
+    loudness::{self, LoudnessOptions, LoudnessOutputFormat, LoudnessTarget, TargetPreset},
+    loudness_graph::{self, LoudnessGraphOptions},
+    normalize::{self, NormalizeOptions, WavSampleFormat},
+    roundtrip::{self, RoundtripOptions},
+    spectrum::{
+        self, parse_frequency_annotation, parse_max_freq, ExportFormat, ExportUnits,
+        FrequencyScale, MaxFrequency, SpectrogramOptions, WindowFunction,
+    },
+    split::{self, SplitMode, SplitOptions},
+    trim::{self, TrimOptions},
+    verify,
+    waveform::{self, parse_time_annotation, WaveformChannel, WaveformOptions, WaveformScale},
 };
 
-use audiotools::utils::detection;
+use audiotools::error::AudioToolsError;
+use audiotools::utils::archive::{is_archive, ExtractedArchive};
+use audiotools::utils::detection::{self, AutoStartDetection};
+use audiotools::utils::hash::HashAlgorithm;
+use audiotools::utils::logging::{self, Verbosity};
+use audiotools::utils::plot::{
+    merge_annotations, metadata_path, parse_color, resolve_font, ImageFormat, ImageOutputOptions,
+    PlotColors, ThemeOptions,
+};
+use audiotools::utils::progress::log_progress;
+use audiotools::utils::stdin::StdinWav;
 use audiotools::utils::time::{self, TimeSpecification};
+use audiotools::utils::{check_dependencies, ffmpeg, is_stdio, parallel, WalkOptions};
 
 // Define CLI application structure using clap
 #[derive(Parser)]
@@ -16,6 +46,17 @@ use audiotools::utils::time::{self, TimeSpecification};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Path to the ffmpeg executable (overrides AUDIOTOOLS_FFMPEG)
+    #[arg(long, global = true)]
+    ffmpeg: Option<PathBuf>,
+
+    /// Path to the ffprobe executable (overrides AUDIOTOOLS_FFPROBE)
+    #[arg(long, global = true)]
+    ffprobe: Option<PathBuf>,
+
+    #[command(flatten)]
+    verbosity: Verbosity,
 }
 
 // Define available subcommands and their arguments
@@ -23,9 +64,9 @@ struct Cli {
 enum Commands {
     /// Convert audio files between formats
     Convert {
-        /// Input directory or file path
-        #[arg(short, long)]
-        input: PathBuf,
+        /// Input files or directories (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
 
         /// Output directory path
         #[arg(short, long)]
@@ -47,10 +88,18 @@ enum Commands {
         #[arg(short, long, default_value = "16")]
         bit_depth: u8,
 
+        /// MP3 bitrate (e.g. 128k, 192k; default 320k)
+        #[arg(long)]
+        bitrate: Option<String>,
+
         /// Target sample rate for conversion
         #[arg(short, long)]
         sample_rate: Option<u32>,
 
+        /// Use the soxr resampler at this quality when changing sample rate
+        #[arg(long, value_enum, requires = "sample_rate")]
+        resampler_quality: Option<ResamplerQuality>,
+
         /// Prefix to add to output filenames
         #[arg(long)]
         prefix: Option<String>,
@@ -59,62 +108,520 @@ enum Commands {
         #[arg(long)]
         postfix: Option<String>,
 
-        /// Process directories recursively
-        #[arg(short, long)]
-        recursive: bool,
+        #[command(flatten)]
+        walk: WalkOptions,
 
-        /// Force overwrite of existing files
-        #[arg(long)]
+        /// Force overwrite of existing files (same as --overwrite-policy overwrite)
+        #[arg(long, conflicts_with = "overwrite_policy")]
         force: bool,
 
-        /// Number of output channels (1=mono, 2=stereo)
-        #[arg(long, value_name = "CHANNELS")]
-        channels: Option<u8>,
+        /// What to do when an output file already exists: skip it, overwrite it,
+        /// rename the new file (name_1.wav, ...), or overwrite only if the source is newer
+        #[arg(long, value_enum, default_value = "skip")]
+        overwrite_policy: OverwritePolicy,
+
+        /// Output channels: a count (1-8) or a layout name (mono, stereo, 2.1, quad,
+        /// 5.0, 5.1, 6.1, 7.1). Upmixing keeps the source channels in place and leaves
+        /// the extra channels silent; mono sources are duplicated to front left/right
+        #[arg(long, value_name = "CHANNELS", value_parser = convert::parse_channel_layout)]
+        channels: Option<ChannelLayout>,
+
+        /// Channel conversion method: "pan" applies --downmix-gain when summing or
+        /// duplicating channels, "none" leaves it to ffmpeg's default conversion
+        #[arg(long, value_enum, default_value = "pan")]
+        downmix: DownmixMode,
+
+        /// Gain in dB applied to each channel when converting channel counts.
+        /// -3 dB keeps uncorrelated stereo at the same loudness when summed to mono;
+        /// correlated (centered) material rises up to +3 dB, and 0 dB may clip.
+        /// Out-of-phase content cancels when summed regardless of gain.
+        #[arg(long, default_value_t = -3.0, allow_negative_numbers = true)]
+        downmix_gain: f32,
 
         /// Target peak level in dBFS (e.g., -1.0)
         #[arg(short = 'l', long = "level", allow_negative_numbers = true)]
         normalize_level: Option<f32>,
+
+        /// Fixed gain in dB (e.g., -6 or 3.5), instead of normalizing to --level
+        #[arg(
+            long,
+            allow_negative_numbers = true,
+            conflicts_with = "normalize_level"
+        )]
+        gain: Option<f32>,
+
+        /// Two-pass loudness normalization to I:TP:LRA (LUFS, dBTP, LU; e.g., -16:-1.5:11)
+        #[arg(
+            long,
+            value_name = "I:TP:LRA",
+            allow_hyphen_values = true,
+            value_parser = convert::parse_loudnorm_target,
+            conflicts_with_all = ["normalize_level", "gain"]
+        )]
+        loudnorm: Option<LoudnormTarget>,
+
+        /// Remove DC offset with a 5 Hz high-pass filter
+        #[arg(long)]
+        remove_dc: bool,
+
+        /// Remove leading and trailing silence
+        #[arg(long)]
+        trim_silence: bool,
+
+        /// Level below which audio counts as silence for --trim-silence (dBFS)
+        #[arg(long, default_value_t = -60.0, allow_negative_numbers = true)]
+        silence_threshold: f32,
+
+        /// Minimum length of silence to remove for --trim-silence (seconds)
+        #[arg(long, default_value = "0.05")]
+        silence_duration: f32,
+
+        /// Cut everything before the detected start of sound (WAV only)
+        #[arg(long)]
+        auto_trim: bool,
+
+        /// Amplitude threshold for --auto-trim start detection
+        #[arg(long, default_value = "0.01")]
+        threshold: f32,
+
+        /// Window size for --auto-trim start detection
+        #[arg(long, default_value = "512")]
+        detection_window: usize,
+
+        /// Minimum duration for --auto-trim start detection (seconds)
+        #[arg(long, default_value = "0.01")]
+        min_duration: f32,
+
+        /// Fade-in length in milliseconds
+        #[arg(long)]
+        fade_in: Option<f32>,
+
+        /// Fade-out length in milliseconds
+        #[arg(long)]
+        fade_out: Option<f32>,
+
+        /// Fade curve shape
+        #[arg(long, value_enum, default_value = "tri")]
+        fade_curve: FadeCurve,
+
+        /// Report how stereo inputs change when folded to mono with --downmix-gain
+        /// (peak and RMS, stereo vs summed mono) instead of converting them
+        #[arg(long)]
+        mono_check: bool,
+
+        /// Run --mono-check and also write each input converted to mono
+        #[arg(long, conflicts_with_all = ["channels", "downmix"])]
+        mono_check_write: bool,
+
+        /// Warn when the mono fold lowers the RMS level by more than this (dB)
+        #[arg(long, default_value = "3.0")]
+        mono_drop_threshold: f32,
+
+        /// Warn when the mono fold raises the peak by more than this (dB)
+        #[arg(long, default_value = "1.0")]
+        mono_peak_threshold: f32,
+
+        /// Give each output the modification and access times of its source
+        #[arg(long)]
+        preserve_timestamps: bool,
+    },
+
+    /// Join audio files end to end into one file
+    Concat {
+        /// Input files or directories (directories are expanded in --sort order)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Output file path (format is taken from the extension: wav, flac, or mp3)
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Input formats to include from directories (e.g., wav,flac,mp3)
+        #[arg(short = 'I', long, value_delimiter = ',', default_value = "wav")]
+        input_format: Vec<String>,
+
+        /// Crossfade length between segments in milliseconds
+        #[arg(long)]
+        crossfade: Option<f32>,
+
+        /// Resample all inputs to this sample rate
+        #[arg(short, long)]
+        sample_rate: Option<u32>,
+
+        /// Output bit depth for WAV files
+        #[arg(short, long, default_value = "16")]
+        bit_depth: u8,
+
+        #[command(flatten)]
+        walk: WalkOptions,
+
+        /// Force overwrite of existing files
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Cut one WAV file into numbered segments
+    #[command(group(ArgGroup::new("mode").required(true).args(["every", "at", "on_silence"])))]
+    Split {
+        /// Input WAV file(s) (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Output directory (defaults to the input file's directory)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Cut into fixed-length chunks (seconds, MM:SS format, or percentage with %)
+        #[arg(long, value_parser = time::parse_time_specification)]
+        every: Option<TimeSpecification>,
+
+        /// Cut at explicit points (comma-separated, seconds, MM:SS, or percentage with %)
+        #[arg(long, value_parser = time::parse_time_specification, value_delimiter = ',')]
+        at: Option<Vec<TimeSpecification>>,
+
+        /// Cut into separate sounds at silent gaps
+        #[arg(long)]
+        on_silence: bool,
+
+        /// Amplitude threshold for sound detection
+        #[arg(long, default_value = "0.01")]
+        threshold: f32,
+
+        /// Window size for sound detection
+        #[arg(long, default_value = "512")]
+        detection_window: usize,
+
+        /// Minimum duration of a sound (seconds)
+        #[arg(long, default_value = "0.01")]
+        min_duration: f32,
+
+        /// Minimum silent gap that separates two sounds (seconds); a new sound can
+        /// only start after the level has stayed below the release threshold this long
+        #[arg(long, visible_alias = "gap-duration", default_value = "0.25")]
+        min_silence: f32,
+
+        /// Lower the release (silence) threshold this many dB below --threshold, like a
+        /// noise gate, so reverb tails and noise hovering at the threshold don't cut sounds
+        #[arg(long, default_value = "0.0")]
+        hysteresis: f32,
+
+        /// Force overwrite of existing files
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Write a time range of one file to a new file
+    Trim {
+        /// Input audio file(s) (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Output file path (default: <input>_trimmed.<ext>)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Start time (seconds, MM:SS format, or percentage with %)
+        #[arg(long, value_parser = time::parse_time_specification)]
+        start: Option<TimeSpecification>,
+
+        /// End time (seconds, MM:SS format, or percentage with %)
+        #[arg(long, value_parser = time::parse_time_specification)]
+        end: Option<TimeSpecification>,
+
+        /// Enable automatic start detection (WAV only)
+        #[arg(long)]
+        auto_start: bool,
+
+        /// Amplitude threshold for auto start detection
+        #[arg(long, default_value = "0.01")]
+        threshold: f32,
+
+        /// Window size for auto start detection
+        #[arg(long, default_value = "512")]
+        detection_window: usize,
+
+        /// Minimum duration for auto start detection (seconds)
+        #[arg(long, default_value = "0.01")]
+        min_duration: f32,
+
+        /// Force overwrite of existing files
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List the onset time of every sound in one file
+    Detect {
+        /// Input audio file(s) (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Write the onsets as a "time,label" CSV for waveform --annotations-file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Print only the first onset: the start that --auto-start uses in waveform,
+        /// spectrum, trim and convert --auto-trim
+        #[arg(long)]
+        first: bool,
+
+        /// Amplitude threshold for sound detection
+        #[arg(long, default_value = "0.01")]
+        threshold: f32,
+
+        /// Window size for sound detection
+        #[arg(long, default_value = "512")]
+        detection_window: usize,
+
+        /// Minimum duration of a sound (seconds)
+        #[arg(long, default_value = "0.01")]
+        min_duration: f32,
+
+        /// Minimum silent gap before the next onset (seconds); the level must stay below
+        /// the release threshold this long before a new onset can trigger
+        #[arg(long, visible_alias = "gap-duration", default_value = "0.05")]
+        min_silence: f32,
+
+        /// Lower the release (silence) threshold this many dB below --threshold, like a
+        /// noise gate, so reverb tails and noise hovering at the threshold don't retrigger
+        #[arg(long, default_value = "0.0")]
+        hysteresis: f32,
+    },
+
+    /// Check WAV files for structural damage (RIFF and chunk sizes, truncation, alignment)
+    Verify {
+        /// Input files or directories (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        #[command(flatten)]
+        walk: WalkOptions,
+    },
+
+    /// Encode files to a codec and back, and report the error against the original
+    Roundtrip {
+        /// Input files or directories (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Codec to test (mp3, flac, ogg)
+        #[arg(long, default_value = "mp3")]
+        format: String,
+
+        /// MP3 bitrate (e.g. 128k, 192k; default 320k)
+        #[arg(long)]
+        bitrate: Option<String>,
+
+        /// Save a difference spectrogram (decoded minus original) for each file
+        #[arg(long)]
+        diff_image: bool,
+
+        /// Directory for the difference spectrograms (default: next to each input)
+        #[arg(long, requires = "diff_image")]
+        output_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        theme: ThemeOptions,
+
+        #[command(flatten)]
+        walk: WalkOptions,
     },
 
     /// Display audio file information
     Info {
-        /// Input directory or file path
-        #[arg(short, long)]
-        input: PathBuf,
+        /// Input files or directories (comma-separated or repeated), or - for a WAV stream on stdin
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
 
-        /// Output file for information
+        /// Output file for information (- or omitted: stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Fields to display in output
+        /// ffprobe fields to display in text output (prefix with format. or stream. to choose the section)
         #[arg(short, long, value_delimiter = ',')]
         fields: Vec<String>,
 
-        /// Process directories recursively
+        /// Output format (text blocks or one CSV row per file)
+        #[arg(long, value_enum, default_value = "text")]
+        output_format: InfoOutputFormat,
+
+        /// Include a checksum of each file
+        #[arg(long, value_enum, default_value = "none")]
+        hash: HashAlgorithm,
+
+        /// For WAV files, hash only the PCM data chunk so metadata changes are ignored
+        #[arg(long, requires = "hash")]
+        audio_hash: bool,
+
+        /// Measure the DC offset (mean sample value) of each channel
+        #[arg(long)]
+        detect_dc: bool,
+
+        /// Measure the spectral centroid and 85% rolloff frequency (brightness)
+        #[arg(long)]
+        spectral: bool,
+
+        /// Measure the L/R phase correlation and stereo width of 2-channel files
+        #[arg(long)]
+        stereo_metrics: bool,
+
+        /// Measure the peak and RMS level (dBFS) of each channel separately
+        #[arg(long)]
+        per_channel: bool,
+
+        /// Report how long the leading and trailing silence of each file is
+        #[arg(long)]
+        silence_report: bool,
+
+        /// Amplitude threshold for --silence-report sound detection
+        #[arg(long, default_value = "0.01")]
+        threshold: f32,
+
+        /// Window size for --silence-report sound detection
+        #[arg(long, default_value = "512")]
+        detection_window: usize,
+
+        /// Minimum duration for --silence-report sound detection (seconds)
+        #[arg(long, default_value = "0.01")]
+        min_duration: f32,
+
+        /// Shortest trailing silence that --silence-report counts (seconds)
+        #[arg(long, default_value = "0.05")]
+        min_silence: f32,
+
+        /// Show how sample levels are distributed between -96 and 0 dBFS in this many bins (1-100)
+        #[arg(long, value_name = "BINS", value_parser = info::parse_histogram_bins)]
+        histogram: Option<usize>,
+
+        /// Report the sample rates, channel counts, bit depths, and codecs across all files
+        /// and list the files that differ from the rest
+        #[arg(long, conflicts_with_all = ["output_format", "hash", "fields", "detect_dc", "spectral", "stereo_metrics", "per_channel", "silence_report", "histogram"])]
+        summarize: bool,
+
+        /// Number of files to probe at once (default: number of CPUs)
         #[arg(short, long)]
-        recursive: bool,
+        jobs: Option<usize>,
+
+        /// Don't run ffprobe: read sample rate, channels, bit depth and duration from
+        /// WAV, FLAC and Ogg (Vorbis/Opus) headers; other fields are left empty
+        #[arg(long)]
+        no_ffprobe: bool,
+
+        #[command(flatten)]
+        walk: WalkOptions,
     },
 
     /// Measure audio loudness using EBU R128
     Loudness {
-        /// Input directory or file path
-        #[arg(short, long)]
-        input: PathBuf,
+        /// Input files or directories (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
 
-        /// Output file for measurements
+        /// Output file for measurements (- or omitted: stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Process directories recursively
-        #[arg(short, long)]
-        recursive: bool,
+        /// Output format (text summary, a JSON array, or one CSV row per file)
+        #[arg(long, value_enum, default_value = "text")]
+        output_format: LoudnessOutputFormat,
+
+        /// Print PASS/FAIL against a delivery target and exit with 1 if any file fails
+        #[arg(
+            long,
+            value_enum,
+            value_name = "PRESET",
+            conflicts_with = "output_format"
+        )]
+        check_target: Option<TargetPreset>,
+
+        /// Integrated loudness target (LUFS) for --check-target, overriding the preset
+        #[arg(long, allow_negative_numbers = true, requires = "check_target")]
+        target_lufs: Option<f32>,
+
+        /// Allowed deviation from the target (LU) for --check-target
+        #[arg(long, requires = "check_target")]
+        tolerance: Option<f32>,
+
+        /// Highest allowed true peak (dBTP) for --check-target
+        #[arg(long, allow_negative_numbers = true, requires = "check_target")]
+        max_true_peak: Option<f32>,
+
+        /// Also measure only the region between the first and last sound (leading and trailing silence removed)
+        #[arg(long)]
+        gate_silence: bool,
+
+        /// Amplitude threshold for --gate-silence sound detection
+        #[arg(long, default_value = "0.01")]
+        threshold: f32,
+
+        /// Window size for --gate-silence sound detection
+        #[arg(long, default_value = "512")]
+        detection_window: usize,
+
+        /// Minimum duration for --gate-silence sound detection (seconds)
+        #[arg(long, default_value = "0.01")]
+        min_duration: f32,
+
+        /// Also report the sample peak, RMS, crest factor and DR (dynamic range) value
+        #[arg(long, conflicts_with = "check_target")]
+        dynamics: bool,
+
+        /// Reuse measurements stored in this JSON file for files whose size and
+        /// modification time are unchanged, and store new ones in it
+        #[arg(long, value_name = "FILE")]
+        cache: Option<PathBuf>,
+
+        #[command(flatten)]
+        walk: WalkOptions,
+    },
+
+    /// Plot momentary and short-term loudness (EBU R128) over time
+    LoudnessGraph {
+        /// Input files or directories (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        #[command(flatten)]
+        walk: WalkOptions,
+
+        #[command(flatten)]
+        naming: ImageOutputOptions,
+
+        /// Lowest loudness shown on the vertical axis (LUFS, must be negative)
+        #[arg(long, default_value_t = -60.0, allow_negative_numbers = true)]
+        floor: f32,
+
+        #[command(flatten)]
+        theme: ThemeOptions,
+
+        /// Output image format
+        #[arg(long, value_enum, default_value = "png")]
+        format: ImageFormat,
+
+        /// Output image width in pixels
+        #[arg(long, default_value = "1200")]
+        width: u32,
+
+        /// Output image height in pixels
+        #[arg(long, default_value = "600")]
+        height: u32,
+    },
+
+    /// Run the conversions listed in a TOML or CSV job manifest
+    Batch {
+        /// Manifest file(s) (.toml with [[job]] tables, or .csv with a header row)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Overwrite existing output files
+        #[arg(long)]
+        force: bool,
     },
 
     /// Normalize audio files to target peak level
     Normalize {
-        /// Input directory or file path
-        #[arg(short, long)]
-        input: PathBuf,
+        /// Input files or directories (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
 
         /// Output directory path
         #[arg(short, long)]
@@ -124,13 +631,51 @@ enum Commands {
         #[arg(short, long, default_value_t = -1.0, allow_negative_numbers = true)]
         level: f32,
 
+        /// Apply a peak limiter after the gain so the output never exceeds the ceiling
+        #[arg(long)]
+        limiter: bool,
+
+        /// Limiter ceiling in dBFS (-24 to 0)
+        #[arg(
+            long,
+            default_value_t = -1.0,
+            allow_negative_numbers = true,
+            requires = "limiter"
+        )]
+        limiter_ceiling: f32,
+
+        /// Output bit depth (16, 24, or 32; 32-bit is written as float unless
+        /// --sample-format int is given)
+        #[arg(short, long, default_value = "24")]
+        bit_depth: u8,
+
+        /// WAV sample format (float requires --bit-depth 32)
+        #[arg(long, value_enum)]
+        sample_format: Option<WavSampleFormat>,
+
+        /// Softly round off WAV peaks pushed past -0.9 dBFS by the gain instead of hard clipping
+        #[arg(long, conflicts_with = "limiter")]
+        soft_clip: bool,
+
+        /// Add TPDF dither before rounding WAV samples to the output bit depth
+        #[arg(long)]
+        dither: bool,
+
+        /// Bring each channel's peak to the target level with its own gain
+        /// (shifts the stereo image of unbalanced material)
+        #[arg(long, visible_alias = "peak-normalize-per-channel")]
+        per_channel: bool,
+
+        /// With --per-channel, apply the smallest channel gain to all channels to keep the image
+        #[arg(long, requires = "per_channel")]
+        link: bool,
+
         /// Input formats to process (e.g., wav,flac,mp3)
         #[arg(short = 'I', long, value_delimiter = ',', default_value = "wav")]
         input_format: Vec<String>,
 
-        /// Process directories recursively
-        #[arg(short, long)]
-        recursive: bool,
+        #[command(flatten)]
+        walk: WalkOptions,
 
         /// Force overwrite of existing files
         #[arg(long)]
@@ -138,15 +683,24 @@ enum Commands {
     },
     /// Create spectrogram from audio file
     Spectrum {
-        /// Input audio file
+        /// Input audio files or directories (comma-separated or repeated), or - to read a WAV stream from stdin
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Output image path, required when reading from stdin (-i -)
         #[arg(short, long)]
-        input: PathBuf,
+        output: Option<PathBuf>,
 
         /// FFT window size
         #[arg(long, default_value = "2048")]
         window_size: usize,
 
-        /// Window overlap ratio (0.0-1.0)
+        /// FFT window function (rectangular maximizes frequency resolution,
+        /// blackman-harris minimizes spectral leakage)
+        #[arg(long, value_enum, default_value = "hann")]
+        window: WindowFunction,
+
+        /// Window overlap ratio (0.0 up to, but not including, 1.0)
         #[arg(long, default_value = "0.75")]
         overlap: f32,
 
@@ -154,13 +708,44 @@ enum Commands {
         #[arg(long, default_value = "20.0")]
         min_freq: f32,
 
-        /// Maximum frequency to display (Hz)
-        #[arg(long, default_value = "20000.0")]
-        max_freq: f32,
+        /// Maximum frequency to display (Hz), or "auto" for the Nyquist frequency.
+        /// Values above Nyquist are lowered to it
+        #[arg(long, default_value = "20000.0", value_parser = parse_max_freq)]
+        max_freq: MaxFrequency,
 
-        /// Process directories recursively
-        #[arg(short, long)]
-        recursive: bool,
+        /// Frequency axis scale (linear or log)
+        #[arg(long, value_enum, default_value = "linear")]
+        freq_scale: FrequencyScale,
+
+        /// Plot a Mel spectrogram with the given number of filters (overrides --freq-scale)
+        #[arg(long, value_name = "N_MELS", num_args = 0..=1, default_missing_value = "128")]
+        mel: Option<usize>,
+
+        /// Plot the average magnitude spectrum of the range (level against frequency) instead of a spectrogram
+        #[arg(long)]
+        average: bool,
+
+        /// Plot the level difference (dB) against this file instead of the spectrogram
+        #[arg(long, value_name = "FILE")]
+        compare: Option<PathBuf>,
+
+        /// Level difference (dB) at which the --compare colors saturate
+        #[arg(long, default_value = "24.0", requires = "compare")]
+        diff_range: f32,
+
+        /// Gamma applied to the heatmap levels (below 1 brightens quiet content, above 1 darkens it)
+        #[arg(long, default_value = "1.0")]
+        gamma: f32,
+
+        /// Map colors relative to the file's own peak (0 dB = loudest bin) instead of full scale
+        #[arg(long, conflicts_with = "compare")]
+        normalize: bool,
+
+        #[command(flatten)]
+        walk: WalkOptions,
+
+        #[command(flatten)]
+        naming: ImageOutputOptions,
 
         // Start time (seconds, MM:SS format, or percentage with %)
         #[arg(long, value_parser = time::parse_time_specification)]
@@ -174,6 +759,11 @@ enum Commands {
         #[arg(long)]
         auto_start: bool,
 
+        /// Print "path<TAB>start<TAB>end" (seconds) for each file to stdout: the
+        /// detected start and the end of the drawn range
+        #[arg(long, requires = "auto_start")]
+        print_detection: bool,
+
         /// Amplitude threshold for auto start detection
         #[arg(long, default_value = "0.01")]
         threshold: f32,
@@ -189,20 +779,78 @@ enum Commands {
         /// Frequency annotations (format: "freq:label", comma-separated)
         #[arg(long = "annotate", value_parser = parse_frequency_annotation, value_delimiter = ',')]
         annotations: Option<Vec<(f32, String)>>,
+
+        /// CSV file of "freq,label" rows, added to any --annotate entries
+        #[arg(long)]
+        annotations_file: Option<PathBuf>,
+
+        /// Export the spectrogram matrix (frames x bins) next to the image
+        #[arg(long, value_enum)]
+        export: Option<ExportFormat>,
+
+        /// Units of the exported values
+        #[arg(long, value_enum, default_value = "db")]
+        export_units: ExportUnits,
+
+        #[command(flatten)]
+        theme: ThemeOptions,
+
+        /// Print the analysis (range, spectral centroid and rolloff) without writing an image
+        #[arg(long)]
+        no_image: bool,
+
+        /// Save the analysis settings and source range as JSON next to each image
+        #[arg(long, conflicts_with = "no_image")]
+        metadata: bool,
+
+        /// Output image format
+        #[arg(long, value_enum, default_value = "png")]
+        format: ImageFormat,
+
+        /// Output image width in pixels
+        #[arg(long, default_value = "1200")]
+        width: u32,
+
+        /// Output image height in pixels
+        #[arg(long, default_value = "600")]
+        height: u32,
+
+        /// Multiply the image size, fonts, line widths and margins (e.g. 2 or 3 for print)
+        #[arg(long, default_value_t = 1.0)]
+        image_scale: f32,
     },
     Waveform {
-        /// Input audio file
-        #[arg(short, long)]
-        input: PathBuf,
+        /// Input audio file(s) or directories (comma-separated or repeated)
+        #[arg(short, long, num_args = 1.., value_delimiter = ',', required = true)]
+        input: Vec<PathBuf>,
+
+        /// Draw all input files on the same axes for comparison
+        #[arg(long)]
+        overlay: bool,
 
-        /// Process directories recursively
+        /// Output image path for --overlay (default: <first input>_overlay.<format>),
+        /// or for a WAV stream read from stdin (-i -)
         #[arg(short, long)]
-        recursive: bool,
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        walk: WalkOptions,
+
+        #[command(flatten)]
+        naming: ImageOutputOptions,
 
         /// Display scale (amplitude or decibel)
         #[arg(long, value_enum, default_value = "amplitude")]
         scale: WaveformScale,
 
+        /// Lowest level shown in decibel scale (dBFS, must be negative)
+        #[arg(long, default_value_t = -60.0, allow_negative_numbers = true)]
+        db_floor: f32,
+
+        /// Channel to display (mix, left, right, or split to stack left/right)
+        #[arg(long, value_enum, default_value = "mix")]
+        channel: WaveformChannel,
+
         /// Start time (seconds, MM:SS format, or percentage with %)
         #[arg(long, value_parser = time::parse_time_specification)]
         start: Option<TimeSpecification>,
@@ -215,6 +863,11 @@ enum Commands {
         #[arg(long)]
         auto_start: bool,
 
+        /// Print "path<TAB>start<TAB>end" (seconds) for each file to stdout: the
+        /// detected start and the end of the drawn range
+        #[arg(long, requires = "auto_start")]
+        print_detection: bool,
+
         /// Amplitude threshold for auto start detection
         #[arg(long, default_value = "0.01")]
         threshold: f32,
@@ -231,15 +884,104 @@ enum Commands {
         #[arg(long = "annotate", value_parser = parse_time_annotation, value_delimiter = ',')]
         annotations: Option<Vec<(f32, String)>>,
 
+        /// CSV file of "time,label" rows, added to any --annotate entries
+        #[arg(long)]
+        annotations_file: Option<PathBuf>,
+
         /// Show RMS envelope
         #[arg(long)]
         show_rms: bool,
+
+        /// RMS window length in milliseconds (shorter tracks transients,
+        /// longer shows overall energy)
+        #[arg(long, default_value = "20.0")]
+        rms_window_ms: f32,
+
+        /// Draw every sample as a line instead of the min/max envelope (for short clips)
+        #[arg(long)]
+        raw: bool,
+
+        /// Mark clipped samples and print their count and timestamps
+        #[arg(long)]
+        show_clipping: bool,
+
+        /// Absolute amplitude at or above which a sample counts as clipped
+        #[arg(long, default_value = "0.999")]
+        clip_threshold: f32,
+
+        /// Waveform color (#RRGGBB or #RRGGBBAA), overrides the theme
+        #[arg(long, value_parser = parse_color)]
+        wave_color: Option<RGBAColor>,
+
+        /// RMS envelope color (#RRGGBB or #RRGGBBAA), overrides the theme
+        #[arg(long, value_parser = parse_color)]
+        rms_color: Option<RGBAColor>,
+
+        #[command(flatten)]
+        theme: ThemeOptions,
+
+        /// Print the analysis (range, peak, RMS, clipped samples) without writing an image
+        #[arg(long)]
+        no_image: bool,
+
+        /// Save the drawing settings and source range as JSON next to each image
+        #[arg(long, conflicts_with = "no_image")]
+        metadata: bool,
+
+        /// Output image format
+        #[arg(long, value_enum, default_value = "png")]
+        format: ImageFormat,
+
+        /// Output image width in pixels
+        #[arg(long, default_value = "1200")]
+        width: u32,
+
+        /// Output image height in pixels
+        #[arg(long, default_value = "600")]
+        height: u32,
+
+        /// Multiply the image size, fonts, line widths and margins (e.g. 2 or 3 for print)
+        #[arg(long, default_value_t = 1.0)]
+        image_scale: f32,
     },
 }
 
 // Main function: Parse CLI arguments and dispatch to appropriate handler
 fn main() {
     let cli = Cli::parse();
+    logging::init_logger(&cli.verbosity);
+    ffmpeg::set_binary_paths(cli.ffmpeg.clone(), cli.ffprobe.clone());
+
+    // ffmpeg/ffprobe を使うコマンドは処理前に実行ファイルの有無を確認する。
+    // WAVだけのノーマライズ（リミッターなし）とWAVのトリムはffmpegを使わない
+    let needs_ffmpeg = match &cli.command {
+        Commands::Normalize {
+            input_format,
+            limiter,
+            ..
+        } => *limiter || input_format.iter().any(|f| !f.eq_ignore_ascii_case("wav")),
+        Commands::Trim { input, .. } => input.iter().any(|path| {
+            !path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        }),
+        Commands::Info { no_ffprobe, .. } => !*no_ffprobe,
+        command => matches!(
+            command,
+            Commands::Convert { .. }
+                | Commands::Loudness { .. }
+                | Commands::LoudnessGraph { .. }
+                | Commands::Concat { .. }
+                | Commands::Batch { .. }
+                | Commands::Roundtrip { .. }
+        ),
+    };
+    if needs_ffmpeg {
+        if let Err(e) = check_dependencies() {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
 
     match cli.command {
         Commands::Convert {
@@ -249,69 +991,567 @@ fn main() {
             input_format,
             output_format,
             bit_depth,
+            bitrate,
             sample_rate,
+            resampler_quality,
             prefix,
             postfix,
-            recursive,
+            walk,
             force,
+            overwrite_policy,
             channels,
+            downmix,
+            downmix_gain,
             normalize_level,
+            gain,
+            loudnorm,
+            remove_dc,
+            trim_silence,
+            silence_threshold,
+            silence_duration,
+            auto_trim,
+            threshold,
+            detection_window,
+            min_duration,
+            fade_in,
+            fade_out,
+            fade_curve,
+            mono_check,
+            mono_check_write,
+            mono_drop_threshold,
+            mono_peak_threshold,
+            preserve_timestamps,
         } => {
-            convert::convert_files(
-                &input,
-                output_dir.as_ref(),
+            // --mono-check-write は報告と同じ pan のゲインでモノラルに書き出す
+            let (channels, downmix) = if mono_check_write {
+                (convert::parse_channel_layout("mono").ok(), DownmixMode::Pan)
+            } else {
+                (channels, downmix)
+            };
+            let options = ConvertOptions {
+                output_dir,
                 flatten,
-                &input_format,
-                &output_format,
+                input_format,
+                output_format,
                 bit_depth,
+                bitrate,
                 sample_rate,
-                prefix.as_deref(),
-                postfix.as_deref(),
-                recursive,
-                force,
+                resampler_quality,
+                prefix,
+                postfix,
+                overwrite: if force {
+                    OverwritePolicy::Overwrite
+                } else {
+                    overwrite_policy
+                },
                 channels,
+                downmix,
+                downmix_gain_db: downmix_gain,
+                gain_db: gain,
                 normalize_level,
-            );
+                normalize_per_channel: false,
+                loudnorm,
+                limiter_ceiling: None,
+                remove_dc,
+                trim_silence: trim_silence.then_some(SilenceTrim {
+                    threshold_db: silence_threshold,
+                    duration: silence_duration,
+                }),
+                auto_trim: detection::create_auto_start_config(
+                    auto_trim,
+                    threshold,
+                    detection_window,
+                    min_duration,
+                ),
+                fade_in_ms: fade_in,
+                fade_out_ms: fade_out,
+                fade_curve,
+                mono_check: (mono_check || mono_check_write).then_some(MonoCheck {
+                    max_level_drop_db: mono_drop_threshold,
+                    max_peak_rise_db: mono_peak_threshold,
+                    write: mono_check_write,
+                }),
+                preserve_timestamps,
+            };
+            let ok = convert::convert_files(&input, &options, &walk, Some(&mut log_progress))
+                .unwrap_or_else(|e| {
+                    error!("Error converting files: {}", e);
+                    false
+                });
+            // --fail-fast では失敗したファイルがあれば終了コード1で終える
+            if !ok && walk.fail_fast {
+                std::process::exit(1);
+            }
+        }
+        Commands::Concat {
+            input,
+            output,
+            input_format,
+            crossfade,
+            sample_rate,
+            bit_depth,
+            walk,
+            force,
+        } => {
+            let options = ConcatOptions {
+                input_format,
+                crossfade_ms: crossfade,
+                sample_rate,
+                bit_depth,
+                force,
+            };
+            match concat::concat_files(&input, &output, &options, &walk) {
+                Ok(_) => info!("Concatenated -> {}", output.display()),
+                Err(e) => {
+                    error!("Error concatenating files: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Split {
+            input,
+            output_dir,
+            every,
+            at,
+            on_silence,
+            threshold,
+            detection_window,
+            min_duration,
+            min_silence,
+            hysteresis,
+            force,
+        } => {
+            let mode = if let Some(length) = every {
+                SplitMode::Every(length)
+            } else if let Some(points) = at {
+                SplitMode::At(points)
+            } else {
+                debug_assert!(on_silence);
+                SplitMode::OnSilence {
+                    detection: AutoStartDetection {
+                        threshold,
+                        window_size: detection_window,
+                        min_duration,
+                        hysteresis,
+                    },
+                    min_silence,
+                }
+            };
+            let options = SplitOptions {
+                mode,
+                output_dir,
+                force,
+            };
+            // 失敗したファイルがあれば、全ファイルを処理した後に終了コード1で終える
+            let mut ok = true;
+            for input in &input {
+                if let Err(e) = split::split_file(input, &options) {
+                    error!("Error splitting {}: {}", input.display(), e);
+                    ok = false;
+                }
+            }
+            if !ok {
+                std::process::exit(1);
+            }
+        }
+        Commands::Trim {
+            input,
+            output,
+            start,
+            end,
+            auto_start,
+            threshold,
+            detection_window,
+            min_duration,
+            force,
+        } => {
+            let options = TrimOptions {
+                time_range: time::create_time_range(start, end),
+                auto_start: detection::create_auto_start_config(
+                    auto_start,
+                    threshold,
+                    detection_window,
+                    min_duration,
+                ),
+                force,
+            };
+            if output.is_some() && input.len() > 1 {
+                error!("--output names a single file; omit it to trim several inputs");
+                std::process::exit(1);
+            }
+            // 失敗したファイルがあれば、全ファイルを処理した後に終了コード1で終える
+            let mut ok = true;
+            for input in &input {
+                let output = output.clone().unwrap_or_else(|| {
+                    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+                    let ext = input.extension().unwrap_or_default().to_string_lossy();
+                    input.with_file_name(format!("{}_trimmed.{}", stem, ext))
+                });
+                match trim::trim_file(input, &output, &options) {
+                    Ok((start, end)) => info!(
+                        "Trimmed: {} -> {} ({:.3}s - {:.3}s)",
+                        input.display(),
+                        output.display(),
+                        start,
+                        end
+                    ),
+                    Err(e) => {
+                        error!("Error trimming {}: {}", input.display(), e);
+                        ok = false;
+                    }
+                }
+            }
+            if !ok {
+                std::process::exit(1);
+            }
+        }
+        Commands::Detect {
+            input,
+            output,
+            first,
+            threshold,
+            detection_window,
+            min_duration,
+            min_silence,
+            hysteresis,
+        } => {
+            let options = OnsetOptions {
+                detection: AutoStartDetection {
+                    threshold,
+                    window_size: detection_window,
+                    min_duration,
+                    hysteresis,
+                },
+                min_silence,
+                first_only: first,
+            };
+            if output.is_some() && input.len() > 1 {
+                error!(
+                    "--output names a single file; detect one input at a time to write annotations"
+                );
+                std::process::exit(1);
+            }
+            let several = input.len() > 1;
+            // 失敗したファイルがあれば、全ファイルを処理した後に終了コード1で終える
+            let mut ok = true;
+            for input in &input {
+                match detect::detect_onsets(input, &options) {
+                    Ok(onsets) => {
+                        // 複数のファイルは # コメント行のファイル名で区切る
+                        if several {
+                            println!("# {}", input.display());
+                        }
+                        for onset in &onsets {
+                            println!("{:.3}", onset);
+                        }
+                        info!("Found {} onsets in {}", onsets.len(), input.display());
+                        if let Some(output) = &output {
+                            match detect::write_annotations(output, &onsets) {
+                                Ok(()) => info!("Annotations written to {}", output.display()),
+                                Err(e) => {
+                                    error!("Error writing {}: {}", output.display(), e);
+                                    ok = false;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error detecting onsets in {}: {}", input.display(), e);
+                        ok = false;
+                    }
+                }
+            }
+            if !ok {
+                std::process::exit(1);
+            }
+        }
+        Commands::Verify { input, walk } => {
+            if !verify::verify_files(&input, &walk, Some(&mut log_progress)) {
+                std::process::exit(1);
+            }
+        }
+        Commands::Roundtrip {
+            input,
+            format,
+            bitrate,
+            diff_image,
+            output_dir,
+            theme,
+            walk,
+        } => {
+            if let Some(dir) = &output_dir {
+                if let Err(e) = std::fs::create_dir_all(dir) {
+                    error!("Failed to create {}: {}", dir.display(), e);
+                    std::process::exit(1);
+                }
+            }
+            let options = RoundtripOptions {
+                format,
+                bitrate,
+                diff_image,
+                output_dir,
+                colors: theme.apply(theme.theme.spectrum_colors()),
+                // 差分画像を書かない場合はフォントを探さない
+                font: if diff_image {
+                    resolve_font(&theme.font)
+                } else {
+                    theme.font.clone()
+                },
+            };
+            if !roundtrip::roundtrip_files(&input, &options, &walk, Some(&mut log_progress)) {
+                std::process::exit(1);
+            }
         }
         Commands::Info {
             input,
             output,
             fields,
-            recursive,
+            output_format,
+            hash,
+            audio_hash,
+            detect_dc,
+            spectral,
+            stereo_metrics,
+            per_channel,
+            silence_report,
+            threshold,
+            detection_window,
+            min_duration,
+            min_silence,
+            histogram,
+            summarize,
+            jobs,
+            no_ffprobe,
+            walk,
         } => {
-            info::get_audio_info(&input, output.as_ref(), &fields, recursive);
+            let options = InfoOptions {
+                fields,
+                output_format,
+                hash,
+                audio_hash,
+                detect_dc,
+                spectral,
+                stereo_metrics,
+                per_channel,
+                silence_report: silence_report.then_some(SilenceDetection {
+                    detection: AutoStartDetection {
+                        threshold,
+                        window_size: detection_window,
+                        min_duration,
+                        hysteresis: 0.0,
+                    },
+                    min_silence,
+                }),
+                histogram,
+                jobs: jobs.unwrap_or_else(parallel::default_jobs),
+                no_ffprobe,
+            };
+            let (input, walk, stdin) = stdin_input(input, walk);
+            let (input, walk, extracted) = match archive_inputs(input, walk) {
+                Ok(archives) => archives,
+                Err(e) => {
+                    error!("{}", e);
+                    drop(stdin);
+                    std::process::exit(1);
+                }
+            };
+            let ok = if summarize {
+                info::summarize_audio_info(&input, output.as_ref(), &options, &walk)
+            } else {
+                info::get_audio_info(
+                    &input,
+                    output.as_ref(),
+                    &options,
+                    &walk,
+                    Some(&mut log_progress),
+                )
+            };
+            drop(extracted);
+            drop(stdin);
+            if !ok && walk.fail_fast {
+                std::process::exit(1);
+            }
         }
         Commands::Loudness {
             input,
             output,
-            recursive,
+            output_format,
+            check_target,
+            target_lufs,
+            tolerance,
+            max_true_peak,
+            gate_silence,
+            threshold,
+            detection_window,
+            min_duration,
+            dynamics,
+            cache,
+            walk,
         } => {
-            loudness::measure_loudness(&input, output.as_ref(), recursive);
+            let gate = detection::create_auto_start_config(
+                gate_silence,
+                threshold,
+                detection_window,
+                min_duration,
+            );
+            let Some(preset) = check_target else {
+                let (input, walk, extracted) = match archive_inputs(input, walk) {
+                    Ok(archives) => archives,
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let options = LoudnessOptions {
+                    output_format,
+                    gate,
+                    dynamics,
+                    cache,
+                };
+                let ok = loudness::measure_loudness(
+                    &input,
+                    output.as_ref(),
+                    &options,
+                    &walk,
+                    Some(&mut log_progress),
+                );
+                drop(extracted);
+                if !ok && walk.fail_fast {
+                    std::process::exit(1);
+                }
+                return;
+            };
+            let target = match LoudnessTarget::new(preset, target_lufs, tolerance, max_true_peak) {
+                Ok(target) => target,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let (input, walk, extracted) = match archive_inputs(input, walk) {
+                Ok(archives) => archives,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let passed = loudness::check_loudness_targets(
+                &input,
+                output.as_ref(),
+                &target,
+                gate.as_ref(),
+                cache.as_deref(),
+                &walk,
+                Some(&mut log_progress),
+            );
+            // 終了前に展開した一時ファイルを削除する
+            drop(extracted);
+            if !passed {
+                std::process::exit(1);
+            }
+        }
+        Commands::LoudnessGraph {
+            input,
+            walk,
+            naming,
+            floor,
+            theme,
+            format,
+            width,
+            height,
+        } => {
+            let options = LoudnessGraphOptions {
+                floor,
+                colors: theme.apply(theme.theme.waveform_colors()),
+                font: resolve_font(&theme.font),
+                format,
+                width,
+                height,
+            };
+            for input in &input {
+                if !loudness_graph::create_loudness_graphs(input, &options, &walk, &naming)
+                    && walk.fail_fast
+                {
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Batch { input, force } => {
+            // 失敗したジョブがあれば、全マニフェストを処理した後に終了コード1で終える
+            let mut ok = true;
+            for manifest in &input {
+                match batch::run_batch(manifest, force) {
+                    Ok(all_done) => ok &= all_done,
+                    Err(e) => {
+                        error!("Error running batch {}: {}", manifest.display(), e);
+                        ok = false;
+                    }
+                }
+            }
+            if !ok {
+                std::process::exit(1);
+            }
         }
         Commands::Normalize {
             input,
             output_dir,
             level,
+            limiter,
+            limiter_ceiling,
+            bit_depth,
+            sample_format,
+            soft_clip,
+            dither,
+            per_channel,
+            link,
             input_format,
-            recursive,
+            walk,
             force,
         } => {
-            let _ = normalize::normalize_files(
+            let options = NormalizeOptions {
+                level,
+                limiter_ceiling: limiter.then_some(limiter_ceiling),
+                bit_depth,
+                sample_format,
+                soft_clip,
+                dither,
+                per_channel,
+                link,
+                force,
+            };
+            let ok = normalize::normalize_files(
                 &input,
                 output_dir.as_ref(),
-                level,
                 &input_format,
-                recursive,
-                force,
-            );
+                &walk,
+                &options,
+                Some(&mut log_progress),
+            )
+            .unwrap_or_else(|e| {
+                error!("Error normalizing files: {}", e);
+                false
+            });
+            if !ok && walk.fail_fast {
+                std::process::exit(1);
+            }
         }
         Commands::Spectrum {
             input,
+            output,
             window_size,
+            window,
             overlap,
             min_freq,
             max_freq,
-            recursive,
+            freq_scale,
+            mel,
+            average,
+            compare,
+            diff_range,
+            gamma,
+            normalize,
+            walk,
+            naming,
             start,
             end,
             auto_start,
@@ -319,7 +1559,27 @@ fn main() {
             detection_window,
             min_duration,
             annotations,
+            annotations_file,
+            export,
+            export_units,
+            theme,
+            no_image,
+            print_detection,
+            metadata,
+            format,
+            width,
+            height,
+            image_scale,
         } => {
+            let from_stdin = input.iter().any(|path| is_stdio(path));
+            if from_stdin && input.len() > 1 {
+                error!("Reading from stdin (-i -) can't be combined with other inputs");
+                std::process::exit(1);
+            }
+            if output.is_some() && !from_stdin {
+                error!("--output is only used when reading from stdin (-i -); use --output-dir");
+                std::process::exit(1);
+            }
             let time_range = time::create_time_range(start, end);
             let auto_start_config = detection::create_auto_start_config(
                 auto_start,
@@ -327,22 +1587,102 @@ fn main() {
                 detection_window,
                 min_duration,
             );
-            spectrum::create_spectrograms(
-                &input,
+            let annotations = match merge_annotations(
+                annotations,
+                annotations_file.as_deref(),
+                parse_frequency_annotation,
+            ) {
+                Ok(annotations) => annotations,
+                Err(e) => {
+                    error!("Error reading annotations: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let options = SpectrogramOptions {
                 window_size,
+                window_function: window,
                 overlap,
                 min_freq,
                 max_freq,
+                freq_scale,
+                mel_bands: mel,
+                average,
                 time_range,
-                auto_start_config,
-                recursive,
+                auto_start: auto_start_config,
                 annotations,
-            );
+                export,
+                export_units,
+                compare,
+                diff_range,
+                gamma,
+                normalize,
+                colors: theme.apply(theme.theme.spectrum_colors()),
+                // 描画しない場合はフォントを探さない（見つからない警告を出さない）
+                font: if no_image {
+                    theme.font.clone()
+                } else {
+                    resolve_font(&theme.font)
+                },
+                no_image,
+                print_detection,
+                metadata,
+                format,
+                width,
+                height,
+                image_scale,
+            };
+            // 標準入力は1ファイルとして --output に書き出す
+            if from_stdin {
+                let output = stdin_output(output, options.no_image && options.export.is_none());
+                let stdin = read_stdin_wav();
+                let result = spectrum::create_spectrogram(stdin.path(), &output, &options);
+                if let (Ok(metadata), true) = (&result, options.print_detection) {
+                    metadata.print_range(Path::new("-"));
+                }
+                match result {
+                    Ok(_) if options.no_image => info!("Analyzed: - (no image written)"),
+                    Ok(metadata) => {
+                        info!("Created spectrogram: - -> {}", output.display());
+                        if options.metadata {
+                            metadata.save(&output, &stdin.walk_options(&walk));
+                        }
+                    }
+                    Err(e) => error!("Error processing stdin: {}", e),
+                }
+                return;
+            }
+            for input in input {
+                // 展開先の一時ディレクトリは処理後に消えるため、画像はアーカイブ名のフォルダに書く
+                let (input, walk, extracted) = match archive_input(input, walk.clone()) {
+                    Ok(archive) => archive,
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let naming = match &extracted {
+                    Some(archive) if naming.output_dir.is_none() => ImageOutputOptions {
+                        output_dir: Some(archive.archive().with_extension("")),
+                        ..naming.clone()
+                    },
+                    _ => naming.clone(),
+                };
+                let ok = spectrum::create_spectrograms(&input, &options, &walk, &naming);
+                if !ok && walk.fail_fast {
+                    drop(extracted);
+                    std::process::exit(1);
+                }
+            }
         }
         Commands::Waveform {
             input,
-            recursive,
+            overlay,
+            output,
+            walk,
+            naming,
             scale,
+            db_floor,
+            channel,
             start,
             end,
             auto_start,
@@ -350,8 +1690,29 @@ fn main() {
             detection_window,
             min_duration,
             annotations,
+            annotations_file,
             show_rms,
+            rms_window_ms,
+            raw,
+            show_clipping,
+            clip_threshold,
+            wave_color,
+            rms_color,
+            theme,
+            no_image,
+            print_detection,
+            metadata,
+            format,
+            width,
+            height,
+            image_scale,
         } => {
+            let defaults = theme.apply(theme.theme.waveform_colors());
+            let colors = PlotColors {
+                wave: wave_color.unwrap_or(defaults.wave),
+                rms: rms_color.unwrap_or(defaults.rms),
+                ..defaults
+            };
             let time_range = time::create_time_range(start, end);
             let auto_start_config = detection::create_auto_start_config(
                 auto_start,
@@ -359,15 +1720,205 @@ fn main() {
                 detection_window,
                 min_duration,
             );
-            waveform::create_waveforms(
-                &input,
+            let annotations = match merge_annotations(
+                annotations,
+                annotations_file.as_deref(),
+                parse_time_annotation,
+            ) {
+                Ok(annotations) => annotations,
+                Err(e) => {
+                    error!("Error reading annotations: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let options = WaveformOptions {
                 scale,
+                db_floor,
+                channel,
                 time_range,
-                auto_start_config,
-                recursive,
+                auto_start: auto_start_config,
                 annotations,
                 show_rms,
-            );
+                rms_window_ms,
+                raw,
+                show_clipping,
+                clip_threshold,
+                colors,
+                font: if no_image {
+                    theme.font.clone()
+                } else {
+                    resolve_font(&theme.font)
+                },
+                no_image,
+                print_detection,
+                metadata,
+                format,
+                width,
+                height,
+                image_scale,
+            };
+            // 標準入力（-i -）は一時ファイルに保存し、そのパスで処理する
+            let stdin = input.iter().any(|path| is_stdio(path)).then(read_stdin_wav);
+            let resolve = |path: &PathBuf| match &stdin {
+                Some(stdin) if is_stdio(path) => stdin.path().to_path_buf(),
+                _ => path.clone(),
+            };
+            let stdin_walk = match &stdin {
+                Some(stdin) => stdin.walk_options(&walk),
+                None => walk.clone(),
+            };
+
+            if overlay {
+                if is_stdio(&input[0]) && output.is_none() {
+                    stdin_output(None, options.no_image);
+                }
+                let output = output.unwrap_or_else(|| {
+                    let stem = input[0].file_stem().unwrap_or_default().to_string_lossy();
+                    let extension = options.format.extension();
+                    let overlay =
+                        input[0].with_file_name(format!("{}_overlay.{}", stem, extension));
+                    naming.image_path(
+                        &overlay,
+                        overlay.parent().unwrap_or(Path::new("")),
+                        extension,
+                    )
+                });
+                let input: Vec<PathBuf> = input.iter().map(resolve).collect();
+                let sources: Vec<&Path> = input.iter().map(PathBuf::as_path).collect();
+                let is_up_to_date = |output: &Path| naming.is_up_to_date(output, &sources);
+                if !options.no_image
+                    && is_up_to_date(&output)
+                    && (!options.metadata || is_up_to_date(&metadata_path(&output)))
+                {
+                    info!(
+                        "Skipped: {} (image is newer than the sources. Use --force to regenerate)",
+                        output.display()
+                    );
+                    return;
+                }
+                if !options.no_image {
+                    if let Some(dir) = output.parent() {
+                        if let Err(e) = std::fs::create_dir_all(dir) {
+                            error!("Error creating {}: {}", dir.display(), e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                match waveform::create_waveform_overlay(&input, &output, &options) {
+                    Ok(_) if options.no_image => {
+                        info!("Analyzed {} files (no image written)", input.len())
+                    }
+                    Ok(metadata) => {
+                        info!("Created waveform overlay: {}", output.display());
+                        if options.metadata {
+                            metadata.save(&output, &stdin_walk);
+                        }
+                    }
+                    Err(e) => error!("Error creating overlay: {}", e),
+                }
+            } else {
+                for path in &input {
+                    if !is_stdio(path) {
+                        if !waveform::create_waveforms(path, &options, &walk, &naming)
+                            && walk.fail_fast
+                        {
+                            std::process::exit(1);
+                        }
+                        continue;
+                    }
+                    let output = stdin_output(output.clone(), options.no_image);
+                    let result = waveform::create_waveform(&resolve(path), &output, &options);
+                    if let (Ok(metadata), true) = (&result, options.print_detection) {
+                        metadata.print_range(Path::new("-"));
+                    }
+                    match result {
+                        Ok(_) if options.no_image => info!("Analyzed: - (no image written)"),
+                        Ok(metadata) => {
+                            info!("Created waveform: - -> {}", output.display());
+                            if options.metadata {
+                                metadata.save(&output, &stdin_walk);
+                            }
+                        }
+                        Err(e) => error!("Error processing stdin: {}", e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 入力に - があれば標準入力のWAVを一時ファイルに保存し、そのファイルを入力にする
+fn stdin_input(
+    inputs: Vec<PathBuf>,
+    walk: WalkOptions,
+) -> (Vec<PathBuf>, WalkOptions, Option<StdinWav>) {
+    if !inputs.iter().any(|input| is_stdio(input)) {
+        return (inputs, walk, None);
+    }
+    let stdin = read_stdin_wav();
+    let inputs = inputs
+        .into_iter()
+        .map(|input| match is_stdio(&input) {
+            true => stdin.path().to_path_buf(),
+            false => input,
+        })
+        .collect();
+    (inputs, stdin.walk_options(&walk), Some(stdin))
+}
+
+// 標準入力のWAVを一時ファイルに保存する（読めなければ終了する）
+fn read_stdin_wav() -> StdinWav {
+    StdinWav::read().unwrap_or_else(|e| {
+        error!("Error reading stdin: {}", e);
+        std::process::exit(1);
+    })
+}
+
+// 標準入力の画像は名前を決められないため --output を必須にする（何も書き出さない場合を除く）
+fn stdin_output(output: Option<PathBuf>, writes_nothing: bool) -> PathBuf {
+    match output {
+        Some(output) => output,
+        None if writes_nothing => PathBuf::from("-"),
+        None => {
+            error!("Reading from stdin (-i -) needs --output for the image");
+            std::process::exit(1);
         }
     }
 }
+
+// zip の入力は一時ディレクトリに展開し、その中を再帰的に処理する。
+// 戻り値の ExtractedArchive を破棄すると展開したファイルは削除される
+fn archive_input(
+    input: PathBuf,
+    walk: WalkOptions,
+) -> Result<(PathBuf, WalkOptions, Option<ExtractedArchive>), AudioToolsError> {
+    if !is_archive(&input) {
+        return Ok((input, walk, None));
+    }
+    let archive = ExtractedArchive::extract(&input).map_err(|e| {
+        AudioToolsError::InvalidInput(format!("Error extracting {}: {}", input.display(), e))
+    })?;
+    Ok((
+        archive.path().clone(),
+        archive.walk_options(&walk),
+        Some(archive),
+    ))
+}
+
+// 複数の入力のうち zip をすべて展開する（archive_input を入力ごとに適用する）。
+// 途中で失敗した場合は、それまでに展開したものを削除してからエラーを返す
+fn archive_inputs(
+    inputs: Vec<PathBuf>,
+    walk: WalkOptions,
+) -> Result<(Vec<PathBuf>, WalkOptions, Vec<ExtractedArchive>), AudioToolsError> {
+    let mut walk = walk;
+    let mut extracted = Vec::new();
+    let mut paths = Vec::new();
+    for input in inputs {
+        let (path, archive_walk, archive) = archive_input(input, walk.clone())?;
+        walk = archive_walk;
+        extracted.extend(archive);
+        paths.push(path);
+    }
+    Ok((paths, walk, extracted))
+}
@@ -0,0 +1,86 @@
+use super::{is_audio_file, temp_dir_path, WalkOptions};
+use crate::error::AudioToolsError;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+// True for a .zip file given as a command input
+pub fn is_archive(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+// The audio entries of a zip archive, extracted to a temporary directory so the
+// usual walkers can process them. The directory is removed on drop
+pub struct ExtractedArchive {
+    archive: PathBuf,
+    dir: PathBuf,
+}
+
+impl ExtractedArchive {
+    // Extract every entry with an audio extension, keeping the archive's folder
+    // layout. Entry names that would escape the directory are skipped. The files
+    // get the archive's modification time, so --force checks compare against it
+    pub fn extract(archive: &Path) -> Result<Self, AudioToolsError> {
+        let invalid = |e: zip::result::ZipError| AudioToolsError::InvalidInput(e.to_string());
+        let mut zip = ZipArchive::new(File::open(archive)?).map_err(invalid)?;
+        let modified = fs::metadata(archive)?.modified()?;
+
+        let extracted = Self {
+            archive: archive.to_path_buf(),
+            dir: temp_dir_path(),
+        };
+        fs::create_dir_all(&extracted.dir)?;
+
+        for index in 0..zip.len() {
+            let mut entry = zip.by_index(index).map_err(invalid)?;
+            let Some(name) = entry.enclosed_name() else {
+                continue;
+            };
+            let is_audio = name
+                .extension()
+                .is_some_and(|ext| is_audio_file(&ext.to_string_lossy()));
+            if entry.is_dir() || !is_audio {
+                continue;
+            }
+
+            let path = extracted.dir.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(&path)?;
+            io::copy(&mut entry, &mut file)?;
+            file.set_modified(modified)?;
+        }
+
+        Ok(extracted)
+    }
+
+    // Directory to walk instead of the archive
+    pub fn path(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    // The archive path this directory was extracted from
+    pub fn archive(&self) -> &Path {
+        &self.archive
+    }
+
+    // Walk options that report files in the extracted directory as paths inside
+    // the archive. get_walker always descends into the directory
+    pub fn walk_options(&self, walk: &WalkOptions) -> WalkOptions {
+        let mut walk = walk.clone();
+        walk.source_roots
+            .push((self.dir.clone(), self.archive.clone()));
+        walk
+    }
+}
+
+impl Drop for ExtractedArchive {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
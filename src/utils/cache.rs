@@ -0,0 +1,115 @@
+use crate::error::AudioToolsError;
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// On-disk JSON store of per-file results. Entries are keyed by the file's
+// absolute path and are only used while the file's size and modification time
+// match the ones recorded with them
+pub struct FileCache<T> {
+    path: PathBuf,
+    entries: BTreeMap<String, CacheEntry<T>>,
+    changed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile<T> {
+    // Version that wrote the file; entries from other versions are discarded
+    // since their measurement may differ
+    generator: String,
+    entries: BTreeMap<String, CacheEntry<T>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry<T> {
+    size: u64,
+    modified_ns: u128,
+    value: T,
+}
+
+// Cheap signature of a file's contents: its size and modification time
+fn signature(file: &Path) -> Option<(u64, u128)> {
+    let metadata = fs::metadata(file).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos()))
+}
+
+fn key(file: &Path) -> String {
+    fs::canonicalize(file)
+        .unwrap_or_else(|_| file.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn generator() -> String {
+    format!("audiotools {}", env!("CARGO_PKG_VERSION"))
+}
+
+impl<T: Serialize + DeserializeOwned> FileCache<T> {
+    // Load the cache at path. A missing file starts an empty cache; an
+    // unreadable one is replaced on save. Entries for files that no longer
+    // exist are dropped
+    pub fn open(path: &Path) -> Self {
+        let mut entries = BTreeMap::new();
+        match fs::read_to_string(path) {
+            Ok(json) => match serde_json::from_str::<CacheFile<T>>(&json) {
+                Ok(file) if file.generator == generator() => entries = file.entries,
+                Ok(file) => debug!(
+                    "Ignoring cache {} written by {}",
+                    path.display(),
+                    file.generator
+                ),
+                Err(e) => warn!("Ignoring unreadable cache {}: {}", path.display(), e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not read cache {}: {}", path.display(), e),
+        }
+        let count = entries.len();
+        entries.retain(|file, _| Path::new(file).exists());
+        Self {
+            path: path.to_path_buf(),
+            changed: entries.len() != count,
+            entries,
+        }
+    }
+
+    // The stored value for file, unless the file changed since it was stored
+    pub fn get(&self, file: &Path) -> Option<&T> {
+        let entry = self.entries.get(&key(file))?;
+        (signature(file)? == (entry.size, entry.modified_ns)).then_some(&entry.value)
+    }
+
+    // Store a value for file under its current signature
+    pub fn insert(&mut self, file: &Path, value: T) {
+        if let Some((size, modified_ns)) = signature(file) {
+            let entry = CacheEntry {
+                size,
+                modified_ns,
+                value,
+            };
+            self.entries.insert(key(file), entry);
+            self.changed = true;
+        }
+    }
+
+    // Write the cache back if anything changed. The file is replaced in one
+    // rename so an interrupted write never leaves a truncated cache
+    pub fn save(self) -> Result<(), AudioToolsError> {
+        if !self.changed {
+            return Ok(());
+        }
+        let file = CacheFile {
+            generator: generator(),
+            entries: self.entries,
+        };
+        let json = serde_json::to_string(&file).expect("Failed to serialize to JSON");
+        let temp = self.path.with_extension("tmp");
+        fs::write(&temp, json)?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::AudioToolsError;
+use hound::WavReader;
+use rodio::{Decoder, Source};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Decoded audio as interleaved f32 samples in the range -1.0..=1.0
+pub struct DecodedAudio {
+    pub samples: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+impl DecodedAudio {
+    // Average all channels of each frame
+    pub fn mono(&self) -> Vec<f32> {
+        self.samples
+            .chunks(self.channels.max(1) as usize)
+            .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+            .collect()
+    }
+
+    // Mean sample value of each channel (the DC offset), summed in f64 so
+    // long files don't lose precision
+    pub fn channel_means(&self) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        let mut sums = vec![0.0f64; channels];
+        for frame in self.samples.chunks_exact(channels) {
+            for (sum, &sample) in sums.iter_mut().zip(frame) {
+                *sum += sample as f64;
+            }
+        }
+        let frames = (self.samples.len() / channels).max(1) as f64;
+        sums.into_iter().map(|sum| (sum / frames) as f32).collect()
+    }
+}
+
+// WAV is read with hound so 24-bit and float files keep their precision;
+// everything else (mp3, flac, ogg, ...) goes through rodio's decoders
+pub fn decode_audio(path: &Path) -> Result<DecodedAudio, AudioToolsError> {
+    let is_wav = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    if is_wav {
+        decode_wav(path)
+    } else {
+        decode_compressed(path)
+    }
+}
+
+// Scale an integer sample read through hound to -1.0..1.0. hound already
+// converts 8-bit WAV (stored unsigned around 128) to signed -128..=127, so
+// every integer depth scales by 2^(bits - 1); i64 keeps the 32-bit shift from
+// overflowing
+pub fn normalize_sample(raw: i32, bits: u16) -> f32 {
+    (raw as f64 / (1_i64 << (bits.clamp(1, 32) - 1)) as f64) as f32
+}
+
+// Decode any supported file and mix it down to mono; returns (samples, sample rate)
+pub fn load_samples(path: &Path) -> Result<(Vec<f32>, u32), AudioToolsError> {
+    let audio = decode_audio(path)?;
+    Ok((audio.mono(), audio.sample_rate))
+}
+
+fn decode_wav(path: &Path) -> Result<DecodedAudio, AudioToolsError> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => reader
+            .samples::<i32>()
+            .map(|s| s.map(|s| normalize_sample(s, spec.bits_per_sample)))
+            .collect::<Result<_, _>>()?,
+    };
+
+    Ok(DecodedAudio {
+        samples,
+        channels: spec.channels,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+fn decode_compressed(path: &Path) -> Result<DecodedAudio, AudioToolsError> {
+    let decoder = Decoder::new(BufReader::new(File::open(path)?))
+        .map_err(|e| AudioToolsError::UnsupportedFormat(format!("{}: {}", path.display(), e)))?;
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+
+    // rodio yields i16 samples
+    let samples = decoder.map(|s| s as f32 / 32768.0).collect();
+
+    Ok(DecodedAudio {
+        samples,
+        channels,
+        sample_rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_sample_per_bit_depth() {
+        // (bits, min, max) of the signed values hound hands out
+        for (bits, min, max) in [
+            (8, -128, 127),
+            (16, i16::MIN as i32, i16::MAX as i32),
+            (24, -(1 << 23), (1 << 23) - 1),
+            (32, i32::MIN, i32::MAX),
+        ] {
+            let full_scale = (1_i64 << (bits - 1)) as f64;
+            assert_eq!(normalize_sample(min, bits), -1.0, "{}-bit min", bits);
+            assert_eq!(
+                normalize_sample(max, bits),
+                ((full_scale - 1.0) / full_scale) as f32,
+                "{}-bit max",
+                bits
+            );
+            assert_eq!(normalize_sample(0, bits), 0.0);
+        }
+    }
+}
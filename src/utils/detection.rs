@@ -1,14 +1,18 @@
+use crate::error::AudioToolsError;
+use crate::utils::decode::normalize_sample;
 use hound::WavReader;
-use rodio::Decoder;
+use rodio::{Decoder, Source};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AutoStartDetection {
     pub threshold: f32,     // 振幅のスレッショルド値
     pub window_size: usize, // 検出用の移動平均ウィンドウサイズ
     pub min_duration: f32,  // 最小持続時間（秒）
+    pub hysteresis: f32,    // 無音に戻ったとみなすスレッショルドを下げる幅（dB）
 }
 
 impl Default for AutoStartDetection {
@@ -17,6 +21,7 @@ impl Default for AutoStartDetection {
             threshold: 0.01,    // デフォルトのスレッショルド値（-40dB相当）
             window_size: 512,   // デフォルトのウィンドウサイズ
             min_duration: 0.01, // デフォルトの最小持続時間（10ms）
+            hysteresis: 0.0,    // デフォルトは開始と終了で同じスレッショルド
         }
     }
 }
@@ -28,6 +33,12 @@ impl AutoStartDetection {
         (sum_squares / window.len() as f32).sqrt()
     }
 
+    // 音の終わりの判定に使うスレッショルド。ノイズゲートと同様に開始のスレッショルドより
+    // hysteresis dB 低くし、残響やノイズでスレッショルド付近を上下しても区間が切れないようにする
+    fn release_threshold(&self) -> f32 {
+        self.threshold * 10f32.powf(-self.hysteresis.max(0.0) / 20.0)
+    }
+
     // ゼロクロッシングを検出する関数
     fn is_zero_crossing(a: f32, b: f32) -> bool {
         (a < 0.0 && b >= 0.0) || (a >= 0.0 && b < 0.0)
@@ -45,22 +56,90 @@ impl AutoStartDetection {
             if !triggered && rms > self.threshold {
                 triggered = true;
                 potential_start = i;
-            } else if triggered {
-                if i - potential_start >= min_samples {
-                    for j in potential_start..i {
-                        if j + 1 < samples.len()
-                            && Self::is_zero_crossing(samples[j], samples[j + 1])
-                        {
-                            return Some(j as f32 / sample_rate);
-                        }
+            } else if triggered && i - potential_start >= min_samples {
+                for j in potential_start..i {
+                    if j + 1 < samples.len() && Self::is_zero_crossing(samples[j], samples[j + 1]) {
+                        return Some(j as f32 / sample_rate);
                     }
-                    return Some(potential_start as f32 / sample_rate);
                 }
+                return Some(potential_start as f32 / sample_rate);
             }
         }
 
         None
     }
+
+    // 先頭と末尾の無音を除いた、音のある区間 (開始, 終了) を求める関数。
+    // 末尾は反転したサンプルで開始点を検出して求める
+    pub fn detect_active_range(&self, samples: &[f32], sample_rate: f32) -> Option<(f32, f32)> {
+        let start = self.detect_start_time(samples, sample_rate)?;
+        let reversed: Vec<f32> = samples.iter().rev().copied().collect();
+        let tail = self.detect_start_time(&reversed, sample_rate)?;
+        let end = samples.len() as f32 / sample_rate - tail;
+        (end > start).then_some((start, end))
+    }
+
+    // 無音が min_silence 秒以上続く最初の位置（無音の開始時刻）を検出する関数。
+    // 無音は終了のスレッショルド（release_threshold）以下の区間とする
+    pub fn detect_end_time(
+        &self,
+        samples: &[f32],
+        sample_rate: f32,
+        min_silence: f32,
+    ) -> Option<f32> {
+        let window_size = self.window_size.max(1);
+        let min_samples = (min_silence * sample_rate) as usize;
+        let release = self.release_threshold();
+        let mut silence_start: Option<usize> = None;
+
+        for (index, window) in samples.chunks(window_size).enumerate() {
+            let position = index * window_size;
+            if Self::calculate_rms(window) > release {
+                silence_start = None;
+            } else {
+                let start = *silence_start.get_or_insert(position);
+                if position + window.len() - start >= min_samples {
+                    return Some(start as f32 / sample_rate);
+                }
+            }
+        }
+
+        None
+    }
+
+    // 開始点検出と無音検出を交互に行い、音のある区間 (開始, 終了) を求める関数
+    pub fn detect_sounds(
+        &self,
+        samples: &[f32],
+        sample_rate: f32,
+        min_silence: f32,
+    ) -> Vec<(f32, f32)> {
+        let total_duration = samples.len() as f32 / sample_rate;
+        let mut segments = Vec::new();
+        let mut offset = 0.0;
+
+        while offset < total_duration {
+            let from = (offset * sample_rate) as usize;
+            let Some(start) = self.detect_start_time(&samples[from..], sample_rate) else {
+                break;
+            };
+            let start = offset + start;
+
+            let from = (start * sample_rate) as usize;
+            let end = self
+                .detect_end_time(&samples[from..], sample_rate, min_silence)
+                .map_or(total_duration, |end| start + end);
+
+            // 無音判定のウィンドウ内で終わる短すぎる区間は除く
+            if end - start >= self.min_duration {
+                segments.push((start, end));
+            }
+            // 次の検出は必ず前に進める
+            offset = end.max(start + self.window_size as f32 / sample_rate);
+        }
+
+        segments
+    }
 }
 
 pub fn create_auto_start_config(
@@ -74,35 +153,46 @@ pub fn create_auto_start_config(
             threshold,
             window_size,
             min_duration,
+            hysteresis: 0.0,
         })
     } else {
         None
     }
 }
 
-pub fn detect_peak_level(input: &PathBuf) -> Result<f32, Box<dyn std::error::Error>> {
-    let mut max_peak = 0.0f32;
+// ファイル全体のピークレベル（dBFS）を求める。WAVの途中で読めないサンプルがあれば
+// 推定値を返さずエラーにする（波形・スペクトログラムの読み込みと同じ扱い）。
+// WAV以外は rodio のデコーダが読めた位置までを対象にする
+pub fn detect_peak_level(input: &PathBuf) -> Result<f32, AudioToolsError> {
+    Ok(overall_peak_level(&detect_channel_peak_levels(input)?))
+}
+
+// チャンネルごとのピークレベル（dBFS）のうち最大のもの
+pub fn overall_peak_level(peaks: &[f32]) -> f32 {
+    peaks.iter().copied().fold(to_dbfs(0.0), f32::max)
+}
+
+// チャンネルごとのピークレベル（dBFS）を求める。読み込みの扱いは detect_peak_level と同じ
+pub fn detect_channel_peak_levels(input: &PathBuf) -> Result<Vec<f32>, AudioToolsError> {
+    let mut peaks: Vec<f32>;
 
     if let Ok(reader) = WavReader::open(input) {
         // WAVファイルの場合
         let spec = reader.spec();
+        let channels = spec.channels.max(1) as usize;
+        peaks = vec![0.0; channels];
         match spec.sample_format {
             hound::SampleFormat::Float => {
-                for sample in reader.into_samples::<f32>() {
-                    if let Ok(sample) = sample {
-                        max_peak = max_peak.max(sample.abs());
-                    }
+                for (i, sample) in reader.into_samples::<f32>().enumerate() {
+                    let peak = &mut peaks[i % channels];
+                    *peak = peak.max(sample?.abs());
                 }
             }
             hound::SampleFormat::Int => {
-                let bits = spec.bits_per_sample;
-                let max_value = (1 << (bits - 1)) as f32;
-
-                for sample in reader.into_samples::<i32>() {
-                    if let Ok(sample) = sample {
-                        let normalized = sample as f32 / max_value;
-                        max_peak = max_peak.max(normalized.abs());
-                    }
+                for (i, sample) in reader.into_samples::<i32>().enumerate() {
+                    let normalized = normalize_sample(sample?, spec.bits_per_sample);
+                    let peak = &mut peaks[i % channels];
+                    *peak = peak.max(normalized.abs());
                 }
             }
         }
@@ -110,16 +200,23 @@ pub fn detect_peak_level(input: &PathBuf) -> Result<f32, Box<dyn std::error::Err
         // WAV以外のフォーマットの場合（mp3, flac等）
         let file = File::open(input)?;
         let reader = BufReader::new(file);
-        let decoder = Decoder::new(reader)?;
+        let decoder =
+            Decoder::new(reader).map_err(|e| AudioToolsError::UnsupportedFormat(e.to_string()))?;
+        let channels = decoder.channels().max(1) as usize;
+        peaks = vec![0.0; channels];
 
         // i16サンプルをf32に正規化(-1.0から1.0の範囲に)
-        for sample in decoder {
+        for (i, sample) in decoder.enumerate() {
             let normalized = sample as f32 / 32768.0; // i16の最大値で正規化
-            max_peak = max_peak.max(normalized.abs());
+            let peak = &mut peaks[i % channels];
+            *peak = peak.max(normalized.abs());
         }
     }
 
     // ピーク値をdBFSに変換
-    let peak_dbfs = 20.0 * max_peak.max(1e-20).log10();
-    Ok(peak_dbfs)
+    Ok(peaks.into_iter().map(to_dbfs).collect())
+}
+
+fn to_dbfs(peak: f32) -> f32 {
+    20.0 * peak.max(1e-20).log10()
 }
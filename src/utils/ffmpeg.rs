@@ -0,0 +1,88 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+// Environment variables that override the ffmpeg/ffprobe executables
+pub const FFMPEG_ENV: &str = "AUDIOTOOLS_FFMPEG";
+pub const FFPROBE_ENV: &str = "AUDIOTOOLS_FFPROBE";
+
+// Paths given explicitly (e.g. by --ffmpeg/--ffprobe); these take precedence
+// over the environment variables
+static FFMPEG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static FFPROBE_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+// Set the executables to use for the rest of the process. Only the first call
+// has an effect.
+pub fn set_binary_paths(ffmpeg: Option<PathBuf>, ffprobe: Option<PathBuf>) {
+    if let Some(path) = ffmpeg {
+        let _ = FFMPEG_OVERRIDE.set(path);
+    }
+    if let Some(path) = ffprobe {
+        let _ = FFPROBE_OVERRIDE.set(path);
+    }
+}
+
+fn resolve(explicit: &OnceLock<PathBuf>, env: &str, name: &str) -> OsString {
+    explicit
+        .get()
+        .map(|path| path.clone().into_os_string())
+        .or_else(|| std::env::var_os(env))
+        .unwrap_or_else(|| name.into())
+}
+
+// Path of the ffmpeg executable, falling back to the bare name on PATH
+pub fn ffmpeg_path() -> OsString {
+    resolve(&FFMPEG_OVERRIDE, FFMPEG_ENV, "ffmpeg")
+}
+
+// Path of the ffprobe executable, falling back to the bare name on PATH
+pub fn ffprobe_path() -> OsString {
+    resolve(&FFPROBE_OVERRIDE, FFPROBE_ENV, "ffprobe")
+}
+
+pub fn ffmpeg_command() -> Command {
+    Command::new(ffmpeg_path())
+}
+
+pub fn ffprobe_command() -> Command {
+    Command::new(ffprobe_path())
+}
+
+// Run `<binary> -version` once so a missing binary is reported before any
+// file is processed instead of failing deep inside a batch
+pub fn check_dependencies() -> Result<(), String> {
+    for (name, path, env) in [
+        ("ffmpeg", ffmpeg_path(), FFMPEG_ENV),
+        ("ffprobe", ffprobe_path(), FFPROBE_ENV),
+    ] {
+        let status = Command::new(&path)
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                return Err(format!(
+                    "{} ({}) exited with {}; check the installation or set {} / --{}",
+                    name,
+                    path.to_string_lossy(),
+                    status,
+                    env,
+                    name
+                ))
+            }
+            Err(_) => {
+                return Err(format!(
+                    "{} not found at '{}'; install it or set {} / --{}",
+                    name,
+                    path.to_string_lossy(),
+                    env,
+                    name
+                ))
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,13 +1,15 @@
+use crate::error::AudioToolsError;
+use crate::utils::ffmpeg::ffprobe_command;
+use log::trace;
 use std::path::Path;
-use std::process::Command;
 
-pub fn run_ffprobe(path: &Path, args: &[&str]) -> Result<String, std::io::Error> {
-    let output = Command::new("ffprobe")
-        .arg("-v")
-        .arg("quiet")
-        .args(args)
-        .arg(path)
-        .output()?;
+pub fn run_ffprobe(path: &Path, args: &[&str]) -> Result<String, AudioToolsError> {
+    let mut cmd = ffprobe_command();
+    cmd.arg("-v").arg("quiet").args(args).arg(path);
+    trace!("Running {:?}", cmd);
+    let output = cmd
+        .output()
+        .map_err(|e| AudioToolsError::Ffmpeg(format!("could not run ffprobe: {}", e)))?;
 
     Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
@@ -0,0 +1,76 @@
+use crate::utils::wave_header::WavHeader;
+use clap::ValueEnum;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// Read buffer size for streaming large files through the hasher
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum HashAlgorithm {
+    None,
+    Md5,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::None => "none",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+// Hash everything read from the reader, returning a lowercase hex digest
+pub fn hash_reader<R: Read>(
+    reader: &mut R,
+    algorithm: HashAlgorithm,
+) -> Result<Option<String>, std::io::Error> {
+    match algorithm {
+        HashAlgorithm::None => Ok(None),
+        HashAlgorithm::Md5 => digest::<Md5, R>(reader).map(Some),
+        HashAlgorithm::Sha256 => digest::<Sha256, R>(reader).map(Some),
+    }
+}
+
+fn digest<D: Digest, R: Read>(reader: &mut R) -> Result<String, std::io::Error> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+// Hash the whole file
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> Result<Option<String>, std::io::Error> {
+    let mut file = File::open(path)?;
+    hash_reader(&mut file, algorithm)
+}
+
+// Hash only the payload of the WAV "data" chunk, so files that differ only in
+// metadata chunks produce the same digest
+pub fn hash_audio_data(
+    path: &Path,
+    algorithm: HashAlgorithm,
+) -> Result<Option<String>, std::io::Error> {
+    let mut file = File::open(path)?;
+    let data = WavHeader::find_chunk(&mut file, b"data")?.ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "data chunk not found")
+    })?;
+    file.seek(SeekFrom::Start(data.offset))?;
+    hash_reader(&mut file.take(data.size as u64), algorithm)
+}
@@ -0,0 +1,50 @@
+use clap::{ArgAction, Args};
+use log::{Level, LevelFilter};
+use std::io::Write;
+
+// Global verbosity flags shared by audiotools and fmtr
+#[derive(Args, Clone, Debug, Default)]
+pub struct Verbosity {
+    /// Show more detail (-v for debug output such as ffmpeg command lines, -vv for trace)
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only show errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+}
+
+impl Verbosity {
+    pub fn level_filter(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Error;
+        }
+        match self.verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+}
+
+// Log to stderr so command output on stdout (info, loudness, CSV) stays clean.
+// Info messages are printed as-is; other levels get a prefix. Dependencies
+// (e.g. the decoders) only show warnings unless -vv is given. RUST_LOG, when
+// set, takes precedence over the flags
+pub fn init_logger(verbosity: &Verbosity) {
+    let level = verbosity.level_filter();
+    env_logger::Builder::new()
+        .filter_level(level.min(if verbosity.verbose >= 2 {
+            LevelFilter::Trace
+        } else {
+            LevelFilter::Warn
+        }))
+        .filter_module("audiotools", level)
+        .filter_module("fmtr", level)
+        .parse_default_env()
+        .format(|buf, record| match record.level() {
+            Level::Info => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "{}: {}", level.as_str().to_lowercase(), record.args()),
+        })
+        .init();
+}
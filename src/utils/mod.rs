@@ -1,43 +1,312 @@
+pub mod archive;
+pub mod cache;
+pub mod decode;
 pub mod detection;
+pub mod ffmpeg;
 pub mod ffprobe;
+pub mod hash;
+pub mod logging;
+pub mod parallel;
+pub mod plot;
+pub mod probe;
+pub mod progress;
+pub mod stdin;
+pub mod summary;
 pub mod time;
+pub mod wav_io;
 pub mod wave_header;
 
 use crate::AUDIO_EXTENSIONS;
 
-use std::path::PathBuf;
-use walkdir::WalkDir;
+pub use decode::load_samples;
+pub use ffmpeg::check_dependencies;
+pub use probe::{probe_basic, BasicInfo};
+pub use progress::{BatchReport, FileOutcome, ProgressCallback, ProgressEvent};
 
-pub fn get_walker(input: &PathBuf, recursive: bool) -> impl Iterator<Item = walkdir::DirEntry> {
-    let walker = if recursive {
-        WalkDir::new(input)
+use clap::{Args, ValueEnum};
+use glob::Pattern;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::{DirEntry, WalkDir};
+
+// Order in which walked files are processed and reported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// File name, then full path for files with the same name
+    #[default]
+    Name,
+    /// Full path
+    Path,
+    /// Modification time, oldest first
+    Mtime,
+    /// File size, smallest first
+    Size,
+}
+
+// Directory traversal options shared by every command that walks an input path,
+// and whether the walk stops at the first file that fails
+#[derive(Args, Clone, Debug, Default)]
+pub struct WalkOptions {
+    /// Process directories recursively
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Follow symbolic links to files and directories
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Maximum directory depth to descend when processing recursively
+    #[arg(long, requires = "recursive")]
+    pub max_depth: Option<usize>,
+
+    /// Only process files whose name matches this glob pattern (e.g. "kick_*.wav")
+    #[arg(long, value_parser = parse_pattern)]
+    pub pattern: Option<Pattern>,
+
+    /// Skip files and folders whose name or path below the input matches this glob
+    /// pattern (e.g. "*_backup.wav" or "cache"); can be repeated
+    #[arg(long, value_parser = parse_pattern)]
+    pub exclude: Vec<Pattern>,
+
+    /// Order in which files are processed
+    #[arg(long, value_enum, default_value_t = SortOrder::Name)]
+    pub sort: SortOrder,
+
+    /// Reverse the --sort order
+    #[arg(long)]
+    pub reverse: bool,
+
+    /// Stop at the first file that fails and exit with status 1
+    /// (by default failures are logged and the remaining files are processed)
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    // One entry per input that was copied to a temporary location (an extracted
+    // archive or a stream read from stdin): (temporary path, original input).
+    // Walked files under it are then reported relative to the original input
+    #[arg(skip)]
+    pub source_roots: Vec<(PathBuf, PathBuf)>,
+}
+
+impl WalkOptions {
+    // Path to show in output and logs for a walked file
+    pub fn display_path(&self, path: &Path) -> PathBuf {
+        for (root, source) in &self.source_roots {
+            match path.strip_prefix(root) {
+                Ok(relative) if relative.as_os_str().is_empty() => return source.clone(),
+                Ok(relative) => return source.join(relative),
+                Err(_) => {}
+            }
+        }
+        path.to_path_buf()
+    }
+}
+
+// Parse a glob pattern for file name matching
+pub fn parse_pattern(s: &str) -> Result<Pattern, String> {
+    Pattern::new(s).map_err(|e| format!("Invalid pattern '{}': {}", s, e))
+}
+
+// Walk the input path and return the matching entries in --sort order.
+// WalkDir's own order depends on the file system, so entries are collected
+// and sorted to keep output and numbering reproducible across machines.
+// Extracted archives are always walked recursively, so entries in the
+// archive's folders are included
+pub fn get_walker(input: &PathBuf, options: &WalkOptions) -> impl Iterator<Item = DirEntry> {
+    let extracted = input.is_dir() && options.source_roots.iter().any(|(root, _)| root == input);
+    let mut walker = WalkDir::new(input).follow_links(options.follow_symlinks);
+    if !options.recursive && !extracted {
+        walker = walker.max_depth(1);
+    } else if let Some(depth) = options.max_depth {
+        walker = walker.max_depth(depth);
+    }
+    let mut entries: Vec<DirEntry> = walker
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !is_excluded(entry, input, options))
+        .filter_map(|e| e.ok())
+        .filter(|entry| match &options.pattern {
+            Some(pattern) => pattern.matches(&entry.file_name().to_string_lossy()),
+            None => true,
+        })
+        .collect();
+    sort_entries(&mut entries, options.sort);
+    if options.reverse {
+        entries.reverse();
+    }
+    entries.into_iter()
+}
+
+// True when an --exclude pattern matches the entry's name or its path relative
+// to the input. Excluded folders are not descended into. The input itself is
+// never excluded, so a path given on the command line is always processed
+fn is_excluded(entry: &DirEntry, input: &Path, options: &WalkOptions) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    let relative = entry.path().strip_prefix(input).unwrap_or(entry.path());
+    options
+        .exclude
+        .iter()
+        .any(|pattern| pattern.matches(&name) || pattern.matches_path(relative))
+}
+
+// Walk several inputs (files and directories can be mixed) one after another.
+// Entries are sorted within each input; the inputs keep the order they were
+// given in
+pub fn get_walkers<'a>(
+    inputs: &'a [PathBuf],
+    options: &'a WalkOptions,
+) -> impl Iterator<Item = DirEntry> + 'a {
+    inputs
+        .iter()
+        .flat_map(move |input| get_walker(input, options))
+}
+
+// Sort by the chosen key, using the path as a tie-breaker so the order is
+// stable. Entries whose metadata can't be read sort first for mtime/size
+fn sort_entries(entries: &mut [DirEntry], order: SortOrder) {
+    match order {
+        SortOrder::Name => entries.sort_by(|a, b| {
+            a.file_name()
+                .cmp(b.file_name())
+                .then(a.path().cmp(b.path()))
+        }),
+        SortOrder::Path => entries.sort_by(|a, b| a.path().cmp(b.path())),
+        SortOrder::Mtime => entries.sort_by_cached_key(|entry| {
+            (
+                entry.metadata().ok().and_then(|m| m.modified().ok()),
+                entry.path().to_path_buf(),
+            )
+        }),
+        SortOrder::Size => entries.sort_by_cached_key(|entry| {
+            (
+                entry.metadata().ok().map(|m| m.len()),
+                entry.path().to_path_buf(),
+            )
+        }),
+    }
+}
+
+// "-" as an input or output path means stdin/stdout, for use in pipelines
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+// A fresh directory name under the system temp directory (not yet created)
+pub(crate) fn temp_dir_path() -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    std::env::temp_dir().join(format!("audiotools-{}-{}", std::process::id(), nanos))
+}
+
+// Quote a CSV value if it contains a comma, quote or line break
+pub fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
     } else {
-        WalkDir::new(input).max_depth(1)
+        value.to_string()
+    }
+}
+
+// Write one record as a single line of JSON (NDJSON) to the output file, or to
+// stdout without one, and flush it so a reader gets each result as soon as the
+// file has been processed
+pub fn write_ndjson<T: Serialize>(output_file: &mut Option<File>, record: &T) {
+    let json = serde_json::to_string(record).expect("Failed to serialize to JSON");
+    let written = match output_file {
+        Some(file) => writeln!(file, "{}", json).and_then(|_| file.flush()),
+        None => {
+            let mut stdout = io::stdout().lock();
+            writeln!(stdout, "{}", json).and_then(|_| stdout.flush())
+        }
     };
-    walker.into_iter().filter_map(|e| e.ok())
+    written.expect("Failed to write to output file");
+}
+
+// Split one CSV line into fields, undoing csv_escape quoting. Quoted fields
+// may contain commas but not line breaks
+pub fn parse_csv_row(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
 }
 
 // Format file size in human-readable format
 pub fn format_size(bytes: u64) -> String {
-    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
-
     if bytes == 0 {
-        return format!("0 {}", UNITS[0]);
+        return "0 B".to_string();
     }
+    let (size, unit) = scale_size(bytes);
+    format!("{:.2} {} ({} bytes)", size, unit, bytes)
+}
 
-    let base = 1024_f64;
-    let exp = (bytes as f64).ln() / base.ln();
-    let unit_index = exp.floor() as usize;
+// Shorter form for totals, e.g. "8.4 GB"
+pub fn format_size_short(bytes: u64) -> String {
+    match scale_size(bytes) {
+        (size, "B") => format!("{} B", size),
+        (size, unit) => format!("{:.1} {}", size, unit),
+    }
+}
+
+// The size in the largest unit it fills at least once.
+// Pick the unit with integer division so exact powers of 1024 never land
+// on the previous unit, and clamp anything larger than PB to PB.
+fn scale_size(bytes: u64) -> (f64, &'static str) {
+    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
 
-    if unit_index >= UNITS.len() {
-        return format!("{} {}", bytes, UNITS[0]);
+    let mut unit_index = 0;
+    let mut divisor = 1_u64;
+    while unit_index < UNITS.len() - 1 && bytes / divisor >= 1024 {
+        divisor *= 1024;
+        unit_index += 1;
     }
 
-    let size = bytes as f64 / base.powi(unit_index as i32);
-    format!("{:.2} {} ({} bytes)", size, UNITS[unit_index], bytes)
+    (bytes as f64 / divisor as f64, UNITS[unit_index])
 }
 
 // Check if file extension matches supported audio formats
 pub fn is_audio_file(ext: &str) -> bool {
     AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_size_at_unit_boundaries() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023.00 B (1023 bytes)");
+        assert_eq!(format_size(1024), "1.00 KB (1024 bytes)");
+        assert_eq!(format_size_short(1023), "1023 B");
+        assert_eq!(format_size_short(1024), "1.0 KB");
+    }
+
+    #[test]
+    fn scale_size_clamps_to_petabytes() {
+        const PB: u64 = 1 << 50;
+        assert_eq!(scale_size(PB), (1.0, "PB"));
+        // Beyond PB the value keeps growing in PB rather than switching unit
+        assert_eq!(scale_size(5000 * PB), (5000.0, "PB"));
+        assert_eq!(
+            format_size(5000 * PB),
+            format!("5000.00 PB ({} bytes)", 5000 * PB)
+        );
+    }
+}
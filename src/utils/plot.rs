@@ -0,0 +1,396 @@
+use crate::error::AudioToolsError;
+use crate::utils::{parse_csv_row, WalkOptions};
+use clap::{Args, ValueEnum};
+use font_kit::source::SystemSource;
+use log::{error, info, warn};
+use plotters::style::RGBAColor;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Output image format for visualization commands
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+// Where the waveform and spectrum commands write their images. By default
+// each image goes next to its source file with the source's name
+#[derive(Args, Clone, Debug, Default)]
+pub struct ImageOutputOptions {
+    /// Write images to this directory instead of next to the source files
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+
+    /// Put every image directly in --output-dir (ignore source directory hierarchy)
+    #[arg(long, requires = "output_dir")]
+    pub flatten: bool,
+
+    /// Prefix to add to image filenames
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    /// Postfix to add to image filenames
+    #[arg(long)]
+    pub postfix: Option<String>,
+
+    /// Regenerate images even if they are newer than their source files
+    #[arg(long)]
+    pub force: bool,
+}
+
+impl ImageOutputOptions {
+    // Image path for a file found while walking `root`. Under --output-dir the
+    // folders between `root` and the file are kept unless --flatten is set
+    pub fn image_path(&self, file: &Path, root: &Path, extension: &str) -> PathBuf {
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        let filename = format!(
+            "{}{}{}.{}",
+            self.prefix.as_deref().unwrap_or(""),
+            stem,
+            self.postfix.as_deref().unwrap_or(""),
+            extension
+        );
+
+        match &self.output_dir {
+            Some(out_dir) if self.flatten => out_dir.join(filename),
+            Some(out_dir) => {
+                let relative_dir = file
+                    .strip_prefix(root)
+                    .unwrap_or(file)
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""));
+                out_dir.join(relative_dir).join(filename)
+            }
+            None => file.with_file_name(filename),
+        }
+    }
+
+    // An existing output can be skipped when it was modified after every
+    // source file, unless --force is given. Missing or unreadable times mean
+    // the output has to be written
+    pub fn is_up_to_date(&self, output: &Path, sources: &[&Path]) -> bool {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        if self.force {
+            return false;
+        }
+        let Some(output_time) = modified(output) else {
+            return false;
+        };
+        sources
+            .iter()
+            .all(|source| modified(source).is_some_and(|time| time <= output_time))
+    }
+}
+
+// Provenance written next to an image with --metadata: the files and time
+// ranges that were plotted, and the command's analysis settings
+#[derive(Debug, Serialize)]
+pub struct ImageMetadata<T> {
+    pub generator: String,
+    pub sources: Vec<PlottedSource>,
+    pub settings: T,
+}
+
+// A source file and the range of it drawn in the image
+#[derive(Debug, Serialize)]
+pub struct PlottedSource {
+    pub path: PathBuf,
+    pub sample_rate: u32,
+    /// Seconds from the start of the file
+    pub start: f32,
+    pub end: f32,
+}
+
+impl<T: Serialize> ImageMetadata<T> {
+    pub fn new(sources: Vec<PlottedSource>, settings: T) -> Self {
+        Self {
+            generator: format!("audiotools {}", env!("CARGO_PKG_VERSION")),
+            sources,
+            settings,
+        }
+    }
+
+    // Print the plotted range of the first source as "path<TAB>start<TAB>end"
+    // (seconds) on stdout, for scripts that use the detected start
+    pub fn print_range(&self, shown: &Path) {
+        if let Some(source) = self.sources.first() {
+            println!(
+                "{}\t{:.3}\t{:.3}",
+                shown.display(),
+                source.start,
+                source.end
+            );
+        }
+    }
+
+    // Write the sidecar for an image and log where it went. A failure is only
+    // logged, since the image itself was written
+    pub fn save(self, image: &Path, walk: &WalkOptions) {
+        match self.write(image, walk) {
+            Ok(path) => info!("Saved metadata: {}", path.display()),
+            Err(e) => error!("Error writing metadata for {}: {}", image.display(), e),
+        }
+    }
+
+    // Write the metadata as pretty-printed JSON next to the image. Source paths
+    // are reported as the user gave them (archive entries, "-" for stdin)
+    fn write(mut self, image: &Path, walk: &WalkOptions) -> Result<PathBuf, AudioToolsError> {
+        for source in &mut self.sources {
+            source.path = walk.display_path(&source.path);
+        }
+        let path = metadata_path(image);
+        let json = serde_json::to_string_pretty(&self).expect("Failed to serialize to JSON");
+        fs::write(&path, json + "\n")?;
+        Ok(path)
+    }
+}
+
+// Sidecar path for an image: the image path with a .json extension
+pub fn metadata_path(image: &Path) -> PathBuf {
+    image.with_extension("json")
+}
+
+/// Font used for plot text unless --font is given
+pub const DEFAULT_FONT: &str = "Fira Code";
+/// Generic family that plotters maps to the system's sans-serif font
+const FALLBACK_FONT: &str = "sans-serif";
+
+/// Color preset for visualization commands
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Theme {
+    /// Light text on a dark navy background
+    #[default]
+    Dark,
+    /// Dark text on a white background (for print and documents)
+    Light,
+}
+
+/// Colors used to draw waveforms and spectrograms
+#[derive(Clone, Copy, Debug)]
+pub struct PlotColors {
+    pub theme: Theme,
+    pub background: RGBAColor,
+    /// Captions, axis labels and axes
+    pub foreground: RGBAColor,
+    pub grid: RGBAColor,
+    pub wave: RGBAColor,
+    pub rms: RGBAColor,
+    pub annotation: RGBAColor,
+    pub clip: RGBAColor,
+}
+
+impl Theme {
+    // Default colors for waveform plots
+    pub fn waveform_colors(self) -> PlotColors {
+        match self {
+            Theme::Dark => PlotColors {
+                theme: self,
+                background: RGBAColor(4, 20, 36, 1.0),
+                foreground: RGBAColor(255, 255, 255, 1.0),
+                grid: RGBAColor(255, 255, 255, 0.2),
+                wave: RGBAColor(0, 128, 255, 1.0),
+                rms: RGBAColor(0, 255, 128, 0.5),
+                annotation: RGBAColor(255, 255, 0, 1.0),
+                clip: RGBAColor(255, 32, 32, 1.0),
+            },
+            Theme::Light => PlotColors {
+                theme: self,
+                background: RGBAColor(255, 255, 255, 1.0),
+                foreground: RGBAColor(32, 32, 32, 1.0),
+                grid: RGBAColor(0, 0, 0, 0.15),
+                wave: RGBAColor(0, 90, 200, 1.0),
+                rms: RGBAColor(0, 160, 80, 0.5),
+                annotation: RGBAColor(200, 100, 0, 1.0),
+                clip: RGBAColor(220, 0, 0, 1.0),
+            },
+        }
+    }
+
+    // Default colors for spectrograms. Annotations are green so they stand
+    // out from the heat map
+    pub fn spectrum_colors(self) -> PlotColors {
+        let colors = self.waveform_colors();
+        PlotColors {
+            annotation: match self {
+                Theme::Dark => RGBAColor(0, 255, 0, 1.0),
+                Theme::Light => RGBAColor(0, 140, 60, 1.0),
+            },
+            ..colors
+        }
+    }
+}
+
+// Theme options shared by the waveform and spectrum commands
+#[derive(Args, Clone, Debug, Default)]
+pub struct ThemeOptions {
+    /// Color preset (dark or light)
+    #[arg(long, value_enum, default_value_t = Theme::Dark)]
+    pub theme: Theme,
+
+    /// Background color (#RRGGBB or #RRGGBBAA), overrides the theme
+    #[arg(long, value_parser = parse_color)]
+    pub bg_color: Option<RGBAColor>,
+
+    /// Annotation color (#RRGGBB or #RRGGBBAA), overrides the theme
+    #[arg(long, value_parser = parse_color)]
+    pub annotation_color: Option<RGBAColor>,
+
+    /// Font family for titles, labels and annotations
+    #[arg(long, default_value = DEFAULT_FONT)]
+    pub font: String,
+}
+
+impl ThemeOptions {
+    // Apply the color overrides to a theme's defaults
+    pub fn apply(&self, defaults: PlotColors) -> PlotColors {
+        PlotColors {
+            background: self.bg_color.unwrap_or(defaults.background),
+            annotation: self.annotation_color.unwrap_or(defaults.annotation),
+            ..defaults
+        }
+    }
+}
+
+// Check that a font family is installed and fall back to the system's
+// sans-serif font with a warning if not. plotters would otherwise substitute
+// a font silently, or draw no text at all when nothing can be loaded
+pub fn resolve_font(name: &str) -> String {
+    let source = SystemSource::new();
+    if source.select_family_by_name(name).is_ok() {
+        return name.to_string();
+    }
+    warn!(
+        "Font '{}' is not installed; using {} instead",
+        name, FALLBACK_FONT
+    );
+    if source.select_family_by_name(FALLBACK_FONT).is_err() {
+        warn!(
+            "No {} font found either; plot text may be missing",
+            FALLBACK_FONT
+        );
+    }
+    FALLBACK_FONT.to_string()
+}
+
+// Parse a "#RRGGBB" or "#RRGGBBAA" hex color (the # is optional)
+pub fn parse_color(s: &str) -> Result<RGBAColor, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let invalid = || format!("Invalid color '{}' (expected #RRGGBB or #RRGGBBAA)", s);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    let alpha = if hex.len() == 8 {
+        channel(6)? as f64 / 255.0
+    } else {
+        1.0
+    };
+    Ok(RGBAColor(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+/// Smallest image size that still leaves room for captions, axis labels and margins
+pub const MIN_WIDTH: u32 = 400;
+pub const MIN_HEIGHT: u32 = 240;
+
+// Check that the requested image size can fit the chart layout
+pub fn validate_dimensions(width: u32, height: u32) -> Result<(), AudioToolsError> {
+    if width < MIN_WIDTH || height < MIN_HEIGHT {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Image size {}x{} is too small (minimum {}x{})",
+            width, height, MIN_WIDTH, MIN_HEIGHT
+        )));
+    }
+    Ok(())
+}
+
+/// Largest --image-scale factor (a 1200x600 image becomes 9600x4800)
+pub const MAX_IMAGE_SCALE: f32 = 8.0;
+
+// Check an --image-scale factor. Values below 1 would shrink the layout under
+// the MIN_WIDTH/MIN_HEIGHT it was checked against
+pub fn validate_image_scale(scale: f32) -> Result<(), AudioToolsError> {
+    if !(1.0..=MAX_IMAGE_SCALE).contains(&scale) {
+        return Err(AudioToolsError::InvalidInput(format!(
+            "Image scale must be between 1 and {}, got {}",
+            MAX_IMAGE_SCALE, scale
+        )));
+    }
+    Ok(())
+}
+
+// Pixel size of the written image: --width and --height times --image-scale.
+// Margins, fonts and line widths are multiplied by the same factor, so the
+// layout matches the unscaled image at a higher pixel density
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    let scale = |size: u32| (size as f32 * scale).round() as u32;
+    (scale(width), scale(height))
+}
+
+/// Parser for one "value:label" annotation, as used by --annotate
+pub type AnnotationParser = fn(&str) -> Result<(f32, String), String>;
+
+// Read an annotations CSV with one "value,label" row per marker (time in
+// seconds for waveforms, frequency in Hz for spectrograms). Each row goes
+// through the same parser as --annotate. Blank lines, # comments and a
+// header row (first row whose value is not a number) are skipped
+pub fn read_annotations_file(
+    path: &Path,
+    parse: AnnotationParser,
+) -> Result<Vec<(f32, String)>, AudioToolsError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        AudioToolsError::InvalidInput(format!("Cannot read {}: {}", path.display(), e))
+    })?;
+    let mut annotations = Vec::new();
+    let mut first_row = true;
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let fields = parse_csv_row(line);
+        let is_header = first_row && fields[0].trim().parse::<f32>().is_err();
+        first_row = false;
+        if is_header {
+            continue;
+        }
+
+        let [value, label] = fields.as_slice() else {
+            return Err(AudioToolsError::InvalidInput(format!(
+                "{} line {}: expected \"value,label\"",
+                path.display(),
+                index + 1
+            )));
+        };
+        let annotation = parse(&format!("{}:{}", value.trim(), label.trim())).map_err(|e| {
+            AudioToolsError::InvalidInput(format!("{} line {}: {}", path.display(), index + 1, e))
+        })?;
+        annotations.push(annotation);
+    }
+
+    Ok(annotations)
+}
+
+// Combine inline --annotate entries with those from --annotations-file
+pub fn merge_annotations(
+    inline: Option<Vec<(f32, String)>>,
+    file: Option<&Path>,
+    parse: AnnotationParser,
+) -> Result<Option<Vec<(f32, String)>>, AudioToolsError> {
+    let Some(file) = file else {
+        return Ok(inline);
+    };
+    let mut annotations = inline.unwrap_or_default();
+    annotations.extend(read_annotations_file(file, parse)?);
+    Ok(Some(annotations))
+}
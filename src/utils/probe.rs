@@ -0,0 +1,177 @@
+use crate::error::AudioToolsError;
+use crate::utils::wave_header::WavHeader;
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// How far from the end of an Ogg file to look for the last page, which holds
+// the final granule position (Ogg pages are at most about 64 KiB)
+const OGG_TAIL_BYTES: u64 = 65_307;
+// Opus always decodes at 48 kHz, whatever rate the source had
+const OPUS_SAMPLE_RATE: u32 = 48_000;
+
+// Stream parameters read straight from a file's headers, without ffprobe
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicInfo {
+    // Container name as ffprobe reports it ("wav", "flac", "ogg")
+    pub container: &'static str,
+    // Codec of the audio stream, when the headers identify it
+    pub codec: Option<&'static str>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    // Bits per sample of lossless formats
+    pub bit_depth: Option<u16>,
+    pub duration_seconds: Option<f64>,
+}
+
+// Read the sample rate, channel count and duration of a WAV, FLAC or Ogg
+// (Vorbis or Opus) file from its headers. The format is recognized from the
+// file's first bytes, not its extension
+pub fn probe_basic(path: &Path) -> Result<BasicInfo, AudioToolsError> {
+    let mut file = File::open(path)?;
+    let start = skip_id3v2(&mut file)?;
+    let mut magic = [0u8; 4];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut magic)?;
+    match &magic {
+        b"RIFF" => probe_wav(&mut file),
+        b"fLaC" => probe_flac(&mut file),
+        b"OggS" => probe_ogg(&mut file, start),
+        _ => Err(AudioToolsError::UnsupportedFormat(format!(
+            "{} is not a WAV, FLAC or Ogg file",
+            path.display()
+        ))),
+    }
+}
+
+// FLAC files may start with an ID3v2 tag; return the offset just after it
+fn skip_id3v2(file: &mut File) -> Result<u64, AudioToolsError> {
+    let mut header = [0u8; 10];
+    if file.read_exact(&mut header).is_err() || &header[..3] != b"ID3" {
+        return Ok(0);
+    }
+    // The tag size is a 28-bit "syncsafe" integer (7 bits per byte)
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |size, &byte| (size << 7) | (byte & 0x7f) as u64);
+    Ok(10 + size)
+}
+
+fn probe_wav(file: &mut File) -> Result<BasicInfo, AudioToolsError> {
+    let header = WavHeader::read_from_file(file)?;
+    let data = WavHeader::find_chunk(file, b"data")?;
+    Ok(BasicInfo {
+        container: "wav",
+        codec: None,
+        sample_rate: header.sample_rate(),
+        channels: header.num_channels(),
+        bit_depth: Some(header.bits_per_sample()).filter(|&bits| bits != 0),
+        duration_seconds: data.and_then(|chunk| header.duration_seconds(chunk.size)),
+    })
+}
+
+// The STREAMINFO block always comes first, right after the "fLaC" marker
+fn probe_flac(file: &mut File) -> Result<BasicInfo, AudioToolsError> {
+    let block_type = file.read_u8()? & 0x7f;
+    let length = file.read_u24::<BigEndian>()?;
+    if block_type != 0 || length < 34 {
+        return Err(invalid("FLAC file does not start with a STREAMINFO block"));
+    }
+    // Skip the block and frame size limits (2 + 2 + 3 + 3 bytes)
+    file.seek(SeekFrom::Current(10))?;
+    // 20 bits sample rate, 3 bits channels - 1, 5 bits bits per sample - 1,
+    // 36 bits total samples (0 when unknown)
+    let packed = file.read_u64::<BigEndian>()?;
+    let sample_rate = (packed >> 44) as u32;
+    let channels = ((packed >> 41) & 0x7) as u16 + 1;
+    let bit_depth = ((packed >> 36) & 0x1f) as u16 + 1;
+    let total_samples = packed & 0xf_ffff_ffff;
+    if sample_rate == 0 {
+        return Err(invalid("FLAC STREAMINFO has a sample rate of 0"));
+    }
+    Ok(BasicInfo {
+        container: "flac",
+        codec: Some("flac"),
+        sample_rate,
+        channels,
+        bit_depth: Some(bit_depth),
+        duration_seconds: (total_samples > 0).then(|| total_samples as f64 / sample_rate as f64),
+    })
+}
+
+// The first page of the first logical stream carries the codec's
+// identification header; the duration comes from the granule position of the
+// stream's last page
+fn probe_ogg(file: &mut File, start: u64) -> Result<BasicInfo, AudioToolsError> {
+    file.seek(SeekFrom::Start(start))?;
+    let mut page = [0u8; 27];
+    file.read_exact(&mut page)?;
+    let serial = u32::from_le_bytes([page[14], page[15], page[16], page[17]]);
+    let mut segments = vec![0u8; page[26] as usize];
+    file.read_exact(&mut segments)?;
+    let packet_len = segments.iter().take_while(|&&len| len == 255).count() * 255
+        + segments
+            .iter()
+            .find(|&&len| len < 255)
+            .copied()
+            .unwrap_or(0) as usize;
+    let mut packet = vec![0u8; packet_len.min(64)];
+    file.read_exact(&mut packet)?;
+
+    // (codec, channels, sample rate, samples to skip at the start)
+    let (codec, channels, sample_rate, pre_skip) = if packet.starts_with(b"\x01vorbis") {
+        let mut header = &packet[7..];
+        let _version = header.read_u32::<LittleEndian>()?;
+        let channels = header.read_u8()?;
+        let sample_rate = header.read_u32::<LittleEndian>()?;
+        ("vorbis", channels, sample_rate, 0)
+    } else if packet.starts_with(b"OpusHead") {
+        let mut header = &packet[8..];
+        let _version = header.read_u8()?;
+        let channels = header.read_u8()?;
+        let pre_skip = header.read_u16::<LittleEndian>()?;
+        ("opus", channels, OPUS_SAMPLE_RATE, pre_skip as u64)
+    } else {
+        return Err(AudioToolsError::UnsupportedFormat(
+            "Ogg stream is not Vorbis or Opus".to_string(),
+        ));
+    };
+    if channels == 0 || sample_rate == 0 {
+        return Err(invalid(
+            "Ogg identification header has no channels or sample rate",
+        ));
+    }
+
+    let duration_seconds = last_granule(file, serial)?
+        .map(|granule| granule.saturating_sub(pre_skip) as f64 / sample_rate as f64);
+    Ok(BasicInfo {
+        container: "ogg",
+        codec: Some(codec),
+        sample_rate,
+        channels: channels as u16,
+        bit_depth: None,
+        duration_seconds,
+    })
+}
+
+// Granule position (samples) of the stream's last page on which a packet ends
+fn last_granule(file: &mut File, serial: u32) -> Result<Option<u64>, AudioToolsError> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let from = len.saturating_sub(OGG_TAIL_BYTES);
+    file.seek(SeekFrom::Start(from))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    Ok((0..tail.len().saturating_sub(26))
+        .rev()
+        .filter(|&i| &tail[i..i + 4] == b"OggS" && tail[i + 14..i + 18] == serial.to_le_bytes())
+        .map(|i| i64::from_le_bytes(tail[i + 6..i + 14].try_into().unwrap()))
+        // -1 marks a page on which no packet ends
+        .find(|&granule| granule >= 0)
+        .map(|granule| granule as u64))
+}
+
+fn invalid(message: &str) -> AudioToolsError {
+    AudioToolsError::InvalidInput(message.to_string())
+}
@@ -0,0 +1,78 @@
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Progress of a batch command, for embedding the batch functions in a GUI or
+// TUI. Events describe outcomes only; the per-file details (output paths,
+// measurements) still go through the logger
+#[derive(Debug)]
+pub enum ProgressEvent<'a> {
+    // A file matched the input filters and is about to be processed
+    FileStarted { path: &'a Path },
+    FileFinished { path: &'a Path, result: FileOutcome },
+    // Sent once, after the last file
+    BatchFinished { summary: BatchReport },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileOutcome {
+    Processed,
+    // Nothing to do, e.g. the output already exists
+    Skipped,
+    Failed,
+}
+
+// Totals of a finished batch
+#[derive(Clone, Debug)]
+pub struct BatchReport {
+    // What happened to processed files, e.g. "converted"
+    pub action: String,
+    pub scanned: usize,
+    pub processed: usize,
+    pub skipped: usize,
+    pub failed: Vec<PathBuf>,
+    pub elapsed: Duration,
+    // True if --fail-fast stopped the batch before the remaining files
+    pub stopped: bool,
+}
+
+// Callback that receives the events of one batch
+pub type ProgressCallback<'a> = &'a mut dyn FnMut(ProgressEvent<'_>);
+
+// The handler used when no callback is given: the batch summary goes through
+// the logger (stderr), so it never ends up in an --output file or in data
+// written to stdout
+pub fn log_progress(event: ProgressEvent<'_>) {
+    match event {
+        ProgressEvent::FileStarted { path } => debug!("Processing {}", path.display()),
+        ProgressEvent::FileFinished { .. } => {}
+        ProgressEvent::BatchFinished { summary } => {
+            info!(
+                "Summary: {} scanned, {} {}, {} skipped, {} failed in {}",
+                summary.scanned,
+                summary.processed,
+                summary.action,
+                summary.skipped,
+                summary.failed.len(),
+                format_elapsed(summary.elapsed)
+            );
+            for path in &summary.failed {
+                info!("  Failed: {}", path.display());
+            }
+            if summary.stopped {
+                info!("Stopped at the first failure (--fail-fast); remaining files were not processed");
+            }
+        }
+    }
+}
+
+// "4.2s" below a minute, "12m 03.5s" above
+fn format_elapsed(elapsed: Duration) -> String {
+    let tenths = (elapsed.as_secs_f64() * 10.0).round() as u64;
+    if tenths < 600 {
+        format!("{}.{}s", tenths / 10, tenths % 10)
+    } else {
+        let rest = tenths % 600;
+        format!("{}m {:02}.{}s", tenths / 600, rest / 10, rest % 10)
+    }
+}
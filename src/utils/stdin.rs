@@ -0,0 +1,77 @@
+use super::{temp_dir_path, WalkOptions};
+use crate::error::AudioToolsError;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+// A WAV stream read from stdin ("-i -"), saved to a temporary file so it can be
+// decoded and probed like any other input. The file is removed on drop
+pub struct StdinWav {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl StdinWav {
+    pub fn read() -> Result<Self, AudioToolsError> {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(AudioToolsError::UnsupportedFormat(
+                "stdin must be a WAV stream".to_string(),
+            ));
+        }
+        fix_streamed_sizes(&mut bytes);
+
+        let dir = temp_dir_path();
+        let stdin = Self {
+            path: dir.join("stdin.wav"),
+            dir,
+        };
+        fs::create_dir_all(&stdin.dir)?;
+        fs::write(&stdin.path, &bytes)?;
+        Ok(stdin)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Walk options that report the file as "-"
+    pub fn walk_options(&self, walk: &WalkOptions) -> WalkOptions {
+        let mut walk = walk.clone();
+        walk.source_roots
+            .push((self.path.clone(), PathBuf::from("-")));
+        walk
+    }
+}
+
+impl Drop for StdinWav {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// A writer that can't seek back (e.g. ffmpeg writing to a pipe) leaves the RIFF
+// and data sizes as 0 or 0xFFFFFFFF. Set them from the bytes actually received
+// so the file decodes to its full length
+fn fix_streamed_sizes(bytes: &mut [u8]) {
+    let read_u32 = |bytes: &[u8], at: usize| {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    };
+
+    let mut position = 12;
+    while position + 8 <= bytes.len() {
+        let size = read_u32(bytes, position + 4) as usize;
+        let remaining = bytes.len() - position - 8;
+        if &bytes[position..position + 4] == b"data" {
+            if size == 0 || size > remaining {
+                let size = remaining.min(u32::MAX as usize) as u32;
+                bytes[position + 4..position + 8].copy_from_slice(&size.to_le_bytes());
+                let riff = (bytes.len() - 8).min(u32::MAX as usize) as u32;
+                bytes[4..8].copy_from_slice(&riff.to_le_bytes());
+            }
+            return;
+        }
+        position += 8 + size + (size & 1);
+    }
+}
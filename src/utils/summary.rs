@@ -0,0 +1,107 @@
+use super::progress::{log_progress, BatchReport, FileOutcome, ProgressCallback, ProgressEvent};
+use super::WalkOptions;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// Per-file outcome counts of a batch command, reported once all files are done.
+// Each outcome is also sent to the progress callback, if one was given
+pub struct BatchSummary<'a> {
+    started: Instant,
+    scanned: usize,
+    processed: usize,
+    skipped: usize,
+    failed: Vec<PathBuf>,
+    fail_fast: bool,
+    progress: Option<ProgressCallback<'a>>,
+}
+
+impl<'a> BatchSummary<'a> {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            scanned: 0,
+            processed: 0,
+            skipped: 0,
+            failed: Vec::new(),
+            fail_fast: false,
+            progress: None,
+        }
+    }
+
+    // A summary for a walk that stops at the first failed file with --fail-fast
+    pub fn for_walk(walk: &WalkOptions) -> Self {
+        Self {
+            fail_fast: walk.fail_fast,
+            ..Self::new()
+        }
+    }
+
+    // Send events to progress instead of logging the summary
+    pub fn with_progress(mut self, progress: Option<ProgressCallback<'a>>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    // True once a file has failed under --fail-fast; the caller then stops
+    // processing the remaining files
+    pub fn should_stop(&self) -> bool {
+        self.fail_fast && self.has_failures()
+    }
+
+    pub fn started(&mut self, path: &Path) {
+        self.emit(ProgressEvent::FileStarted { path });
+    }
+
+    pub fn processed(&mut self, path: &Path) {
+        self.scanned += 1;
+        self.processed += 1;
+        self.finished(path, FileOutcome::Processed);
+    }
+
+    pub fn skipped(&mut self, path: &Path) {
+        self.scanned += 1;
+        self.skipped += 1;
+        self.finished(path, FileOutcome::Skipped);
+    }
+
+    pub fn failed(&mut self, path: &Path) {
+        self.scanned += 1;
+        self.failed.push(path.to_path_buf());
+        self.finished(path, FileOutcome::Failed);
+    }
+
+    pub fn has_failures(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    // `done` names what happened to processed files, e.g. "converted"
+    pub fn report(&mut self, done: &str) {
+        let summary = BatchReport {
+            action: done.to_string(),
+            scanned: self.scanned,
+            processed: self.processed,
+            skipped: self.skipped,
+            failed: self.failed.clone(),
+            elapsed: self.started.elapsed(),
+            stopped: self.should_stop(),
+        };
+        self.emit(ProgressEvent::BatchFinished { summary });
+    }
+
+    fn finished(&mut self, path: &Path, result: FileOutcome) {
+        self.emit(ProgressEvent::FileFinished { path, result });
+    }
+
+    fn emit(&mut self, event: ProgressEvent<'_>) {
+        match &mut self.progress {
+            Some(progress) => progress(event),
+            None => log_progress(event),
+        }
+    }
+}
+
+impl Default for BatchSummary<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,3 +1,7 @@
+use crate::error::AudioToolsError;
+use std::fmt;
+use std::ops::Range;
+
 #[derive(Clone, Debug)]
 pub enum TimeSpecification {
     Seconds(f32),             // 秒指定
@@ -5,6 +9,28 @@ pub enum TimeSpecification {
     Percentage(f32),          // パーセンテージ指定
 }
 
+impl TimeSpecification {
+    // 全体の長さを基準に秒へ変換する
+    pub fn to_seconds(&self, total_duration: f32) -> f32 {
+        match self {
+            TimeSpecification::Seconds(s) => *s,
+            TimeSpecification::MinutesSeconds(m, s) => *m as f32 * 60.0 + *s as f32,
+            TimeSpecification::Percentage(p) => total_duration * p,
+        }
+    }
+}
+
+// エラーメッセージ用に指定されたままの形で表示する
+impl fmt::Display for TimeSpecification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSpecification::Seconds(s) => write!(f, "{}s", s),
+            TimeSpecification::MinutesSeconds(m, s) => write!(f, "{}:{:02}", m, s),
+            TimeSpecification::Percentage(p) => write!(f, "{:.1}%", p * 100.0),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct TimeRange {
     pub start: TimeSpecification,
@@ -12,29 +38,30 @@ pub struct TimeRange {
 }
 
 impl TimeRange {
-    pub fn resolve(&self, total_duration: f32) -> Result<(f32, f32), String> {
-        let convert_to_seconds = |spec: &TimeSpecification, total: f32| -> Result<f32, String> {
-            match spec {
-                TimeSpecification::Seconds(s) => Ok(*s),
-                TimeSpecification::MinutesSeconds(m, s) => Ok(*m as f32 * 60.0 + *s as f32),
-                TimeSpecification::Percentage(p) => Ok(total * p),
-            }
-        };
-
-        let start_time = convert_to_seconds(&self.start, total_duration)?;
-        let end_time = convert_to_seconds(&self.end, total_duration)?;
+    // 秒に変換して検査する。パーセンテージなどプログラムから直接作られた指定も
+    // CLIからの指定と同じく、ファイルの範囲（0〜長さ）に収まらなければエラーにする
+    pub fn resolve(&self, total_duration: f32) -> Result<(f32, f32), AudioToolsError> {
+        let start_time = self.start.to_seconds(total_duration);
+        let end_time = self.end.to_seconds(total_duration);
 
-        // 妥当性チェック
-        if start_time >= end_time {
-            return Err("Start time must be less than end time".to_string());
-        }
-        if start_time < 0.0 {
-            return Err("Start time must be positive".to_string());
+        for (label, spec, time) in [
+            ("Start", &self.start, start_time),
+            ("End", &self.end, end_time),
+        ] {
+            if !(0.0..=total_duration).contains(&time) {
+                let resolved = match spec {
+                    TimeSpecification::Seconds(_) => String::new(),
+                    _ => format!(" ({:.3}s)", time),
+                };
+                return Err(AudioToolsError::TimeRange(format!(
+                    "{} time {}{} is outside the audio duration (0s - {:.3}s)",
+                    label, spec, resolved, total_duration
+                )));
+            }
         }
-        if end_time > total_duration {
-            return Err(format!(
-                "End time ({}) exceeds audio duration ({})",
-                end_time, total_duration
+        if start_time >= end_time {
+            return Err(AudioToolsError::TimeRange(
+                "Start time must be less than end time".to_string(),
             ));
         }
 
@@ -42,6 +69,25 @@ impl TimeRange {
     }
 }
 
+// 秒単位の範囲をサンプル位置に変換する。丸めで末尾を超えないよう len に収め、
+// 範囲が空になる場合はエラーにする
+pub fn sample_range(
+    start_time: f32,
+    end_time: f32,
+    sample_rate: f32,
+    len: usize,
+) -> Result<Range<usize>, AudioToolsError> {
+    let start = ((start_time * sample_rate) as usize).min(len);
+    let end = ((end_time * sample_rate) as usize).min(len);
+    if start >= end {
+        return Err(AudioToolsError::TimeRange(format!(
+            "Selected range ({}s - {}s) contains no samples",
+            start_time, end_time
+        )));
+    }
+    Ok(start..end)
+}
+
 pub fn create_time_range(
     start: Option<TimeSpecification>,
     end: Option<TimeSpecification>,
@@ -63,7 +109,7 @@ pub fn parse_time_specification(time_str: &str) -> Result<TimeSpecification, Str
             .trim_end_matches('%')
             .parse::<f32>()
             .map_err(|_| "Invalid percentage format")?;
-        if percentage < 0.0 || percentage > 100.0 {
+        if !(0.0..=100.0).contains(&percentage) {
             return Err("Percentage must be between 0 and 100".to_string());
         }
         Ok(TimeSpecification::Percentage(percentage / 100.0))
@@ -88,3 +134,50 @@ pub fn parse_time_specification(time_str: &str) -> Result<TimeSpecification, Str
         Ok(TimeSpecification::Seconds(seconds))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_range_of_full_duration() {
+        assert_eq!(sample_range(0.0, 1.0, 48000.0, 48000).unwrap(), 0..48000);
+    }
+
+    #[test]
+    fn sample_range_past_the_end() {
+        // 末尾を1サンプル超える範囲は末尾までに収める
+        let past_end = 48001.0 / 48000.0;
+        assert_eq!(
+            sample_range(0.5, past_end, 48000.0, 48000).unwrap(),
+            24000..48000
+        );
+        // 末尾から始まる範囲にはサンプルがない
+        assert!(sample_range(1.0, past_end, 48000.0, 48000).is_err());
+    }
+
+    #[test]
+    fn resolve_rejects_out_of_range_percentage() {
+        // parse_time_specification を通さずに作った 0〜100% 外の指定
+        let range = TimeRange {
+            start: TimeSpecification::Seconds(0.0),
+            end: TimeSpecification::Percentage(1.5),
+        };
+        assert!(matches!(
+            range.resolve(10.0),
+            Err(AudioToolsError::TimeRange(message)) if message.contains("End time 150.0% (15.000s)")
+        ));
+
+        let range = TimeRange {
+            start: TimeSpecification::Percentage(-0.1),
+            end: TimeSpecification::Percentage(1.0),
+        };
+        assert!(range.resolve(10.0).is_err());
+        // 範囲内のパーセンテージは秒に変換される
+        let range = TimeRange {
+            start: TimeSpecification::Percentage(0.25),
+            end: TimeSpecification::Percentage(1.0),
+        };
+        assert_eq!(range.resolve(10.0).unwrap(), (2.5, 10.0));
+    }
+}
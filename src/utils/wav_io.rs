@@ -0,0 +1,88 @@
+use crate::utils::decode::normalize_sample;
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::path::Path;
+
+// Interleaved samples in the file's native representation, so that writing
+// them back preserves bit depth and sample format exactly
+pub enum WavSamples {
+    Int(Vec<i32>),
+    Float(Vec<f32>),
+}
+
+pub struct WavData {
+    pub spec: WavSpec,
+    pub samples: WavSamples,
+}
+
+impl WavData {
+    pub fn read(path: &Path) -> Result<Self, hound::Error> {
+        let mut reader = WavReader::open(path)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            SampleFormat::Float => {
+                WavSamples::Float(reader.samples::<f32>().collect::<Result<_, _>>()?)
+            }
+            SampleFormat::Int => {
+                WavSamples::Int(reader.samples::<i32>().collect::<Result<_, _>>()?)
+            }
+        };
+        Ok(WavData { spec, samples })
+    }
+
+    // Number of sample frames (one sample per channel)
+    pub fn frames(&self) -> usize {
+        let len = match &self.samples {
+            WavSamples::Int(samples) => samples.len(),
+            WavSamples::Float(samples) => samples.len(),
+        };
+        len / self.spec.channels as usize
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.spec.sample_rate as f32
+    }
+
+    // Downmix to mono f32 in -1.0..1.0, as used for level and onset detection
+    pub fn mono(&self) -> Vec<f32> {
+        let channels = self.spec.channels as usize;
+        match &self.samples {
+            WavSamples::Float(samples) => samples
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                .collect(),
+            WavSamples::Int(samples) => {
+                let bits = self.spec.bits_per_sample;
+                samples
+                    .chunks(channels)
+                    .map(|frame| {
+                        frame
+                            .iter()
+                            .map(|&s| normalize_sample(s, bits))
+                            .sum::<f32>()
+                            / channels as f32
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Write frames start..end to a new file with the same spec
+    pub fn write_frames(&self, path: &Path, start: usize, end: usize) -> Result<(), hound::Error> {
+        let channels = self.spec.channels as usize;
+        let (start, end) = (start * channels, end.min(self.frames()) * channels);
+        let mut writer = WavWriter::create(path, self.spec)?;
+        match &self.samples {
+            WavSamples::Int(samples) => {
+                for &sample in &samples[start..end] {
+                    writer.write_sample(sample)?;
+                }
+            }
+            WavSamples::Float(samples) => {
+                for &sample in &samples[start..end] {
+                    writer.write_sample(sample)?;
+                }
+            }
+        }
+        writer.finalize()
+    }
+}
@@ -1,8 +1,8 @@
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WavHeader {
     chunk_id: [u8; 4],
     chunk_size: u32,
@@ -17,7 +17,78 @@ pub struct WavHeader {
     bits_per_sample: u16,
 }
 
+// A chunk found while walking a RIFF/WAVE file
+#[derive(Debug, Clone)]
+pub struct RiffChunk {
+    pub id: [u8; 4],
+    // Byte offset of the chunk payload (just after the 8-byte chunk header)
+    pub offset: u64,
+    // Payload size in bytes, excluding the pad byte of odd-sized chunks
+    pub size: u32,
+    // Size written in the chunk header. Larger than `size` when the chunk
+    // runs past the end of the file
+    pub declared_size: u32,
+}
+
+impl RiffChunk {
+    pub fn id_str(&self) -> String {
+        String::from_utf8_lossy(&self.id).into_owned()
+    }
+}
+
 impl WavHeader {
+    // Walk every top-level chunk after the RIFF header. Chunks such as JUNK,
+    // bext or LIST may appear before "fmt ", so fields must not be read at
+    // fixed offsets.
+    pub fn read_chunks(file: &mut File) -> Result<Vec<RiffChunk>, std::io::Error> {
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut riff = [0; 4];
+        let mut wave = [0; 4];
+        file.read_exact(&mut riff)?;
+        file.read_u32::<LittleEndian>()?;
+        file.read_exact(&mut wave)?;
+        if &riff != b"RIFF" || &wave != b"WAVE" {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Not a RIFF/WAVE file",
+            ));
+        }
+
+        let file_len = file.metadata()?.len();
+        let mut chunks = Vec::new();
+        let mut position = 12_u64;
+
+        while position + 8 <= file_len {
+            file.seek(SeekFrom::Start(position))?;
+            let mut id = [0; 4];
+            file.read_exact(&mut id)?;
+            let declared_size = file.read_u32::<LittleEndian>()?;
+            let offset = position + 8;
+
+            // Streamed or truncated files may declare more data than exists
+            let size = declared_size.min((file_len - offset).min(u32::MAX as u64) as u32);
+            chunks.push(RiffChunk {
+                id,
+                offset,
+                size,
+                declared_size,
+            });
+
+            // Chunks are word-aligned: odd sizes are followed by a pad byte
+            position = offset + size as u64 + (size as u64 & 1);
+        }
+
+        Ok(chunks)
+    }
+
+    // Find the first chunk with the given id
+    pub fn find_chunk(file: &mut File, id: &[u8; 4]) -> Result<Option<RiffChunk>, std::io::Error> {
+        Ok(Self::read_chunks(file)?
+            .into_iter()
+            .find(|chunk| &chunk.id == id))
+    }
+
     pub fn read_from_file(file: &mut File) -> Result<Self, std::io::Error> {
         let mut header = WavHeader {
             chunk_id: [0; 4],
@@ -33,11 +104,18 @@ impl WavHeader {
             bits_per_sample: 0,
         };
 
+        let fmt = Self::find_chunk(file, b"fmt ")?.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "fmt chunk not found")
+        })?;
+
+        file.seek(SeekFrom::Start(0))?;
         file.read_exact(&mut header.chunk_id)?;
         header.chunk_size = file.read_u32::<LittleEndian>()?;
         file.read_exact(&mut header.format)?;
-        file.read_exact(&mut header.subchunk1_id)?;
-        header.subchunk1_size = file.read_u32::<LittleEndian>()?;
+
+        header.subchunk1_id = fmt.id;
+        header.subchunk1_size = fmt.size;
+        file.seek(SeekFrom::Start(fmt.offset))?;
         header.audio_format = file.read_u16::<LittleEndian>()?;
         header.num_channels = file.read_u16::<LittleEndian>()?;
         header.sample_rate = file.read_u32::<LittleEndian>()?;
@@ -48,6 +126,31 @@ impl WavHeader {
         Ok(header)
     }
 
+    pub fn num_channels(&self) -> u16 {
+        self.num_channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        self.bits_per_sample
+    }
+
+    pub fn block_align(&self) -> u16 {
+        self.block_align
+    }
+
+    // Length in seconds of a data chunk of `data_size` bytes
+    pub fn duration_seconds(&self, data_size: u32) -> Option<f64> {
+        if self.block_align == 0 || self.sample_rate == 0 {
+            return None;
+        }
+        let frames = data_size / self.block_align as u32;
+        Some(frames as f64 / self.sample_rate as f64)
+    }
+
     pub fn format_info(&self) -> String {
         format!(
             "WAV Header Information:\n\
@@ -76,3 +179,93 @@ impl WavHeader {
         )
     }
 }
+
+// Broadcast Wave Format "bext" chunk (EBU Tech 3285)
+#[derive(Debug, Clone)]
+pub struct BroadcastExtension {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    pub origination_date: String,
+    pub origination_time: String,
+    // Sample count since midnight of the first sample in the file
+    pub time_reference: u64,
+    pub version: u16,
+    pub coding_history: String,
+}
+
+impl BroadcastExtension {
+    // Fixed part of the chunk before the variable-length coding history
+    const FIXED_SIZE: usize = 602;
+
+    // Read the bext chunk if the file has one
+    pub fn read_from_file(file: &mut File) -> Result<Option<Self>, std::io::Error> {
+        let chunk = match WavHeader::find_chunk(file, b"bext")? {
+            Some(chunk) if chunk.size as usize >= Self::FIXED_SIZE => chunk,
+            _ => return Ok(None),
+        };
+
+        let mut data = vec![0; chunk.size as usize];
+        file.seek(SeekFrom::Start(chunk.offset))?;
+        file.read_exact(&mut data)?;
+
+        // Text fields are fixed-size ASCII, padded with NULs
+        let text = |range: std::ops::Range<usize>| -> String {
+            let bytes = &data[range];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+        };
+        let mut reference = &data[338..348];
+
+        Ok(Some(BroadcastExtension {
+            description: text(0..256),
+            originator: text(256..288),
+            originator_reference: text(288..320),
+            origination_date: text(320..330),
+            origination_time: text(330..338),
+            time_reference: reference.read_u64::<LittleEndian>()?,
+            version: reference.read_u16::<LittleEndian>()?,
+            coding_history: text(Self::FIXED_SIZE..data.len()),
+        }))
+    }
+
+    pub fn format_info(&self, sample_rate: u32) -> String {
+        let mut info = format!(
+            "Broadcast Info:\n\
+             Description: {}\n\
+             Originator: {}\n\
+             Originator Reference: {}\n\
+             Origination Date: {}\n\
+             Origination Time: {}\n\
+             Time Reference: {} samples",
+            self.description,
+            self.originator,
+            self.originator_reference,
+            self.origination_date,
+            self.origination_time,
+            self.time_reference,
+        );
+
+        // Show the timeline position as HH:MM:SS.mmm when the rate is known
+        if sample_rate > 0 {
+            let seconds = self.time_reference as f64 / sample_rate as f64;
+            info.push_str(&format!(
+                " ({:02}:{:02}:{:06.3})",
+                (seconds / 3600.0) as u64,
+                (seconds % 3600.0 / 60.0) as u64,
+                seconds % 60.0
+            ));
+        }
+        info.push_str(&format!("\nBWF Version: {}\n", self.version));
+
+        if !self.coding_history.is_empty() {
+            info.push_str(&format!(
+                "Coding History: {}\n",
+                self.coding_history
+                    .replace("\r\n", "; ")
+                    .trim_end_matches("; ")
+            ));
+        }
+        info
+    }
+}